@@ -1,25 +1,173 @@
-use std::{collections::HashMap, fmt::Display, iter::Peekable, str::Chars};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    iter::Peekable,
+    str::Chars,
+};
 
 type Id = String;
 type Tag = String;
 
+/// Keywords of mutation expressions, which cannot name a variant or a variation
+pub const RESERVED_NAMES: &[&str] = &["base", "choose", "upto"];
+
+/// Largest number of products `choose` and `upto` may enumerate
+pub const MAX_COMBINATIONS: usize = 100_000;
+
 pub fn compute_mutations(
     expr: &str,
     tag_map: &HashMap<String, Vec<String>>,
     variation_map: &HashMap<String, Vec<String>>,
     variant_list: &[String],
 ) -> anyhow::Result<Vec<Vec<String>>> {
-    let mut parser = Parser::new(expr);
-    let expr = parser.parse();
+    let mut parser = Parser::new(expr)?;
+    let expr = parser.parse()?;
     expr.into_sum_of_products(tag_map, variation_map, variant_list)
 }
 
+/// What to do with a term that selects several variants of the same variation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Leave the term out and report it
+    #[default]
+    Drop,
+    /// Fail the expansion
+    Error,
+}
+
+/// Terms of an expansion after repeated variants, duplicate terms and conflicts are resolved.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Resolution {
+    /// The terms that can be applied, in enumeration order
+    pub terms: Vec<Vec<String>>,
+    /// Terms left out because they select several variants of one variation
+    pub dropped: Vec<Vec<String>>,
+    /// Number of terms left out because an earlier term selects the same variants
+    pub duplicates: usize,
+}
+
+/// Makes every term applicable: repeated variants within a term are merged, terms selecting
+/// the same variants as an earlier one are removed, and terms selecting several variants of
+/// one variation of `variation_map` are dropped or rejected according to `policy`
+pub fn resolve_conflicts(
+    terms: Vec<Vec<String>>,
+    variation_map: &HashMap<String, Vec<String>>,
+    policy: ConflictPolicy,
+) -> anyhow::Result<Resolution> {
+    let mut variations_of: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (variation, variants) in variation_map {
+        for variant in variants {
+            variations_of
+                .entry(variant.as_str())
+                .or_default()
+                .push(variation.as_str());
+        }
+    }
+    for variations in variations_of.values_mut() {
+        variations.sort_unstable();
+        variations.dedup();
+    }
+
+    let mut resolution = Resolution::default();
+    let mut seen = HashSet::new();
+    for term in terms {
+        let mut unique: Vec<String> = vec![];
+        for variant in term {
+            if !unique.contains(&variant) {
+                unique.push(variant);
+            }
+        }
+
+        if let Some((variation, selected)) = conflict(&unique, &variations_of) {
+            match policy {
+                ConflictPolicy::Drop => {
+                    log::warn!(
+                        "dropping term {:?}, it selects several variants of variation '{}': {:?}",
+                        unique,
+                        variation,
+                        selected
+                    );
+                    resolution.dropped.push(unique);
+                    continue;
+                }
+                ConflictPolicy::Error => anyhow::bail!(
+                    "term {:?} selects several variants of variation '{}' {:?}, but only one variant of a variation can be active at a time",
+                    unique,
+                    variation,
+                    selected
+                ),
+            }
+        }
+
+        if !seen.insert(term_key(&unique)) {
+            resolution.duplicates += 1;
+            continue;
+        }
+        resolution.terms.push(unique);
+    }
+    Ok(resolution)
+}
+
+/// Returns the first variation (in name order) from which `term` selects several variants,
+/// with those variants in term order. `variations_of` maps each variant to the variations
+/// having it.
+fn conflict(
+    term: &[String],
+    variations_of: &HashMap<&str, Vec<&str>>,
+) -> Option<(String, Vec<String>)> {
+    let mut selected: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for variant in term {
+        for variation in variations_of.get(variant.as_str()).into_iter().flatten() {
+            selected.entry(variation).or_default().push(variant.clone());
+        }
+    }
+    selected
+        .into_iter()
+        .find(|(_, selected)| selected.len() > 1)
+        .map(|(variation, selected)| (variation.to_string(), selected))
+}
+
+/// The successive forms of a mutation expression while it is expanded into terms.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expansion {
+    /// The parsed expression, fully parenthesized
+    pub parsed: String,
+    /// The expression after `+tag` and `*tag` are replaced by the tagged variations
+    pub tags_distributed: String,
+    /// The expression after variations are replaced by the sum of their variants
+    pub variations_distributed: String,
+    /// The resulting sum of products, each term a list of variants
+    pub terms: Vec<Vec<String>>,
+}
+
+/// Expands a mutation expression like [`compute_mutations`], keeping the intermediate forms
+pub fn expand(
+    expr: &str,
+    tag_map: &HashMap<String, Vec<String>>,
+    variation_map: &HashMap<String, Vec<String>>,
+    variant_list: &[String],
+) -> anyhow::Result<Expansion> {
+    let mut parser = Parser::new(expr)?;
+    let expr = parser.parse()?;
+    expr.expand(tag_map, variation_map, variant_list)
+}
+
 #[derive(Debug, PartialEq)]
 pub(crate) enum Expr {
     Sum(Box<Expr>, Box<Expr>),
     Product(Box<Expr>, Box<Expr>),
+    /// Terms of the left side that do not include any term of the right side
+    Difference(Box<Expr>, Box<Expr>),
+    /// Terms that appear on both sides
+    Intersection(Box<Expr>, Box<Expr>),
+    /// Products of exactly `k` distinct terms of the expression
+    Choose(usize, Box<Expr>),
+    /// Products of at most `k` distinct terms of the expression
+    UpTo(usize, Box<Expr>),
     USum(Tag),
     UProduct(Tag),
+    /// The empty product, i.e. the base program
+    Base,
     Id(Id),
 }
 
@@ -28,8 +176,13 @@ impl Display for Expr {
         match self {
             Expr::Sum(lhs, rhs) => write!(f, "({} + {})", lhs, rhs),
             Expr::Product(lhs, rhs) => write!(f, "({} * {})", lhs, rhs),
+            Expr::Difference(lhs, rhs) => write!(f, "({} - {})", lhs, rhs),
+            Expr::Intersection(lhs, rhs) => write!(f, "({} & {})", lhs, rhs),
+            Expr::Choose(k, expr) => write!(f, "choose({}, {})", k, expr),
+            Expr::UpTo(k, expr) => write!(f, "upto({}, {})", k, expr),
             Expr::USum(tag) => write!(f, "+{}", tag),
             Expr::UProduct(tag) => write!(f, "*{}", tag),
+            Expr::Base => write!(f, "base"),
             Expr::Id(id) => write!(f, "{}", id),
         }
     }
@@ -43,114 +196,157 @@ impl Expr {
     /// @variant_list: A complete list of all variant ids
     /// @return: A list of lists of ids that represent the sum of products
     fn into_sum_of_products(
-        &self,
+        self,
         tag_map: &HashMap<String, Vec<String>>,
         variation_map: &HashMap<String, Vec<String>>,
         variant_list: &[String],
     ) -> anyhow::Result<Vec<Vec<String>>> {
+        self.expand(tag_map, variation_map, variant_list)
+            .map(|expansion| expansion.terms)
+    }
+
+    fn expand(
+        self,
+        tag_map: &HashMap<String, Vec<String>>,
+        variation_map: &HashMap<String, Vec<String>>,
+        variant_list: &[String],
+    ) -> anyhow::Result<Expansion> {
         // Distributes tags +t into (t1 + t2 + ... + tn) and *t into (t1 * t2 * ... * tn)
-        let tagless_expr = self.distribute_tags(tag_map);
+        let tagless_expr = self.distribute_tags(tag_map)?;
         // Distributes variation v into a sum of its alternatives
-        let variation_distributed = tagless_expr.distribute_variations(variation_map);
+        let variation_distributed = tagless_expr.distribute_variations(variation_map)?;
+        // Keywords would shadow the variants and variations of the same name it selects
+        let mut names = vec![];
+        tagless_expr.collect_variants(&mut names);
+        variation_distributed.collect_variants(&mut names);
+        if let Some(name) = names
+            .iter()
+            .find(|name| RESERVED_NAMES.contains(&name.as_str()))
+        {
+            anyhow::bail!(
+                "'{}' is a keyword of mutation expressions and cannot name a variant or variation, rename it",
+                name
+            );
+        }
         // Check that all the variants in the expression are in the variant list
         let mut variants = vec![];
         variation_distributed.collect_variants(&mut variants);
         for variant in variants.iter() {
             if !variant_list.contains(variant) {
-                anyhow::bail!("Variant {} is not in the variant list", variant);
+                anyhow::bail!("unknown variant or variation '{}'", variant);
             }
         }
-        let variation_distributed = variation_distributed.distribute();
+        let terms = variation_distributed.distribute()?;
 
-        Ok(variation_distributed)
+        Ok(Expansion {
+            parsed: self.to_string(),
+            tags_distributed: tagless_expr.to_string(),
+            variations_distributed: variation_distributed.to_string(),
+            terms,
+        })
     }
 
     fn collect_variants(&self, variants: &mut Vec<String>) {
         match self {
-            Expr::Sum(lhs, rhs) => {
-                lhs.collect_variants(variants);
-                rhs.collect_variants(variants);
-            }
-            Expr::Product(lhs, rhs) => {
+            Expr::Sum(lhs, rhs)
+            | Expr::Product(lhs, rhs)
+            | Expr::Difference(lhs, rhs)
+            | Expr::Intersection(lhs, rhs) => {
                 lhs.collect_variants(variants);
                 rhs.collect_variants(variants);
             }
+            Expr::Choose(_, expr) | Expr::UpTo(_, expr) => expr.collect_variants(variants),
             Expr::USum(_) | Expr::UProduct(_) => {
                 unreachable!("Unary expressions are elimiated in [distributed_tags] phase")
             }
+            Expr::Base => {}
             Expr::Id(id) => {
                 variants.push(id.clone());
             }
         }
     }
 
-    fn distribute_tags(&self, tag_map: &HashMap<String, Vec<String>>) -> Expr {
-        match self {
+    fn distribute_tags(&self, tag_map: &HashMap<String, Vec<String>>) -> anyhow::Result<Expr> {
+        Ok(match self {
             Expr::Sum(lhs, rhs) => {
-                let lhs = lhs.distribute_tags(tag_map);
-                let rhs = rhs.distribute_tags(tag_map);
+                let lhs = lhs.distribute_tags(tag_map)?;
+                let rhs = rhs.distribute_tags(tag_map)?;
                 Expr::Sum(Box::new(lhs), Box::new(rhs))
             }
             Expr::Product(lhs, rhs) => {
-                let lhs = lhs.distribute_tags(tag_map);
-                let rhs = rhs.distribute_tags(tag_map);
+                let lhs = lhs.distribute_tags(tag_map)?;
+                let rhs = rhs.distribute_tags(tag_map)?;
                 Expr::Product(Box::new(lhs), Box::new(rhs))
             }
-            Expr::USum(tag) => {
-                let ids = vec![];
-                let ids = tag_map.get(tag).unwrap_or(&ids);
-                let mut sum = Expr::Id(ids[0].clone());
-                for id in ids.iter().skip(1) {
-                    sum = Expr::Sum(Box::new(sum), Box::new(Expr::Id(id.clone())));
-                }
-                sum
+            Expr::Difference(lhs, rhs) => {
+                let lhs = lhs.distribute_tags(tag_map)?;
+                let rhs = rhs.distribute_tags(tag_map)?;
+                Expr::Difference(Box::new(lhs), Box::new(rhs))
             }
-            Expr::UProduct(tag) => {
-                let ids = vec![];
-                let ids = tag_map.get(tag).unwrap_or(&ids);
-                let mut product = Expr::Id(ids[0].clone());
-                for id in ids.iter().skip(1) {
-                    product = Expr::Product(Box::new(product), Box::new(Expr::Id(id.clone())));
-                }
-                product
+            Expr::Intersection(lhs, rhs) => {
+                let lhs = lhs.distribute_tags(tag_map)?;
+                let rhs = rhs.distribute_tags(tag_map)?;
+                Expr::Intersection(Box::new(lhs), Box::new(rhs))
             }
+            Expr::Choose(k, expr) => Expr::Choose(*k, Box::new(expr.distribute_tags(tag_map)?)),
+            Expr::UpTo(k, expr) => Expr::UpTo(*k, Box::new(expr.distribute_tags(tag_map)?)),
+            Expr::USum(tag) => fold_ids(tagged_ids(tag, tag_map)?, Expr::Sum),
+            Expr::UProduct(tag) => fold_ids(tagged_ids(tag, tag_map)?, Expr::Product),
+            Expr::Base => Expr::Base,
             Expr::Id(id) => Expr::Id(id.clone()),
-        }
+        })
     }
 
-    fn distribute_variations(&self, variation_map: &HashMap<String, Vec<String>>) -> Expr {
-        match self {
+    fn distribute_variations(
+        &self,
+        variation_map: &HashMap<String, Vec<String>>,
+    ) -> anyhow::Result<Expr> {
+        Ok(match self {
             Expr::Sum(lhs, rhs) => {
-                let lhs = lhs.distribute_variations(variation_map);
-                let rhs = rhs.distribute_variations(variation_map);
+                let lhs = lhs.distribute_variations(variation_map)?;
+                let rhs = rhs.distribute_variations(variation_map)?;
                 Expr::Sum(Box::new(lhs), Box::new(rhs))
             }
             Expr::Product(lhs, rhs) => {
-                let lhs = lhs.distribute_variations(variation_map);
-                let rhs = rhs.distribute_variations(variation_map);
+                let lhs = lhs.distribute_variations(variation_map)?;
+                let rhs = rhs.distribute_variations(variation_map)?;
                 Expr::Product(Box::new(lhs), Box::new(rhs))
             }
+            Expr::Difference(lhs, rhs) => {
+                let lhs = lhs.distribute_variations(variation_map)?;
+                let rhs = rhs.distribute_variations(variation_map)?;
+                Expr::Difference(Box::new(lhs), Box::new(rhs))
+            }
+            Expr::Intersection(lhs, rhs) => {
+                let lhs = lhs.distribute_variations(variation_map)?;
+                let rhs = rhs.distribute_variations(variation_map)?;
+                Expr::Intersection(Box::new(lhs), Box::new(rhs))
+            }
+            Expr::Choose(k, expr) => {
+                Expr::Choose(*k, Box::new(expr.distribute_variations(variation_map)?))
+            }
+            Expr::UpTo(k, expr) => {
+                Expr::UpTo(*k, Box::new(expr.distribute_variations(variation_map)?))
+            }
             Expr::USum(_) | Expr::UProduct(_) => {
                 unreachable!("Unary expressions are elimiated in [distributed_tags] phase")
             }
+            Expr::Base => Expr::Base,
             Expr::Id(id) => match variation_map.get(id) {
-                Some(ids) => {
-                    let mut sum = Expr::Id(ids[0].clone());
-                    for id in ids.iter().skip(1) {
-                        sum = Expr::Sum(Box::new(sum), Box::new(Expr::Id(id.clone())));
-                    }
-                    sum
+                Some(ids) if ids.is_empty() => {
+                    anyhow::bail!("variation '{}' has no variants", id)
                 }
+                Some(ids) => fold_ids(ids, Expr::Sum),
                 None => Expr::Id(id.clone()),
             },
-        }
+        })
     }
 
-    fn distribute(&self) -> Vec<Vec<String>> {
-        match self {
+    fn distribute(&self) -> anyhow::Result<Vec<Vec<String>>> {
+        Ok(match self {
             Expr::Product(lhs, rhs) => {
-                let lhs = lhs.distribute();
-                let rhs = rhs.distribute();
+                let lhs = lhs.distribute()?;
+                let rhs = rhs.distribute()?;
                 let mut result = vec![];
                 for l in lhs.iter() {
                     for r in rhs.iter() {
@@ -162,166 +358,406 @@ impl Expr {
                 result
             }
             Expr::Sum(lhs, rhs) => {
-                let mut lhs = lhs.distribute();
-                let rhs = rhs.distribute();
+                let mut lhs = lhs.distribute()?;
+                let rhs = rhs.distribute()?;
                 lhs.extend(rhs);
                 lhs
             }
+            Expr::Difference(lhs, rhs) => {
+                let mut lhs = lhs.distribute()?;
+                let rhs = rhs.distribute()?;
+                lhs.retain(|term| !rhs.iter().any(|excluded| excludes(excluded, term)));
+                lhs
+            }
+            Expr::Intersection(lhs, rhs) => {
+                let mut lhs = lhs.distribute()?;
+                let rhs: HashSet<Vec<String>> =
+                    rhs.distribute()?.iter().map(|t| term_key(t)).collect();
+                lhs.retain(|term| rhs.contains(&term_key(term)));
+                lhs
+            }
+            Expr::Choose(k, expr) => {
+                let terms = expr.distribute()?;
+                check_combinations(self, std::iter::once(binomial(terms.len(), *k)))?;
+                combinations(&terms, *k)
+            }
+            Expr::UpTo(k, expr) => {
+                let terms = expr.distribute()?;
+                check_combinations(self, (1..=*k).map(|size| binomial(terms.len(), size)))?;
+                (1..=*k)
+                    .flat_map(|size| combinations(&terms, size))
+                    .collect()
+            }
             Expr::USum(_) | Expr::UProduct(_) => {
                 unreachable!("Unary expressions are elimiated in [distributed_tags] phase")
             }
+            Expr::Base => vec![vec![]],
             Expr::Id(id) => vec![vec![id.clone()]],
+        })
+    }
+}
+
+/// Fails when `counts`, the number of products of each size `expr` selects, add up to more
+/// than [`MAX_COMBINATIONS`]
+fn check_combinations(
+    expr: &Expr,
+    mut counts: impl Iterator<Item = Option<usize>>,
+) -> anyhow::Result<()> {
+    let total = counts.try_fold(0usize, |total, count| total.checked_add(count?));
+    match total {
+        Some(total) if total <= MAX_COMBINATIONS => Ok(()),
+        _ => anyhow::bail!(
+            "'{}' selects more than {} terms, narrow the expression or lower k",
+            expr,
+            MAX_COMBINATIONS
+        ),
+    }
+}
+
+/// The number of ways to pick `k` of `n` items, `None` on overflow
+fn binomial(n: usize, k: usize) -> Option<usize> {
+    if k > n {
+        return Some(0);
+    }
+    (0..k.min(n - k)).try_fold(1usize, |count, i| Some(count.checked_mul(n - i)? / (i + 1)))
+}
+
+/// Whether `excluded` removes `term` in a difference: every variant of a non-empty
+/// `excluded` is in `term`, or both are the base program
+fn excludes(excluded: &[String], term: &[String]) -> bool {
+    if excluded.is_empty() {
+        return term.is_empty();
+    }
+    excluded.iter().all(|variant| term.contains(variant))
+}
+
+/// The variants a term activates, sorted and without repetitions, so that two terms activate
+/// the same set of variants exactly when their keys are equal
+fn term_key(term: &[String]) -> Vec<String> {
+    let mut key = term.to_vec();
+    key.sort_unstable();
+    key.dedup();
+    key
+}
+
+/// Products of every combination of `k` distinct terms, in enumeration order
+fn combinations(terms: &[Vec<String>], k: usize) -> Vec<Vec<String>> {
+    if k == 0 {
+        return vec![vec![]];
+    }
+    let mut result = vec![];
+    for (index, term) in terms.iter().enumerate() {
+        for rest in combinations(&terms[index + 1..], k - 1) {
+            let mut product = term.clone();
+            product.extend(rest);
+            result.push(product);
+        }
+    }
+    result
+}
+
+/// Returns the variations tagged with `tag`
+fn tagged_ids<'a>(
+    tag: &str,
+    tag_map: &'a HashMap<String, Vec<String>>,
+) -> anyhow::Result<&'a Vec<String>> {
+    match tag_map.get(tag) {
+        Some(ids) if !ids.is_empty() => Ok(ids),
+        _ => {
+            let mut tags: Vec<&String> = tag_map.keys().collect();
+            tags.sort();
+            if tags.is_empty() {
+                anyhow::bail!(
+                    "unknown tag '{}', no variation in the project is tagged",
+                    tag
+                )
+            }
+            anyhow::bail!(
+                "unknown tag '{}', known tags are: {}",
+                tag,
+                tags.iter()
+                    .map(|tag| tag.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
         }
     }
 }
 
+/// Folds a non-empty list of ids into a left-nested chain of `op`
+fn fold_ids(ids: &[String], op: fn(Box<Expr>, Box<Expr>) -> Expr) -> Expr {
+    let mut expr = Expr::Id(ids[0].clone());
+    for id in ids.iter().skip(1) {
+        expr = op(Box::new(expr), Box::new(Expr::Id(id.clone())));
+    }
+    expr
+}
+
 #[derive(Debug, PartialEq, Clone)]
 enum Token {
     Plus,
+    Minus,
     Star,
+    Ampersand,
+    Comma,
     OpenParen,
     CloseParen,
     Identifier(String),
 }
 
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Plus => write!(f, "'+'"),
+            Token::Minus => write!(f, "'-'"),
+            Token::Star => write!(f, "'*'"),
+            Token::Ampersand => write!(f, "'&'"),
+            Token::Comma => write!(f, "','"),
+            Token::OpenParen => write!(f, "'('"),
+            Token::CloseParen => write!(f, "')'"),
+            Token::Identifier(id) => write!(f, "'{}'", id),
+        }
+    }
+}
+
+/// Builds a syntax error pointing at `column` (1-based) of `input`
+fn syntax_error(input: &str, column: usize, message: &str) -> anyhow::Error {
+    let line: String = input
+        .chars()
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .collect();
+    anyhow::anyhow!(
+        "invalid mutation expression: {} at column {}\n  {}\n  {}^",
+        message,
+        column,
+        line,
+        " ".repeat(column.saturating_sub(1))
+    )
+}
+
 struct Lexer<'a> {
+    source: &'a str,
     input: Peekable<Chars<'a>>,
+    /// Column of the next character, starting from 1
+    column: usize,
 }
 
 impl<'a> Lexer<'a> {
     fn new(input: &'a str) -> Self {
         Self {
+            source: input,
             input: input.chars().peekable(),
+            column: 1,
         }
     }
 
-    fn next_token(&mut self) -> Option<Token> {
+    fn bump(&mut self) {
+        self.input.next();
+        self.column += 1;
+    }
+
+    /// Returns the next token along with its column, or `None` at the end of the input
+    fn next_token(&mut self) -> anyhow::Result<Option<(Token, usize)>> {
         while let Some(&c) = self.input.peek() {
-            match c {
-                '+' => {
-                    self.input.next();
-                    return Some(Token::Plus);
-                }
-                '*' => {
-                    self.input.next();
-                    return Some(Token::Star);
-                }
-                '(' => {
-                    self.input.next();
-                    return Some(Token::OpenParen);
-                }
-                ')' => {
-                    self.input.next();
-                    return Some(Token::CloseParen);
-                }
+            let column = self.column;
+            let token = match c {
+                '+' => Token::Plus,
+                '-' => Token::Minus,
+                '*' => Token::Star,
+                '&' => Token::Ampersand,
+                ',' => Token::Comma,
+                '(' => Token::OpenParen,
+                ')' => Token::CloseParen,
                 'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => {
                     let mut id = String::new();
                     while let Some(&ch) = self.input.peek() {
                         if ch.is_alphanumeric() || ch == '_' {
                             id.push(ch);
-                            self.input.next();
+                            self.bump();
                         } else {
                             break;
                         }
                     }
-                    return Some(Token::Identifier(id));
+                    return Ok(Some((Token::Identifier(id), column)));
                 }
-                ' ' | '\t' | '\n' => {
-                    self.input.next(); // Skip whitespace
+                c if c.is_whitespace() => {
+                    self.bump();
+                    continue;
                 }
-                _ => panic!("Unexpected character: {}", c),
-            }
+                _ => {
+                    return Err(syntax_error(
+                        self.source,
+                        column,
+                        &format!("unexpected character '{}'", c),
+                    ))
+                }
+            };
+            self.bump();
+            return Ok(Some((token, column)));
         }
-        None
+        Ok(None)
     }
 }
 
 struct Parser<'a> {
     lexer: Lexer<'a>,
     current_token: Option<Token>,
+    /// Column of the current token, or one past the end of the input
+    column: usize,
 }
 
 impl<'a> Parser<'a> {
-    fn new(input: &'a str) -> Self {
-        let mut lexer = Lexer::new(input);
-        let current_token = lexer.next_token();
-        Self {
-            lexer,
-            current_token,
+    fn new(input: &'a str) -> anyhow::Result<Self> {
+        let mut parser = Self {
+            lexer: Lexer::new(input),
+            current_token: None,
+            column: 1,
+        };
+        parser.consume()?;
+        Ok(parser)
+    }
+
+    fn consume(&mut self) -> anyhow::Result<()> {
+        match self.lexer.next_token()? {
+            Some((token, column)) => {
+                self.current_token = Some(token);
+                self.column = column;
+            }
+            None => {
+                self.current_token = None;
+                self.column = self.lexer.column;
+            }
         }
+        Ok(())
     }
 
-    fn consume(&mut self) {
-        self.current_token = self.lexer.next_token();
+    /// Builds an error for the current token, which was expected to be `expected`
+    fn unexpected(&self, expected: &str) -> anyhow::Error {
+        let found = match &self.current_token {
+            Some(token) => token.to_string(),
+            None => "end of expression".to_string(),
+        };
+        syntax_error(
+            self.lexer.source,
+            self.column,
+            &format!("expected {}, found {}", expected, found),
+        )
+    }
+
+    fn parse(&mut self) -> anyhow::Result<Expr> {
+        let expr = self.parse_sum()?;
+        if self.current_token.is_some() {
+            return Err(self.unexpected("an operator or end of expression"));
+        }
+        Ok(expr)
     }
 
-    fn parse(&mut self) -> Expr {
-        self.parse_sum()
+    fn parse_sum(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.parse_intersection()?;
+
+        loop {
+            let op = match self.current_token {
+                Some(Token::Plus) => Expr::Sum,
+                Some(Token::Minus) => Expr::Difference,
+                _ => break,
+            };
+            self.consume()?;
+            let right = self.parse_intersection()?;
+            left = op(Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
     }
 
-    fn parse_sum(&mut self) -> Expr {
-        let mut left = self.parse_product();
+    fn parse_intersection(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.parse_product()?;
 
-        while let Some(Token::Plus) = self.current_token {
-            self.consume();
-            let right = self.parse_product();
-            left = Expr::Sum(Box::new(left), Box::new(right));
+        while let Some(Token::Ampersand) = self.current_token {
+            self.consume()?;
+            let right = self.parse_product()?;
+            left = Expr::Intersection(Box::new(left), Box::new(right));
         }
 
-        left
+        Ok(left)
     }
 
-    fn parse_product(&mut self) -> Expr {
-        let mut left = self.parse_primary();
+    fn parse_product(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.parse_primary()?;
 
         while let Some(Token::Star) = self.current_token {
-            self.consume();
-            let right = self.parse_primary();
+            self.consume()?;
+            let right = self.parse_primary()?;
             left = Expr::Product(Box::new(left), Box::new(right));
         }
 
-        left
+        Ok(left)
     }
 
-    fn parse_primary(&mut self) -> Expr {
+    fn parse_tag(&mut self, operator: char) -> anyhow::Result<Tag> {
+        self.consume()?;
         match self.current_token.clone() {
-            Some(Token::Plus) => {
-                self.consume();
-                let tag = match self.current_token.clone() {
-                    Some(Token::Identifier(id)) => {
-                        self.consume();
-                        id
-                    }
-                    _ => panic!("Expected identifier after unary plus"),
-                };
-                Expr::USum(tag)
+            Some(Token::Identifier(id)) => {
+                self.consume()?;
+                Ok(id)
             }
-            Some(Token::Star) => {
-                self.consume();
-                let tag = match self.current_token.clone() {
-                    Some(Token::Identifier(id)) => {
-                        self.consume();
-                        id
-                    }
-                    _ => panic!("Expected identifier after unary star"),
-                };
-                Expr::UProduct(tag)
+            _ => Err(self.unexpected(&format!("a tag name after unary '{}'", operator))),
+        }
+    }
+
+    /// Parses `(k, expr)` following a combinator name
+    fn parse_combinator_args(&mut self, name: &str) -> anyhow::Result<(usize, Expr)> {
+        self.consume()?;
+        let k = match &self.current_token {
+            Some(Token::Identifier(k)) => k.parse::<usize>().ok(),
+            _ => None,
+        }
+        .ok_or_else(|| self.unexpected(&format!("a number as the first argument of '{}'", name)))?;
+        self.consume()?;
+        if self.current_token != Some(Token::Comma) {
+            return Err(self.unexpected("','"));
+        }
+        self.consume()?;
+        let expr = self.parse_sum()?;
+        if self.current_token != Some(Token::CloseParen) {
+            return Err(self.unexpected("')'"));
+        }
+        self.consume()?;
+        Ok((k, expr))
+    }
+
+    fn parse_primary(&mut self) -> anyhow::Result<Expr> {
+        match self.current_token.clone() {
+            Some(Token::Plus) => Ok(Expr::USum(self.parse_tag('+')?)),
+            Some(Token::Star) => Ok(Expr::UProduct(self.parse_tag('*')?)),
+            Some(Token::Identifier(id)) if id == "base" => {
+                self.consume()?;
+                Ok(Expr::Base)
             }
             Some(Token::Identifier(id)) => {
-                self.consume();
-                Expr::Id(id)
+                self.consume()?;
+                match (id.as_str(), &self.current_token) {
+                    ("choose", Some(Token::OpenParen)) => {
+                        let (k, expr) = self.parse_combinator_args("choose")?;
+                        Ok(Expr::Choose(k, Box::new(expr)))
+                    }
+                    ("upto", Some(Token::OpenParen)) => {
+                        let (k, expr) = self.parse_combinator_args("upto")?;
+                        Ok(Expr::UpTo(k, Box::new(expr)))
+                    }
+                    _ => Ok(Expr::Id(id)),
+                }
             }
             Some(Token::OpenParen) => {
-                self.consume();
-                let expr = self.parse();
+                self.consume()?;
+                let expr = self.parse_sum()?;
                 if let Some(Token::CloseParen) = self.current_token {
-                    self.consume();
-                    expr
+                    self.consume()?;
+                    Ok(expr)
                 } else {
-                    panic!("Expected closing parenthesis");
+                    Err(self.unexpected("')'"))
                 }
             }
-            _ => panic!("Unexpected token: {:?}", self.current_token),
+            _ => Err(self.unexpected("a variant, variation, tag, 'base' or '('")),
         }
     }
 }
@@ -333,8 +769,8 @@ mod tests {
     #[test]
     fn test_simple_expr() {
         let input = "a + b * c";
-        let mut parser = Parser::new(input);
-        let expr = parser.parse();
+        let mut parser = Parser::new(input).unwrap();
+        let expr = parser.parse().unwrap();
         assert_eq!(
             expr,
             Expr::Sum(
@@ -350,8 +786,8 @@ mod tests {
     #[test]
     fn test_nested_expr() {
         let input = "a + ((b * c) + d)";
-        let mut parser = Parser::new(input);
-        let expr = parser.parse();
+        let mut parser = Parser::new(input).unwrap();
+        let expr = parser.parse().unwrap();
         assert_eq!(
             expr,
             Expr::Sum(
@@ -370,8 +806,8 @@ mod tests {
     #[test]
     fn test_complex_names() {
         let input = "a1 + b_2 * c3";
-        let mut parser = Parser::new(input);
-        let expr = parser.parse();
+        let mut parser = Parser::new(input).unwrap();
+        let expr = parser.parse().unwrap();
         assert_eq!(
             expr,
             Expr::Sum(
@@ -387,8 +823,8 @@ mod tests {
     #[test]
     fn test_unary_expr() {
         let input = "+easy * insert";
-        let mut parser = Parser::new(input);
-        let expr = parser.parse();
+        let mut parser = Parser::new(input).unwrap();
+        let expr = parser.parse().unwrap();
         assert_eq!(
             expr,
             Expr::Product(
@@ -401,8 +837,8 @@ mod tests {
     #[test]
     fn test_unary_expr_paren() {
         let input = "+easy * (insert + delete)";
-        let mut parser = Parser::new(input);
-        let expr = parser.parse();
+        let mut parser = Parser::new(input).unwrap();
+        let expr = parser.parse().unwrap();
         assert_eq!(
             expr,
             Expr::Product(
@@ -418,8 +854,8 @@ mod tests {
     #[test]
     fn test_distribute_tags() {
         let input = "+easy * (insert + delete)";
-        let mut parser = Parser::new(input);
-        let expr = parser.parse();
+        let mut parser = Parser::new(input).unwrap();
+        let expr = parser.parse().unwrap();
         let tag_map = vec![("easy".to_string(), vec!["a".to_string(), "b".to_string()])]
             .into_iter()
             .collect();
@@ -461,4 +897,188 @@ mod tests {
             ]
         );
     }
+
+    fn parse_error(input: &str) -> String {
+        Parser::new(input)
+            .and_then(|mut parser| parser.parse())
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_error("a + b!"),
+            "invalid mutation expression: unexpected character '!' at column 6\n  a + b!\n       ^"
+        );
+        assert!(parse_error("").contains(
+            "expected a variant, variation, tag, 'base' or '(', found end of expression at column 1"
+        ));
+        assert!(parse_error("a + ").contains("found end of expression at column 5"));
+        assert!(parse_error("+ (a)").contains("expected a tag name after unary '+', found '('"));
+        assert!(parse_error("(a + b").contains("expected ')', found end of expression"));
+        assert!(parse_error("a b").contains("expected an operator or end of expression, found 'b'"));
+        assert!(parse_error("a + b)").contains("found ')' at column 6"));
+    }
+
+    #[test]
+    fn test_unknown_names() {
+        let tag_map = HashMap::from([("easy".to_string(), vec!["insert".to_string()])]);
+        let variation_map = HashMap::from([(
+            "insert".to_string(),
+            vec!["insert_1".to_string(), "insert_2".to_string()],
+        )]);
+        let variant_list = vec!["insert_1".to_string(), "insert_2".to_string()];
+
+        let err = compute_mutations("+hard", &tag_map, &variation_map, &variant_list).unwrap_err();
+        assert_eq!(err.to_string(), "unknown tag 'hard', known tags are: easy");
+
+        let err = compute_mutations("insrt", &tag_map, &variation_map, &variant_list).unwrap_err();
+        assert_eq!(err.to_string(), "unknown variant or variation 'insrt'");
+
+        assert_eq!(
+            compute_mutations("*easy", &tag_map, &variation_map, &variant_list).unwrap(),
+            vec![vec!["insert_1".to_string()], vec!["insert_2".to_string()]]
+        );
+    }
+
+    #[test]
+    fn test_reserved_names_and_combination_cap() {
+        let variation_map = HashMap::from([
+            ("base".to_string(), vec!["base_1".to_string()]),
+            ("v".to_string(), vec!["choose".to_string()]),
+        ]);
+        let variant_list = vec!["base_1".to_string(), "choose".to_string()];
+        let tag_map = HashMap::from([("t".to_string(), vec!["base".to_string()])]);
+        // Only expressions selecting a keyword-named variant or variation are rejected
+        assert_eq!(
+            compute_mutations("base_1", &tag_map, &variation_map, &variant_list).unwrap(),
+            vec![vec!["base_1".to_string()]]
+        );
+        let err = compute_mutations("+t", &tag_map, &variation_map, &variant_list).unwrap_err();
+        assert!(err.to_string().starts_with("'base' is a keyword"));
+        let err = compute_mutations("v", &tag_map, &variation_map, &variant_list).unwrap_err();
+        assert!(err.to_string().starts_with("'choose' is a keyword"));
+
+        let variants: Vec<String> = (0..200).map(|i| format!("v_{i}")).collect();
+        let variation_map = HashMap::from([("v".to_string(), variants.clone())]);
+        let err = compute_mutations("choose(3, v)", &HashMap::new(), &variation_map, &variants)
+            .unwrap_err();
+        assert!(err.to_string().contains("selects more than 100000 terms"));
+        assert!(
+            compute_mutations("upto(3, v)", &HashMap::new(), &variation_map, &variants).is_err()
+        );
+        assert_eq!(
+            compute_mutations("upto(2, v)", &HashMap::new(), &variation_map, &variants)
+                .unwrap()
+                .len(),
+            200 + 19_900
+        );
+        assert_eq!(binomial(1000, 2), Some(499_500));
+        assert_eq!(binomial(3, 5), Some(0));
+        assert_eq!(binomial(usize::MAX, 3), None);
+    }
+
+    fn terms(expr: &str) -> Vec<Vec<String>> {
+        let tag_map = HashMap::from([
+            (
+                "easy".to_string(),
+                vec!["insert".to_string(), "delete".to_string()],
+            ),
+            (
+                "new".to_string(),
+                vec!["delete".to_string(), "union".to_string()],
+            ),
+        ]);
+        let variation_map = HashMap::from([
+            (
+                "insert".to_string(),
+                vec!["insert_1".to_string(), "insert_2".to_string()],
+            ),
+            ("delete".to_string(), vec!["delete_1".to_string()]),
+            ("union".to_string(), vec!["union_1".to_string()]),
+        ]);
+        let variant_list: Vec<String> = variation_map.values().flatten().cloned().collect();
+        compute_mutations(expr, &tag_map, &variation_map, &variant_list).unwrap()
+    }
+
+    fn term_list(terms: &[&[&str]]) -> Vec<Vec<String>> {
+        terms
+            .iter()
+            .map(|term| term.iter().map(|v| v.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_difference_and_intersection() {
+        assert_eq!(terms("+easy - insert"), term_list(&[&["delete_1"]]));
+        assert_eq!(terms("+easy & +new"), term_list(&[&["delete_1"]]));
+        // A product is excluded as soon as it includes an excluded term.
+        assert_eq!(
+            terms("insert * (delete + union) - union_1"),
+            term_list(&[&["insert_1", "delete_1"], &["insert_2", "delete_1"]])
+        );
+        // `&` binds tighter than `+` and `-`.
+        assert_eq!(
+            terms("union + +easy & +new"),
+            term_list(&[&["union_1"], &["delete_1"]])
+        );
+    }
+
+    #[test]
+    fn test_combinators_and_base() {
+        assert_eq!(
+            terms("choose(2, insert + delete)"),
+            term_list(&[
+                &["insert_1", "insert_2"],
+                &["insert_1", "delete_1"],
+                &["insert_2", "delete_1"]
+            ])
+        );
+        assert_eq!(
+            terms("upto(2, delete + union)"),
+            term_list(&[&["delete_1"], &["union_1"], &["delete_1", "union_1"]])
+        );
+        assert_eq!(terms("choose(3, delete + union)"), term_list(&[]));
+        assert_eq!(terms("base + delete"), term_list(&[&[], &["delete_1"]]));
+        assert_eq!(terms("base * delete"), term_list(&[&["delete_1"]]));
+
+        assert!(parse_error("choose(x, a)")
+            .contains("expected a number as the first argument of 'choose'"));
+        assert!(parse_error("choose(2 a)").contains("expected ',', found 'a'"));
+    }
+
+    #[test]
+    fn test_resolve_conflicts() {
+        let variation_map = HashMap::from([
+            (
+                "insert".to_string(),
+                vec!["insert_1".to_string(), "insert_2".to_string()],
+            ),
+            ("delete".to_string(), vec!["delete_1".to_string()]),
+        ]);
+        let raw = term_list(&[
+            &["insert_1", "insert_1"],
+            &["insert_1", "insert_2"],
+            &["insert_1", "delete_1"],
+            &["delete_1", "insert_1"],
+            &["insert_1"],
+        ]);
+
+        let resolution =
+            resolve_conflicts(raw.clone(), &variation_map, ConflictPolicy::Drop).unwrap();
+        assert_eq!(
+            resolution.terms,
+            term_list(&[&["insert_1"], &["insert_1", "delete_1"]])
+        );
+        assert_eq!(resolution.dropped, term_list(&[&["insert_1", "insert_2"]]));
+        assert_eq!(resolution.duplicates, 2);
+
+        let err = resolve_conflicts(raw, &variation_map, ConflictPolicy::Error)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(
+            "term [\"insert_1\", \"insert_2\"] selects several variants of variation 'insert'"
+        ));
+    }
 }
@@ -6,12 +6,24 @@
 //! Unlike the CLI commands in `commands.rs`, these functions return
 //! structured results instead of printing to stdout.
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::time::{SystemTime, UNIX_EPOCH};
-
-use crate::{Language, Project, ProjectConfig, SpanContent};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    algebra::{self, ConflictPolicy},
+    equivalent_variants,
+    project::mapped_language,
+    recovery::{self, Journal},
+    Campaign, Code, Equivalence, History, HistoryEntry, Language, MalformedVariation, Project,
+    ProjectConfig, Snapshot, SpanContent, Workspace,
+};
 
 #[cfg(feature = "rust-ast")]
 fn is_parseable_rust_source(source: &str) -> bool {
@@ -40,8 +52,21 @@ pub struct VariationInfo {
     pub tags: Vec<String>,
 }
 
-/// Result of a set/unset operation.
+/// A variant that cannot change the program's behavior, see [`equivalent_variants_of`].
 #[derive(Debug, Clone, PartialEq)]
+pub struct EquivalentVariant {
+    /// Path to the file containing the variation of the variant
+    pub path: PathBuf,
+    /// Line number where the variation starts (1-indexed)
+    pub line: usize,
+    /// Name of the variant
+    pub variant: String,
+    /// What the variant is equivalent to
+    pub equivalence: Equivalence,
+}
+
+/// Result of a set/unset operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SetResult {
     /// Path to the file that was modified
     pub file: PathBuf,
@@ -53,6 +78,317 @@ pub struct SetResult {
     pub new_active: usize,
 }
 
+/// A term applied by stepping through an incremental campaign.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CampaignStep {
+    /// Index of the applied term
+    pub index: usize,
+    /// Total number of terms in the campaign
+    pub total: usize,
+    /// Variants activated by the term
+    pub term: Vec<String>,
+}
+
+/// A variant of an expanded term, along with the variation it belongs to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TermVariant {
+    /// Name of the variant
+    pub name: String,
+    /// Name of its variation (if named)
+    pub variation: Option<String>,
+    /// Path to the file containing the variation
+    pub path: PathBuf,
+    /// Line number where the variation starts (1-indexed)
+    pub line: usize,
+}
+
+/// Several variants of the same variation selected by one term.
+///
+/// A variation has a single active variant, so such a term cannot be applied as written:
+/// activating the variants one after the other leaves only the last one active.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TermConflict {
+    /// Name of the variation (if named)
+    pub variation: Option<String>,
+    /// Path to the file containing the variation
+    pub path: PathBuf,
+    /// Line number where the variation starts (1-indexed)
+    pub line: usize,
+    /// The variants of the variation selected by the term
+    pub variants: Vec<String>,
+}
+
+/// A term of an expanded mutation expression.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExplainedTerm {
+    /// Variants activated together by the term
+    pub variants: Vec<TermVariant>,
+    /// Variations from which the term selects more than one variant
+    pub conflicts: Vec<TermConflict>,
+}
+
+/// How a mutation expression expands into terms, see [`explain_expr`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExprExplanation {
+    /// The expression as given
+    pub expr: String,
+    /// The parsed expression, fully parenthesized
+    pub parsed: String,
+    /// The expression after `+tag` and `*tag` are replaced by the tagged variations
+    pub tags_distributed: String,
+    /// The expression after variations are replaced by the sum of their variants
+    pub variations_distributed: String,
+    /// The resulting terms, in the order they would be tested
+    pub terms: Vec<ExplainedTerm>,
+}
+
+/// How serious a lint finding is, see [`lint_project`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintSeverity {
+    /// Makes some operations behave unexpectedly
+    Error,
+    /// Likely a mistake, but harmless
+    Warning,
+}
+
+/// The kind of a lint finding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintKind {
+    /// A variant name is used more than once, only the first one can be activated by name
+    DuplicateVariant,
+    /// A variation name is used more than once, expressions select all of them together
+    DuplicateVariation,
+    /// An anonymous variation cannot be selected by a variation name or a tag
+    UnreachableVariation,
+    /// Tags on an anonymous variation are ignored
+    TagsOnAnonymousVariation,
+    /// The base of a variation has no code
+    EmptyBase,
+    /// A variant has the same code as the base, it can never be killed
+    VariantSameAsBase,
+    /// A variant or variation is named after a keyword of mutation expressions, see
+    /// [`algebra::RESERVED_NAMES`]
+    ReservedName,
+    /// The mutation markers of a file do not parse, see [`Project::diagnostics`]
+    SyntaxError,
+    /// A variation does not have exactly one active variant, see [`Project::malformed`]
+    MalformedVariation,
+}
+
+/// A finding of [`lint_project`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LintIssue {
+    pub severity: LintSeverity,
+    pub kind: LintKind,
+    /// File of the variation the issue is about
+    pub path: PathBuf,
+    /// Line where the variation starts, or of the syntax error (1-indexed)
+    pub line: usize,
+    /// Column of the syntax error (1-indexed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// Description of the issue
+    pub message: String,
+}
+
+/// Classification of a term after running the test command against it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestOutcome {
+    /// The test command failed, so the mutant was detected
+    Killed,
+    /// The test command passed, so the mutant went undetected
+    Survived,
+    /// The test command did not finish within the per-mutant timeout
+    TimedOut,
+    /// The term could not be applied or the test command could not be started
+    Errored,
+    /// The mutant does not compile: a Rust file no longer parses or the build command failed
+    Stillborn,
+}
+
+/// Result of running the test command against one term of a mutation expression.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TermResult {
+    /// Variants that were active during the run
+    pub variants: Vec<String>,
+    /// Whether this is the run of the base program made before the terms, rather than a
+    /// term of the expression, which may also be `base`
+    #[serde(default)]
+    pub is_baseline: bool,
+    /// Exit code of the test command, or of the build command for `Stillborn` outcomes
+    /// (`None` if it was terminated by a signal or never ran)
+    pub exit_code: Option<i32>,
+    /// Wall-clock duration of the test command in milliseconds
+    pub duration_ms: u64,
+    /// Captured standard output of the test command, or of the failed build command
+    pub stdout: String,
+    /// Captured standard error of the test command, or of the failed build command
+    pub stderr: String,
+    /// Classification of the run
+    pub outcome: TestOutcome,
+    /// Error that prevented the run, for `Errored` outcomes, or why the mutant does not
+    /// compile, for `Stillborn` ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Results of a test campaign over a mutation expression.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestReport {
+    /// The mutation expression that was tested
+    pub expr: String,
+    /// The test command that was run for each term
+    pub command: String,
+    /// Result of running the command on the base program, with every variation at base
+    pub baseline: TermResult,
+    /// Time limit applied to each term, in milliseconds
+    pub timeout_ms: u64,
+    /// Results in the order the terms were enumerated
+    pub results: Vec<TermResult>,
+    /// Terms that were not run because they select several variants of one variation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dropped: Vec<Vec<String>>,
+    /// Terms that were not run because they select a variant equivalent to the base or to
+    /// another variant, see [`RunOptions::skip_equivalent`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<Vec<String>>,
+    /// Mutation score and survivors of the campaign
+    pub summary: MutationSummary,
+}
+
+/// Aggregate view of a test campaign.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MutationSummary {
+    /// Number of tested terms, excluding the baseline
+    pub total: usize,
+    /// Number of terms whose test command failed
+    pub killed: usize,
+    /// Number of terms whose test command passed
+    pub survived: usize,
+    /// Number of terms whose test command was killed after the timeout
+    pub timed_out: usize,
+    /// Number of terms that could not be run
+    pub errored: usize,
+    /// Number of terms that do not compile, they are left out of the score
+    #[serde(default)]
+    pub stillborn: usize,
+    /// Whether the test command failed on the base program
+    pub baseline_failed: bool,
+    /// `(killed + timed_out) / (total - stillborn)`, absent when there are no compiling terms
+    /// or the baseline failed, as every mutant would then be counted as killed
+    pub score: Option<f64>,
+    /// Surviving variants grouped by the name of their variation (`anonymous` for unnamed ones)
+    pub survivors_by_variation: BTreeMap<String, Vec<String>>,
+    /// Surviving variants grouped by the tags of their variation
+    pub survivors_by_tag: BTreeMap<String, Vec<String>>,
+}
+
+impl MutationSummary {
+    fn new(
+        baseline: &TermResult,
+        results: &[TermResult],
+        tag_map: &HashMap<String, Vec<String>>,
+        variation_map: &HashMap<String, Vec<String>>,
+    ) -> Self {
+        let count = |outcome| results.iter().filter(|r| r.outcome == outcome).count();
+        let total = results.len();
+        let killed = count(TestOutcome::Killed);
+        let timed_out = count(TestOutcome::TimedOut);
+        let detected = killed + timed_out;
+        let stillborn = count(TestOutcome::Stillborn);
+        let compiled = total - stillborn;
+        let baseline_failed = baseline.outcome != TestOutcome::Survived;
+
+        let mut by_variation: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let mut by_tag: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let survivors = results
+            .iter()
+            .filter(|r| r.outcome == TestOutcome::Survived)
+            .flat_map(|r| r.variants.iter());
+        for variant in survivors {
+            let variations: Vec<&String> = variation_map
+                .iter()
+                .filter(|(_, variants)| variants.contains(variant))
+                .map(|(variation, _)| variation)
+                .collect();
+            if variations.is_empty() {
+                by_variation
+                    .entry("anonymous".to_string())
+                    .or_default()
+                    .insert(variant.clone());
+            }
+            for variation in variations {
+                by_variation
+                    .entry(variation.clone())
+                    .or_default()
+                    .insert(variant.clone());
+                for (tag, tagged) in tag_map {
+                    if tagged.contains(variation) {
+                        by_tag
+                            .entry(tag.clone())
+                            .or_default()
+                            .insert(variant.clone());
+                    }
+                }
+            }
+        }
+        let into_lists = |groups: BTreeMap<String, BTreeSet<String>>| {
+            groups
+                .into_iter()
+                .map(|(group, variants)| (group, variants.into_iter().collect()))
+                .collect()
+        };
+
+        MutationSummary {
+            total,
+            killed,
+            survived: count(TestOutcome::Survived),
+            timed_out,
+            errored: count(TestOutcome::Errored),
+            stillborn,
+            baseline_failed,
+            score: (compiled > 0 && !baseline_failed).then(|| detected as f64 / compiled as f64),
+            survivors_by_variation: into_lists(by_variation),
+            survivors_by_tag: into_lists(by_tag),
+        }
+    }
+}
+
+/// Options controlling how [`run_tests`] runs the terms of an expression.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Run each term in an isolated copy of the project instead of the working tree
+    pub copy: bool,
+    /// Number of terms run concurrently; more than one job implies `copy`
+    pub jobs: usize,
+    /// Time limit for each term, taken from the project's `timeout` setting or derived from
+    /// the baseline duration when absent
+    pub timeout: Option<Duration>,
+    /// Run the terms even if the test command fails on the base program
+    pub allow_failing_baseline: bool,
+    /// What to do with terms that select several variants of one variation
+    pub conflicts: ConflictPolicy,
+    /// Command run before the test command for each term, taken from the project's `build`
+    /// setting when absent; a failure marks the term stillborn
+    pub build: Option<String>,
+    /// Skip the terms selecting a variant that is equivalent to its base or to an earlier
+    /// variant in every variation having it, see [`equivalent_variants_of`]
+    pub skip_equivalent: bool,
+}
+
+/// Factor applied to the baseline duration to derive the default per-mutant timeout.
+const BASELINE_TIMEOUT_FACTOR: u32 = 5;
+/// Lower bound for the derived per-mutant timeout, so that fast suites tolerate jitter.
+const MIN_DERIVED_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Derives the default per-mutant timeout from the duration of the baseline run.
+pub fn derive_timeout(baseline: Duration) -> Duration {
+    (baseline * BASELINE_TIMEOUT_FACTOR).max(MIN_DERIVED_TIMEOUT)
+}
+
 /// Target format for syntax conversion.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConversionTarget {
@@ -155,6 +491,234 @@ pub fn list_variations(project: &Project) -> Vec<VariationInfo> {
     result
 }
 
+/// Finds the variants of a project that cannot change the program's behavior.
+///
+/// The check parses and normalizes every variant, so it is kept out of [`list_variations`]
+/// and only run when equivalent variants are asked for.
+///
+/// # Arguments
+///
+/// * `project` - The project to check
+///
+/// # Returns
+///
+/// Each equivalent variant with the variation it belongs to and what it is equivalent to, in
+/// the order of [`list_variations`], see [`equivalent_variants`]. Variants are told apart by
+/// their variation's file and line, since variants of different files may share a name.
+/// Functional variations are not checked.
+pub fn equivalent_variants_of(project: &Project) -> Vec<EquivalentVariant> {
+    let mut result = Vec::new();
+
+    for file in &project.files {
+        if file.code.functional.is_some() {
+            continue;
+        }
+        for span in &file.code.spans {
+            if let SpanContent::Variation(v) = &span.content {
+                result.extend(equivalent_variants(&file.code.language, v).into_iter().map(
+                    |(variant, equivalence)| EquivalentVariant {
+                        path: file.path.clone(),
+                        line: span.line,
+                        variant,
+                        equivalence,
+                    },
+                ));
+            }
+        }
+    }
+
+    result
+}
+
+/// The variants that are equivalent in every variation having them, so that selecting them
+/// by name cannot change the program.
+fn equivalent_everywhere(project: &Project) -> HashSet<String> {
+    let equivalent = equivalent_variants_of(project);
+    let equivalent: HashSet<(&Path, usize, &str)> = equivalent
+        .iter()
+        .map(|e| (e.path.as_path(), e.line, e.variant.as_str()))
+        .collect();
+
+    let mut everywhere: HashMap<String, bool> = HashMap::new();
+    for info in list_variations(project) {
+        for variant in &info.variants {
+            let here = equivalent.contains(&(info.path.as_path(), info.line, variant.as_str()));
+            *everywhere.entry(variant.clone()).or_insert(true) &= here;
+        }
+    }
+    everywhere
+        .into_iter()
+        .filter_map(|(variant, all)| all.then_some(variant))
+        .collect()
+}
+
+/// Checks the variations of a project for mistakes that the other commands silently accept,
+/// along with the files left out of the project because their variations could not be read.
+///
+/// # Arguments
+///
+/// * `project` - The project to check
+///
+/// # Returns
+///
+/// The issues found, errors first, then by file and line.
+pub fn lint_project(project: &Project) -> Vec<LintIssue> {
+    let mut issues: Vec<LintIssue> = project
+        .diagnostics
+        .iter()
+        .map(|diagnostic| LintIssue {
+            severity: LintSeverity::Error,
+            kind: LintKind::SyntaxError,
+            path: diagnostic.path.clone(),
+            line: diagnostic.line,
+            column: Some(diagnostic.column),
+            message: diagnostic.message(),
+        })
+        .chain(project.malformed.iter().map(|variation| LintIssue {
+            severity: LintSeverity::Error,
+            kind: LintKind::MalformedVariation,
+            path: variation.path.clone(),
+            line: variation.line,
+            column: None,
+            message: variation.message(),
+        }))
+        .collect();
+    let mut variants: BTreeMap<&str, Vec<(&Path, usize)>> = BTreeMap::new();
+    let mut variations: BTreeMap<&str, Vec<(&Path, usize)>> = BTreeMap::new();
+
+    for file in &project.files {
+        for span in &file.code.spans {
+            let SpanContent::Variation(variation) = &span.content else {
+                continue;
+            };
+            let location = (file.path.as_path(), span.line);
+            let issue = |severity, kind, message: String| LintIssue {
+                severity,
+                kind,
+                path: file.path.clone(),
+                line: span.line,
+                column: None,
+                message,
+            };
+
+            for variant in &variation.variants {
+                variants.entry(&variant.name).or_default().push(location);
+            }
+            for name in variation
+                .name
+                .iter()
+                .chain(variation.variants.iter().map(|variant| &variant.name))
+                .filter(|name| algebra::RESERVED_NAMES.contains(&name.as_str()))
+            {
+                issues.push(issue(
+                    LintSeverity::Error,
+                    LintKind::ReservedName,
+                    format!(
+                        "'{}' is a keyword of mutation expressions, rename it to select it",
+                        name
+                    ),
+                ));
+            }
+            match &variation.name {
+                Some(name) => variations.entry(name).or_default().push(location),
+                None => {
+                    issues.push(issue(
+                        LintSeverity::Warning,
+                        LintKind::UnreachableVariation,
+                        format!(
+                            "anonymous variation with variants {:?} can only be selected by variant names, name it to refer to it in expressions",
+                            variation.variants.iter().map(|v| &v.name).collect::<Vec<_>>()
+                        ),
+                    ));
+                    if !variation.tags.is_empty() {
+                        issues.push(issue(
+                            LintSeverity::Warning,
+                            LintKind::TagsOnAnonymousVariation,
+                            format!(
+                                "tags {:?} are ignored because the variation has no name",
+                                variation.tags
+                            ),
+                        ));
+                    }
+                }
+            }
+
+            let base = normalized_code(&variation.base.lines());
+            if base.is_empty() {
+                issues.push(issue(
+                    LintSeverity::Warning,
+                    LintKind::EmptyBase,
+                    format!(
+                        "the base of variation '{}' is empty",
+                        variation.name.as_deref().unwrap_or("anonymous")
+                    ),
+                ));
+            }
+            for variant in &variation.variants {
+                if normalized_code(&variant.lines()) == base {
+                    issues.push(issue(
+                        LintSeverity::Warning,
+                        LintKind::VariantSameAsBase,
+                        format!("variant '{}' has the same code as the base", variant.name),
+                    ));
+                }
+            }
+        }
+    }
+
+    for (severity, kind, what, names) in [
+        (
+            LintSeverity::Error,
+            LintKind::DuplicateVariant,
+            "variant",
+            &variants,
+        ),
+        (
+            LintSeverity::Warning,
+            LintKind::DuplicateVariation,
+            "variation",
+            &variations,
+        ),
+    ] {
+        for (name, locations) in names {
+            if locations.len() < 2 {
+                continue;
+            }
+            let (path, line) = locations[0];
+            issues.push(LintIssue {
+                severity,
+                kind,
+                path: path.to_path_buf(),
+                line,
+                column: None,
+                message: format!(
+                    "{} name '{}' is defined {} times, at {}",
+                    what,
+                    name,
+                    locations.len(),
+                    locations
+                        .iter()
+                        .map(|(path, line)| format!("{}:{}", path.to_string_lossy(), line))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            });
+        }
+    }
+
+    issues.sort_by(|a, b| (a.severity, &a.path, a.line).cmp(&(b.severity, &b.path, b.line)));
+    issues
+}
+
+/// The code of a variant with its whitespace collapsed, for comparisons
+fn normalized_code(lines: &[String]) -> String {
+    lines
+        .iter()
+        .flat_map(|line| line.split_whitespace())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 /// Sets a variant as active, returning what was changed.
 ///
 /// # Arguments
@@ -167,6 +731,13 @@ pub fn list_variations(project: &Project) -> Vec<VariationInfo> {
 /// * `Ok(SetResult)` - Information about what was changed
 /// * `Err(ApiError)` - If the variant was not found or is already active
 pub fn set_variant(project: &mut Project, variant: &str) -> Result<SetResult, ApiError> {
+    let snapshot = take_snapshot(&project.root, &files_with_variant(project, variant), &[]);
+    let result = apply_set_variant(project, variant)?;
+    record_history(snapshot, &format!("set {}", variant), vec![result.clone()]);
+    Ok(result)
+}
+
+fn apply_set_variant(project: &mut Project, variant: &str) -> Result<SetResult, ApiError> {
     let mut all_variants = Vec::new();
 
     for file in project.files.iter_mut() {
@@ -240,6 +811,17 @@ pub fn set_variant(project: &mut Project, variant: &str) -> Result<SetResult, Ap
 /// * `Ok(SetResult)` - Information about what was changed
 /// * `Err(ApiError)` - If the variant was not found
 pub fn unset_variant(project: &mut Project, variant: &str) -> Result<SetResult, ApiError> {
+    let snapshot = take_snapshot(&project.root, &files_with_variant(project, variant), &[]);
+    let result = apply_unset_variant(project, variant)?;
+    record_history(
+        snapshot,
+        &format!("unset {}", variant),
+        vec![result.clone()],
+    );
+    Ok(result)
+}
+
+fn apply_unset_variant(project: &mut Project, variant: &str) -> Result<SetResult, ApiError> {
     let mut all_variants = Vec::new();
 
     for file in project.files.iter_mut() {
@@ -306,6 +888,11 @@ pub fn try_set_variant_match_replace(
         });
     }
 
+    let snapshot = take_snapshot(
+        &History::root_for(path),
+        &match_replace_sources(&content),
+        &[],
+    );
     let applied = crate::syntax::match_replace::set_variant_in_match_replace(&content, variant)
         .map_err(|e| ApiError::ProjectError(e.to_string()))?;
 
@@ -315,12 +902,14 @@ pub fn try_set_variant_match_replace(
         });
     }
 
-    Ok(Some(SetResult {
+    let result = SetResult {
         file: applied.source_path,
         variation: applied.variation_name,
         previous_active: applied.previous_active,
         new_active: applied.new_active,
-    }))
+    };
+    record_history(snapshot, &format!("set {}", variant), vec![result.clone()]);
+    Ok(Some(result))
 }
 
 /// Tries to unset a variant through a match-replace sidecar document.
@@ -345,15 +934,30 @@ pub fn try_unset_variant_match_replace(
         });
     }
 
+    let snapshot = take_snapshot(
+        &History::root_for(path),
+        &match_replace_sources(&content),
+        &[],
+    );
     let applied = crate::syntax::match_replace::unset_variant_in_match_replace(&content, variant)
         .map_err(|e| ApiError::ProjectError(e.to_string()))?;
 
-    Ok(Some(SetResult {
+    let result = SetResult {
         file: applied.source_path,
         variation: applied.variation_name,
         previous_active: applied.previous_active,
         new_active: applied.new_active,
-    }))
+    };
+    record_history(
+        snapshot,
+        &format!("unset {}", variant),
+        vec![result.clone()],
+    );
+    Ok(Some(result))
+}
+
+fn match_replace_sources(content: &str) -> Vec<PathBuf> {
+    crate::syntax::match_replace::source_paths(content).unwrap_or_default()
 }
 
 fn load_match_replace_document(path: &Path) -> Result<Option<(PathBuf, String)>, ApiError> {
@@ -396,6 +1000,8 @@ fn load_match_replace_document(path: &Path) -> Result<Option<(PathBuf, String)>,
 /// * `Ok(Vec<SetResult>)` - Information about all variations that were reset
 /// * `Err(ApiError)` - If an error occurred during reset
 pub fn reset_all(project: &mut Project) -> Result<Vec<SetResult>, ApiError> {
+    let paths: Vec<PathBuf> = project.files.iter().map(|file| file.path.clone()).collect();
+    let snapshot = take_snapshot(&project.root, &paths, &[]);
     let mut results = Vec::new();
 
     for file in project.files.iter_mut() {
@@ -423,1115 +1029,2892 @@ pub fn reset_all(project: &mut Project) -> Result<Vec<SetResult>, ApiError> {
             .map_err(|e| ApiError::ProjectError(e.to_string()))?;
     }
 
+    record_history(snapshot, "reset", results.clone());
     Ok(results)
 }
 
-/// Converts a file's mutation syntax in place.
+/// Resets the variations that do not have exactly one active variant to their base.
 ///
-/// Currently supported:
-/// - Rust comment syntax -> Rust functional syntax.
-/// - Rust functional syntax -> comment syntax.
-/// - Comment syntax -> preprocessor syntax.
-/// - Comment syntax -> patch syntax.
-/// - Comment syntax -> match-replace syntax.
-/// - Preprocessor syntax -> comment syntax.
-/// - Patch syntax -> comment syntax.
-/// - Match-replace syntax -> comment syntax.
-pub fn convert_file(path: &Path, target: ConversionTarget) -> Result<PathBuf, ApiError> {
-    if !path.is_file() {
+/// Files with such variations are left out of the project when it is loaded, see
+/// [`Project::malformed`]; load the project again to work with the repaired files.
+///
+/// # Arguments
+///
+/// * `project` - The project whose malformed variations to repair
+///
+/// # Returns
+///
+/// * `Ok(Vec<MalformedVariation>)` - The variations that were reset
+/// * `Err(ApiError)` - If a file could not be rewritten
+pub fn repair_project(project: &Project) -> Result<Vec<MalformedVariation>, ApiError> {
+    let mut paths: Vec<PathBuf> = project
+        .malformed
+        .iter()
+        .map(|variation| variation.path.clone())
+        .collect();
+    paths.sort();
+    paths.dedup();
+    let custom_languages = project
+        .config
+        .as_ref()
+        .map(|config| config.custom_languages.clone())
+        .unwrap_or_default();
+    let file_languages = match &project.config {
+        Some(config) => config.file_language_matchers(&project.root)?,
+        None => vec![],
+    };
+
+    let snapshot = take_snapshot(&project.root, &paths, &[]);
+    let mut repaired = vec![];
+    for path in &paths {
+        let language = mapped_language(&file_languages, path);
+        repaired.extend(Code::repair_file(path, language, &custom_languages)?);
+    }
+    record_history(snapshot, "repair", vec![]);
+    Ok(repaired)
+}
+
+/// Reverts the last operation recorded in the history of the project containing `path`.
+///
+/// Set, unset, reset and conversions are recorded with the content of every file they
+/// changed, so undoing a conversion also restores the original source and removes the
+/// files it created.
+///
+/// # Arguments
+///
+/// * `path` - A path inside the project
+///
+/// # Returns
+///
+/// * `Ok(Some(HistoryEntry))` - The operation that was reverted
+/// * `Ok(None)` - If there is nothing to undo
+/// * `Err(ApiError)` - If a file changed since the operation, or could not be restored
+pub fn undo(path: &Path) -> Result<Option<HistoryEntry>, ApiError> {
+    Ok(History::undo(&History::root_for(path))?)
+}
+
+/// Applies again the last operation reverted by [`undo`].
+///
+/// # Arguments
+///
+/// * `path` - A path inside the project
+///
+/// # Returns
+///
+/// * `Ok(Some(HistoryEntry))` - The operation that was applied again
+/// * `Ok(None)` - If there is nothing to redo
+/// * `Err(ApiError)` - If a file changed since the operation was undone, or could not be
+///   restored
+pub fn redo(path: &Path) -> Result<Option<HistoryEntry>, ApiError> {
+    Ok(History::redo(&History::root_for(path))?)
+}
+
+fn files_with_variant(project: &Project, variant: &str) -> Vec<PathBuf> {
+    project
+        .files
+        .iter()
+        .filter(|file| file.code.get_all_variants().iter().any(|v| v == variant))
+        .map(|file| file.path.clone())
+        .collect()
+}
+
+/// Snapshots the files an operation is about to change; a failure only disables recording
+/// the operation in the history.
+fn take_snapshot(root: &Path, files: &[PathBuf], dirs: &[PathBuf]) -> Option<Snapshot> {
+    match Snapshot::take(root, files, dirs) {
+        Ok(snapshot) => Some(snapshot),
+        Err(err) => {
+            log::warn!(
+                "the operation will not be recorded in the history: {:#}",
+                err
+            );
+            None
+        }
+    }
+}
+
+fn record_history(snapshot: Option<Snapshot>, operation: &str, activations: Vec<SetResult>) {
+    if let Some(snapshot) = snapshot {
+        if let Err(err) = snapshot.record(operation, activations) {
+            log::warn!("could not record '{}' in the history: {:#}", operation, err);
+        }
+    }
+}
+
+/// Starts an incremental campaign for a mutation expression.
+///
+/// The expression is expanded into its sum-of-products terms, which are persisted in the
+/// project's state directory. No term is applied until [`next_term`] is called.
+///
+/// # Arguments
+///
+/// * `project` - The project to run the campaign on, must have no active variants
+/// * `expr` - The mutation expression to enumerate
+/// * `conflicts` - What to do with terms that select several variants of one variation
+///
+/// # Returns
+///
+/// * `Ok(Campaign)` - The newly started campaign
+/// * `Err(ApiError)` - If the project has active variants or the expression is invalid
+pub fn start_campaign(
+    project: &Project,
+    expr: &str,
+    conflicts: ConflictPolicy,
+) -> Result<Campaign, ApiError> {
+    let active_variants = project.active_variants();
+    if !active_variants.is_empty() {
         return Err(ApiError::ProjectError(format!(
-            "path '{}' is not a file",
-            path.display()
+            "a campaign can only be started when there are no active variants, found {:?}",
+            active_variants
         )));
     }
 
-    match target {
-        ConversionTarget::RustFunctional => {
-            let extension = path
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .ok_or_else(|| {
-                    ApiError::ProjectError("file extension is not valid unicode".to_string())
-                })?;
+    let resolution = resolve_terms(project, expr, conflicts)?;
 
-            if extension != "rs" {
-                return Err(ApiError::ProjectError(format!(
-                    "Rust functional conversion is only supported for .rs files (got '.{}')",
-                    extension
-                )));
-            }
+    let mut campaign = Campaign::new(expr, resolution.terms);
+    campaign.dropped = resolution.dropped;
+    campaign.save(&project.root)?;
 
-            let content = std::fs::read_to_string(path)?;
-            let language = crate::syntax::functional::functional_language_for_extension(extension)
-                .ok_or_else(|| {
-                    ApiError::ProjectError(format!(
-                        "no functional mutation backend is available for '.{}'",
-                        extension
-                    ))
-                })?;
-            let spans = crate::syntax::comment::parse_code(&content)
-                .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-            let converted =
-                crate::syntax::functional::render_functional_code(language, &content, &spans)
-                    .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-            std::fs::write(path, converted)?;
-        }
-        ConversionTarget::RustComment => {
-            let extension = path
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .ok_or_else(|| {
-                    ApiError::ProjectError("file extension is not valid unicode".to_string())
-                })?;
+    Ok(campaign)
+}
 
-            if extension != "rs" {
-                return Err(ApiError::ProjectError(format!(
-                    "Rust comment conversion is only supported for .rs files (got '.{}')",
-                    extension
-                )));
-            }
+/// Explains how a mutation expression expands, without applying anything.
+///
+/// # Arguments
+///
+/// * `project` - The project whose tags, variations and variants the expression refers to
+/// * `expr` - The mutation expression to explain
+///
+/// # Returns
+///
+/// * `Ok(ExprExplanation)` - The intermediate forms and the located terms, with the
+///   conflicts of each term
+/// * `Err(ApiError)` - If the expression is invalid
+pub fn explain_expr(project: &Project, expr: &str) -> Result<ExprExplanation, ApiError> {
+    let expansion = algebra::expand(
+        expr,
+        &project.tag_map(),
+        &project.variation_map(),
+        &project.all_variants(),
+    )?;
+    let variations = list_variations(project);
 
-            let content = std::fs::read_to_string(path)?;
-            let language = crate::syntax::functional::functional_language_for_extension(extension)
-                .ok_or_else(|| {
-                    ApiError::ProjectError(format!(
-                        "no functional mutation backend is available for '.{}'",
-                        extension
-                    ))
-                })?;
-            let converted =
-                crate::syntax::functional::render_comment_code_from_functional(language, &content)
-                    .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-            std::fs::write(path, converted)?;
-        }
-        ConversionTarget::Preprocessor => {
-            let content = std::fs::read_to_string(path)?;
-            let spans = crate::syntax::comment::parse_code(&content)
-                .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-            let converted =
-                crate::syntax::preprocessor::render_preprocessor_code_from_comment(&spans)
-                    .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-            std::fs::write(path, converted)?;
-        }
-        ConversionTarget::Patch => {
-            let content = std::fs::read_to_string(path)?;
-            let spans = crate::syntax::comment::parse_code(&content)
-                .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-            let rendered = crate::syntax::patch::render_patch_bundle_from_comment(
-                &spans,
-                &path.to_string_lossy(),
-            )
-            .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-            std::fs::write(path, &rendered.base_source)?;
-            let bundle_dir = crate::syntax::patch::patch_bundle_dir_for_source(path)
-                .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-            let manifest_path = crate::syntax::patch::write_patch_bundle(&bundle_dir, &rendered)
-                .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-            return Ok(manifest_path);
-        }
-        ConversionTarget::MatchReplace => {
-            let content = std::fs::read_to_string(path)?;
-            let spans = crate::syntax::comment::parse_code(&content)
-                .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-            let converted = crate::syntax::match_replace::render_match_replace_code_from_comment(
-                &spans,
-                &path.to_string_lossy(),
-            )
-            .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+    let terms = expansion
+        .terms
+        .iter()
+        .map(|term| ExplainedTerm {
+            variants: term
+                .iter()
+                .filter_map(|variant| {
+                    let info = variations.iter().find(|v| v.variants.contains(variant))?;
+                    Some(TermVariant {
+                        name: variant.clone(),
+                        variation: info.name.clone(),
+                        path: info.path.clone(),
+                        line: info.line,
+                    })
+                })
+                .collect(),
+            conflicts: term_conflicts(term, &variations),
+        })
+        .collect();
+
+    Ok(ExprExplanation {
+        expr: expr.to_string(),
+        parsed: expansion.parsed,
+        tags_distributed: expansion.tags_distributed,
+        variations_distributed: expansion.variations_distributed,
+        terms,
+    })
+}
 
-            // Keep the original source file as the base program (with mutations stripped).
-            let mut base_source = String::new();
-            for span in &spans {
-                match &span.content {
-                    SpanContent::Line(line) => base_source.push_str(line),
-                    SpanContent::Variation(variation) => {
-                        for line in variation.base.lines() {
-                            base_source.push_str(&line);
-                            base_source.push('\n');
-                        }
-                    }
+/// Enumerates the terms of `expr` that can be applied to `project`.
+///
+/// Every variation is told apart by its location, so a term may combine variants of
+/// variations that share a name in different places, and anonymous variations are checked
+/// as well.
+fn resolve_terms(
+    project: &Project,
+    expr: &str,
+    conflicts: ConflictPolicy,
+) -> Result<algebra::Resolution, ApiError> {
+    let terms = algebra::compute_mutations(
+        expr,
+        &project.tag_map(),
+        &project.variation_map(),
+        &project.all_variants(),
+    )?;
+
+    let membership = list_variations(project)
+        .into_iter()
+        .map(|variation| {
+            let name = format!(
+                "{} ({}:{})",
+                variation.name.as_deref().unwrap_or("anonymous"),
+                variation.path.to_string_lossy(),
+                variation.line
+            );
+            (name, variation.variants)
+        })
+        .collect();
+
+    let resolution = algebra::resolve_conflicts(terms, &membership, conflicts)?;
+    if resolution.duplicates > 0 {
+        log::info!(
+            "skipping {} term(s) that repeat an earlier term",
+            resolution.duplicates
+        );
+    }
+    Ok(resolution)
+}
+
+/// Returns the variations from which `term` selects more than one distinct variant.
+fn term_conflicts(term: &[String], variations: &[VariationInfo]) -> Vec<TermConflict> {
+    variations
+        .iter()
+        .filter_map(|variation| {
+            let mut selected: Vec<String> = vec![];
+            for variant in term {
+                if variation.variants.contains(variant) && !selected.contains(variant) {
+                    selected.push(variant.clone());
                 }
             }
-            std::fs::write(path, base_source)?;
+            (selected.len() > 1).then(|| TermConflict {
+                variation: variation.name.clone(),
+                path: variation.path.clone(),
+                line: variation.line,
+                variants: selected,
+            })
+        })
+        .collect()
+}
 
-            let file_name = path
-                .file_name()
-                .and_then(|name| name.to_str())
-                .ok_or_else(|| ApiError::ProjectError("invalid source filename".to_string()))?;
-            let out_path = path.with_file_name(format!("{file_name}.match_replace.json"));
-            std::fs::write(&out_path, converted)?;
-            return Ok(out_path);
+/// Restores the variants recorded by an interrupted test run to base.
+///
+/// # Arguments
+///
+/// * `project` - The project the interrupted run was testing (mutably)
+///
+/// # Returns
+///
+/// * `Ok(Vec<String>)` - The variants that were still active and have been reset, empty if
+///   no run was interrupted
+/// * `Err(ApiError)` - If the journal cannot be read or the files cannot be written
+pub fn recover(project: &mut Project) -> Result<Vec<String>, ApiError> {
+    let Some(journal) = Journal::load(&project.root)? else {
+        return Ok(vec![]);
+    };
+
+    let active = project
+        .active_variants()
+        .into_iter()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let mut restored = vec![];
+    for variant in journal.variants {
+        if !active.contains(&variant) {
+            continue;
         }
-        ConversionTarget::Comment => {
-            let extension = path
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .ok_or_else(|| {
-                    ApiError::ProjectError("file extension is not valid unicode".to_string())
-                })?;
-            let content = std::fs::read_to_string(path)?;
+        project.unset(&variant)?;
+        restored.push(variant);
+    }
+    Journal::remove(&project.root)?;
 
-            if extension == "rs" {
-                if let Some(language) =
-                    crate::syntax::functional::functional_language_for_extension(extension)
-                {
-                    if crate::syntax::functional::looks_like_mutations(language, &content) {
-                        let converted =
-                            crate::syntax::functional::render_comment_code_from_functional(
-                                language, &content,
-                            )
-                            .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-                        std::fs::write(path, converted)?;
-                        return Ok(path.to_path_buf());
-                    }
-                }
-            }
+    Ok(restored)
+}
 
-            if crate::syntax::preprocessor::looks_like_mutations(&content) {
-                let language =
-                    Language::extension_to_language(extension, &vec![]).unwrap_or(Language::Rust);
-                let converted = crate::syntax::preprocessor::render_comment_code_from_preprocessor(
-                    language, &content,
-                )
-                .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-                std::fs::write(path, converted)?;
-            } else if crate::syntax::patch::looks_like_mutations(&content) {
-                let (source_path, converted) =
-                    crate::syntax::patch::render_comment_code_from_patch(path, &content)
-                        .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-                std::fs::write(&source_path, converted)?;
-                return Ok(source_path);
-            } else if crate::syntax::match_replace::looks_like_mutations(&content) {
-                let (source_path, converted) =
-                    crate::syntax::match_replace::render_comment_code_from_match_replace(&content)
-                        .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-                std::fs::write(&source_path, converted)?;
-                return Ok(source_path);
-            } else {
-                return Err(ApiError::ProjectError(
-                    "unable to detect source syntax for conversion to comment".to_string(),
-                ));
-            }
+/// Returns the campaign currently in progress for the project, if any.
+pub fn campaign_status(project: &Project) -> Result<Option<Campaign>, ApiError> {
+    Ok(Campaign::load(&project.root)?)
+}
+
+/// Applies the term following the current one.
+///
+/// After the last term the project is reset to base and `Ok(None)` is returned, calling
+/// it again starts the campaign over.
+pub fn next_term(project: &mut Project) -> Result<Option<CampaignStep>, ApiError> {
+    let campaign = load_campaign(project)?;
+    let target = match campaign.cursor {
+        None => 0,
+        Some(index) => index + 1,
+    };
+    let target = (target < campaign.terms.len()).then_some(target);
+    goto_term(project, target)
+}
+
+/// Applies the term preceding the current one.
+///
+/// Stepping back from the first term resets the project to base and returns `Ok(None)`.
+pub fn prev_term(project: &mut Project) -> Result<Option<CampaignStep>, ApiError> {
+    let campaign = load_campaign(project)?;
+    let target = campaign.cursor.and_then(|index| index.checked_sub(1));
+    goto_term(project, target)
+}
+
+/// Moves the campaign to the term at `index`, or back to base when `index` is `None`.
+///
+/// The variants of the previously applied term are reset before the new term is applied.
+pub fn goto_term(
+    project: &mut Project,
+    index: Option<usize>,
+) -> Result<Option<CampaignStep>, ApiError> {
+    let mut campaign = load_campaign(project)?;
+    let total = campaign.terms.len();
+    if let Some(index) = index {
+        if index >= total {
+            return Err(ApiError::ProjectError(format!(
+                "term index {} is out of range, the campaign has {} term(s)",
+                index, total
+            )));
         }
     }
 
-    Ok(path.to_path_buf())
+    if let Some(current) = campaign.current().cloned() {
+        let mut expected = current.clone();
+        expected.sort();
+        let mut active = project
+            .active_variants()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        active.sort();
+        if active != expected {
+            log::warn!(
+                "active variants {:?} do not match the current campaign term {:?}",
+                active,
+                current
+            );
+        }
+        for variant in &current {
+            project.unset(variant)?;
+        }
+    }
+
+    campaign.cursor = index;
+    let step = match index {
+        Some(index) => {
+            let term = campaign.terms[index].clone();
+            project.set_many(&term)?;
+            Some(CampaignStep { index, total, term })
+        }
+        None => None,
+    };
+    campaign.save(&project.root)?;
+
+    Ok(step)
 }
 
-/// Imports externally generated Rust mutants into Marauders comment mutation syntax.
+/// Stops the campaign in progress, resetting its current term to base and removing
+/// the persisted state.
+pub fn stop_campaign(project: &mut Project) -> Result<Campaign, ApiError> {
+    let campaign = load_campaign(project)?;
+    if let Some(current) = campaign.current() {
+        for variant in current {
+            project.unset(variant)?;
+        }
+    }
+    Campaign::remove(&project.root)?;
+    Ok(campaign)
+}
+
+fn load_campaign(project: &Project) -> Result<Campaign, ApiError> {
+    Campaign::load(&project.root)?.ok_or_else(|| {
+        ApiError::ProjectError(format!(
+            "no campaign in progress at '{}', start one with `marauders start`",
+            project.root.display()
+        ))
+    })
+}
+
+/// Runs a test command against every term of a mutation expression.
 ///
-/// The `base_path` must point to the original `.rs` file.
-/// Each path in `mutant_paths` must point to a mutated variant of the same file.
-pub fn import_rust_mutants(
-    base_path: &Path,
-    mutant_paths: &[PathBuf],
-    output_path: Option<&Path>,
-    name_prefix: &str,
-) -> Result<PathBuf, ApiError> {
-    if !base_path.is_file() {
+/// The command is first run on the base program; if it fails the campaign is aborted,
+/// unless `options.allow_failing_baseline` is set. Each term is then applied (in the
+/// working tree, or in a fresh copy of the project when `options.copy` is set or more than
+/// one job is requested, in which case `options.jobs` terms run concurrently), the command
+/// is run at the project root under the per-mutant timeout and its outcome is recorded.
+/// The timeout is `options.timeout`, falling back to the `timeout` key of `marauder.toml`,
+/// and otherwise derived from the duration of the baseline run with [`derive_timeout`]. A
+/// command that runs out of time is killed along with its process group. The working tree
+/// is reset to base once all terms have run.
+///
+/// # Arguments
+///
+/// * `project` - The project to test, must have no active variants
+/// * `expr` - The mutation expression whose terms are tested
+/// * `command` - The shell command that runs the tests
+/// * `options` - How the terms are run
+/// * `on_result` - Called with each result as soon as its term finishes, starting with the
+///   baseline (whose `is_baseline` is set); concurrent terms are reported as they complete
+///
+/// # Returns
+///
+/// * `Ok(TestReport)` - The baseline, the results of all terms in enumeration order, and
+///   their summary
+/// * `Err(ApiError)` - If some files of the project could not be parsed, the project has
+///   active variants, the expression is invalid, or the baseline run fails
+pub fn run_tests(
+    project: &mut Project,
+    expr: &str,
+    command: &str,
+    options: &RunOptions,
+    mut on_result: impl FnMut(&TermResult),
+) -> Result<TestReport, ApiError> {
+    if let Some(journal) = Journal::load(&project.root)? {
         return Err(ApiError::ProjectError(format!(
-            "base path '{}' is not a file",
-            base_path.display()
+            "a previous test run was interrupted while {:?} were active, run `marauders recover` to restore them to base",
+            journal.variants
         )));
     }
-    if base_path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+    // The mutants of files that could not be read would be missing from the mutation score
+    if !project.parse_errors.is_empty() {
         return Err(ApiError::ProjectError(format!(
-            "Rust mutant import expects a .rs base file (got '{}')",
-            base_path.display()
+            "failed to parse {} file(s), fix them before testing:\n{}",
+            project.parse_errors.len(),
+            project.parse_errors.join("\n")
         )));
     }
-    if mutant_paths.is_empty() {
+    let active_variants = project.active_variants();
+    if !active_variants.is_empty() {
         return Err(ApiError::ProjectError(
-            "expected at least one mutant path".to_string(),
+            "test command is only available when there are no active variants in the project, if you would like run the test command, please first run `marauders reset`.".to_string(),
         ));
     }
 
-    let base_source = normalize_external_mutant_source(&std::fs::read_to_string(base_path)?);
-    let mut mutant_sources = Vec::new();
-    for mutant_path in mutant_paths {
-        if !mutant_path.is_file() {
-            return Err(ApiError::ProjectError(format!(
-                "mutant path '{}' is not a file",
-                mutant_path.display()
-            )));
+    let algebra::Resolution { terms, dropped, .. } =
+        resolve_terms(project, expr, options.conflicts)?;
+    let (terms, skipped) = if options.skip_equivalent {
+        let equivalent = equivalent_everywhere(project);
+        terms
+            .into_iter()
+            .partition(|term| !term.iter().any(|variant| equivalent.contains(variant)))
+    } else {
+        (terms, vec![])
+    };
+    if !skipped.is_empty() {
+        log::info!(
+            "skipping {} term(s) that select an equivalent variant",
+            skipped.len()
+        );
+    }
+
+    let configured_timeout = options.timeout.or_else(|| {
+        project
+            .config
+            .as_ref()
+            .and_then(|config| config.timeout)
+            .map(Duration::from_secs)
+    });
+
+    let jobs = options.jobs.max(1);
+    // Functional mutations are selected through the environment, the files never change, so
+    // there is nothing to isolate.
+    let functional_env = functional_env_vars(project, &terms);
+    if functional_env.is_some() {
+        log::info!("all targeted variations use functional syntax, activating variants through environment variables");
+        if options.copy {
+            log::warn!("ignoring copy mode, functional mutations run in the project root");
         }
-        if mutant_path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+    }
+    let copy = (options.copy || jobs > 1) && functional_env.is_none();
+
+    let build = options.build.clone().or_else(|| {
+        project
+            .config
+            .as_ref()
+            .and_then(|config| config.build.clone())
+    });
+    // The build is only limited by a configured timeout, its duration has nothing to do with
+    // the duration of the test suite.
+    let mut commands = TermCommands {
+        build: build.as_deref(),
+        build_timeout: configured_timeout,
+        test: command,
+        timeout: configured_timeout,
+    };
+    // The sources of functional mutations are the same for every term, so they are built
+    // once, and the baseline and the terms only run the test command.
+    if let Some(build) = commands.build.filter(|_| functional_env.is_some()) {
+        commands.build = None;
+        match project.run_with_timeout(build, commands.build_timeout)? {
+            Some(output) if output.status.success() => {}
+            output => {
+                return Err(ApiError::ProjectError(format!(
+                    "the build command fails on the base program ({})",
+                    match output.and_then(|output| output.status.code()) {
+                        Some(code) => format!("exit code {}", code),
+                        None => "timed out or terminated by a signal".to_string(),
+                    }
+                )))
+            }
+        }
+    }
+
+    let mut baseline = run_term(project, &[], &commands, copy)?;
+    baseline.is_baseline = true;
+    if recovery::interrupted() {
+        return Err(ApiError::ProjectError(
+            "interrupted while running the baseline".to_string(),
+        ));
+    }
+    on_result(&baseline);
+    if baseline.outcome != TestOutcome::Survived {
+        if !options.allow_failing_baseline {
             return Err(ApiError::ProjectError(format!(
-                "Rust mutant import expects .rs mutants (got '{}')",
-                mutant_path.display()
+                "the test command fails on the base program ({}), fix the test suite before running mutants or pass `--allow-failing-baseline`",
+                describe_failure(&baseline)
             )));
         }
-        mutant_sources.push(normalize_external_mutant_source(&std::fs::read_to_string(
-            mutant_path,
-        )?));
+        log::warn!(
+            "the test command fails on the base program ({}), every mutant will be reported as killed",
+            describe_failure(&baseline)
+        );
     }
 
-    let converted = crate::syntax::functional::import_rust_mutants_from_files(
-        &base_source,
-        &mutant_sources,
-        name_prefix,
-    )
-    .map_err(|e| ApiError::ProjectError(e.to_string()))?;
-
-    let destination = output_path.unwrap_or(base_path);
-    std::fs::write(destination, converted)?;
-    Ok(destination.to_path_buf())
-}
+    let timeout = configured_timeout
+        .unwrap_or_else(|| derive_timeout(Duration::from_millis(baseline.duration_ms)));
+    log::info!("running terms with a timeout of {:?}", timeout);
+    commands.timeout = Some(timeout);
+    let commands = &commands;
+
+    // The term that was running when the run was interrupted, when run one at a time
+    let mut cut_short: Option<&Vec<String>> = None;
+    let results = if let Some(env) = &functional_env {
+        let project: &Project = project;
+        let run = |term: &[String]| run_term_with_env(project, term, env, commands);
+        if jobs > 1 {
+            run_terms_in_parallel(&terms, jobs, run, &mut on_result)
+        } else {
+            let mut results = Vec::with_capacity(terms.len());
+            for term in &terms {
+                let result = run(term);
+                if recovery::interrupted() {
+                    cut_short = Some(term);
+                    break;
+                }
+                on_result(&result);
+                results.push(result);
+            }
+            results
+        }
+    } else if jobs > 1 {
+        let project: &Project = project;
+        let run = |term: &[String]| run_term_in_workspace(project, term, commands);
+        run_terms_in_parallel(&terms, jobs, run, &mut on_result)
+    } else {
+        let mut results = Vec::with_capacity(terms.len());
+        for term in &terms {
+            let result = run_term(project, term, commands, copy)?;
+            if recovery::interrupted() {
+                cut_short = Some(term);
+                break;
+            }
+            on_result(&result);
+            results.push(result);
+        }
+        results
+    };
 
-/// Imports Rust mutants directly from a cargo-mutants output directory.
-///
-/// This reads unified diff files from `<cargo_mutants_dir>/diff` (or directly from
-/// `cargo_mutants_dir` if no `diff` subdirectory exists), applies matching diffs to
-/// `base_path`, and imports the resulting mutants into Marauders comment syntax.
-pub fn import_rust_mutants_from_cargo_mutants_output(
-    base_path: &Path,
-    cargo_mutants_dir: &Path,
-    output_path: Option<&Path>,
-    name_prefix: &str,
-) -> Result<PathBuf, ApiError> {
-    if !base_path.is_file() {
-        return Err(ApiError::ProjectError(format!(
-            "base path '{}' is not a file",
-            base_path.display()
-        )));
-    }
-    if base_path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
-        return Err(ApiError::ProjectError(format!(
-            "Rust mutant import expects a .rs base file (got '{}')",
-            base_path.display()
-        )));
+    // Rewriting the files would turn functional mutations back into comment syntax.
+    if functional_env.is_none() {
+        project.reset()?;
     }
-    if !cargo_mutants_dir.is_dir() {
+    Journal::remove(&project.root)?;
+
+    if recovery::interrupted() {
+        let running = cut_short
+            .map(|term| format!(" while running {:?}", term))
+            .unwrap_or_default();
         return Err(ApiError::ProjectError(format!(
-            "cargo-mutants dir '{}' is not a directory",
-            cargo_mutants_dir.display()
+            "interrupted{} after {} of {} term(s), the project was restored to base",
+            running,
+            results.len(),
+            terms.len()
         )));
     }
 
-    let base_source = normalize_external_mutant_source(&std::fs::read_to_string(base_path)?);
-    let mutant_sources = collect_mutant_sources_from_cargo_mutants_output(
-        base_path,
-        cargo_mutants_dir,
-        &base_source,
-    )?;
-    if mutant_sources.is_empty() {
-        return Err(ApiError::ProjectError(format!(
-            "no applicable Rust mutants for '{}' were found in '{}'",
-            base_path.display(),
-            cargo_mutants_dir.display()
-        )));
-    }
-
-    let converted = crate::syntax::functional::import_rust_mutants_from_files(
-        &base_source,
-        &mutant_sources,
-        name_prefix,
-    )
-    .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+    let summary = MutationSummary::new(
+        &baseline,
+        &results,
+        &project.tag_map(),
+        &project.variation_map(),
+    );
 
-    let destination = output_path.unwrap_or(base_path);
-    std::fs::write(destination, converted)?;
-    Ok(destination.to_path_buf())
+    Ok(TestReport {
+        expr: expr.to_string(),
+        command: command.to_string(),
+        baseline,
+        timeout_ms: timeout.as_millis() as u64,
+        results,
+        dropped,
+        skipped,
+        summary,
+    })
 }
 
-/// Runs `cargo mutants` for the project containing `base_path` and imports the generated
-/// mutants into Marauders comment mutation syntax.
-///
-/// This is a convenience wrapper for fully automated external-mutant import:
-/// 1) discover Cargo project root from `base_path`,
-/// 2) run `cargo mutants --check --output <tmpdir>`,
-/// 3) import diff output for `base_path` into `output_path`.
-pub fn auto_generate_and_import_rust_mutants(
-    base_path: &Path,
-    output_path: &Path,
-    name_prefix: &str,
-    write_diffs_to_workdir: bool,
-) -> Result<PathBuf, ApiError> {
-    if !base_path.is_file() {
-        return Err(ApiError::ProjectError(format!(
-            "base path '{}' is not a file",
-            base_path.display()
-        )));
-    }
-    if base_path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
-        return Err(ApiError::ProjectError(format!(
-            "Rust mutant import expects a .rs base file (got '{}')",
-            base_path.display()
-        )));
+fn describe_failure(result: &TermResult) -> String {
+    match (&result.outcome, result.exit_code, &result.error) {
+        (_, _, Some(error)) => error.clone(),
+        (TestOutcome::TimedOut, _, _) => "timed out".to_string(),
+        (_, Some(code), _) => format!("exit code {}", code),
+        (_, None, _) => "terminated by a signal".to_string(),
     }
+}
 
-    let pid = std::process::id();
-    let nanos = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|d| d.as_nanos())
-        .unwrap_or(0);
-    let synthetic_project_root =
-        std::env::temp_dir().join(format!("marauders_single_file_project_{pid}_{nanos}"));
-    let (project_root, project_file_rel, cleanup_synthetic_project) =
-        if let Some(project_root) = find_cargo_project_root(base_path) {
-            (
-                project_root.clone(),
-                base_path
-                    .strip_prefix(&project_root)
-                    .ok()
-                    .map(PathBuf::from),
-                false,
-            )
-        } else {
-            let rel = create_single_file_cargo_project(base_path, &synthetic_project_root)?;
-            (synthetic_project_root.clone(), Some(rel), true)
-        };
+/// Commands run for each term of a test campaign.
+struct TermCommands<'a> {
+    /// Command building the mutant, a failure makes it stillborn
+    build: Option<&'a str>,
+    build_timeout: Option<Duration>,
+    /// Command testing the mutant
+    test: &'a str,
+    timeout: Option<Duration>,
+}
 
-    let cargo_mutants_out =
-        std::env::temp_dir().join(format!("marauders_cargo_mutants_{pid}_{nanos}"));
-    std::fs::create_dir_all(&cargo_mutants_out)?;
+/// How far a term got before its outcome was decided.
+enum TermRun {
+    /// The test command ran for `duration`, `output` is `None` if it ran out of time
+    Tested {
+        output: Option<Output>,
+        duration: Duration,
+    },
+    /// The mutant does not compile, with the output of the build command if it ran
+    Stillborn {
+        reason: String,
+        output: Option<Output>,
+    },
+}
 
-    let mut command = Command::new("cargo");
-    command
-        .arg("mutants")
-        .arg("--check")
-        .arg("--baseline")
-        .arg("skip")
-        .arg("--output")
-        .arg(&cargo_mutants_out);
-    if let Some(rel) = &project_file_rel {
-        command.arg("--file").arg(rel);
-    } else if let Ok(rel) = base_path.strip_prefix(&project_root) {
-        command.arg("--file").arg(rel);
+/// Runs the commands with the variants of `term` active, leaving the working tree at base.
+fn run_term(
+    project: &mut Project,
+    term: &[String],
+    commands: &TermCommands,
+    copy: bool,
+) -> anyhow::Result<TermResult> {
+    if copy {
+        return Ok(run_term_in_workspace(project, term, commands));
     }
-    let run = command.current_dir(&project_root).output().map_err(|e| {
-        ApiError::ProjectError(format!(
-            "failed to run 'cargo mutants' in '{}': {}",
-            project_root.display(),
-            e
-        ))
-    })?;
-    let copied_diffs = if write_diffs_to_workdir {
-        Some(copy_cargo_mutants_diffs_to_workdir(&cargo_mutants_out))
-    } else {
-        None
-    };
-
-    let result = import_rust_mutants_from_cargo_mutants_output(
-        base_path,
-        &cargo_mutants_out,
-        Some(output_path),
-        name_prefix,
-    );
 
-    if let Some(Err(copy_err)) = copied_diffs {
-        let _ = std::fs::remove_dir_all(&cargo_mutants_out);
-        if cleanup_synthetic_project {
-            let _ = std::fs::remove_dir_all(&project_root);
+    // Record the term before applying it, so `marauders recover` can restore it if the run
+    // is killed before the variants are unset.
+    if !term.is_empty() {
+        Journal {
+            variants: term.to_vec(),
         }
-        return Err(copy_err);
+        .save(&project.root)?;
     }
 
-    if result.is_err() && !run.status.success() {
-        let stderr = String::from_utf8_lossy(&run.stderr);
-        let stdout = String::from_utf8_lossy(&run.stdout);
-        let detail = if !stderr.trim().is_empty() {
-            stderr.trim()
-        } else {
-            stdout.trim()
-        };
-        let _ = std::fs::remove_dir_all(&cargo_mutants_out);
-        if cleanup_synthetic_project {
-            let _ = std::fs::remove_dir_all(&project_root);
-        }
-        return Err(ApiError::ProjectError(format!(
-            "'cargo mutants' failed for '{}': {}",
-            project_root.display(),
-            detail
-        )));
+    let start = Instant::now();
+    let run = project
+        .set_many(&term.to_vec())
+        .and_then(|_| run_applied(project, term, commands, &[]));
+    let duration = start.elapsed();
+    for variant in term {
+        project.unset(variant)?;
     }
+    Journal::remove(&project.root)?;
+    Ok(TermResult::new(term, run, duration))
+}
 
-    let _ = std::fs::remove_dir_all(&cargo_mutants_out);
-    if cleanup_synthetic_project {
-        let _ = std::fs::remove_dir_all(&project_root);
-    }
-    result
+/// Runs the commands with the variants of `term` active in a fresh copy of the project.
+fn run_term_in_workspace(
+    project: &Project,
+    term: &[String],
+    commands: &TermCommands,
+) -> TermResult {
+    let start = Instant::now();
+    let run = Workspace::new(project).and_then(|workspace| {
+        let mut copy = workspace.project()?;
+        copy.set_many(&term.to_vec())?;
+        run_applied(&copy, term, commands, &[])
+    });
+    TermResult::new(term, run, start.elapsed())
 }
 
-/// Collects Rust mutant file paths from a directory (e.g. `cargo-mutants` output).
+/// Checks that the mutant with `term` applied compiles, then runs the test command, both
+/// with the additional environment variables `env`.
 ///
-/// Files are filtered to `.rs` files with the same file name as `base_path`,
-/// parseable Rust source, and source content different from the base file.
-pub fn collect_rust_mutants_from_dir(
-    base_path: &Path,
-    mutants_dir: &Path,
-) -> Result<Vec<PathBuf>, ApiError> {
-    if !base_path.is_file() {
-        return Err(ApiError::ProjectError(format!(
-            "base path '{}' is not a file",
-            base_path.display()
-        )));
-    }
-    if !mutants_dir.is_dir() {
-        return Err(ApiError::ProjectError(format!(
-            "mutants dir '{}' is not a directory",
-            mutants_dir.display()
-        )));
+/// Rust files holding the variants of `term` are parsed first, so that mutants that are not
+/// even syntactically valid never reach the build command.
+fn run_applied(
+    project: &Project,
+    term: &[String],
+    commands: &TermCommands,
+    env: &[(String, String)],
+) -> anyhow::Result<TermRun> {
+    let mut files: Vec<PathBuf> = term
+        .iter()
+        .flat_map(|variant| files_with_variant(project, variant))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rs"))
+        .collect();
+    files.sort();
+    files.dedup();
+    for path in files {
+        let source = std::fs::read_to_string(&path)?;
+        if !is_parseable_rust_source(&source) {
+            let path = path.strip_prefix(&project.root).unwrap_or(&path);
+            return Ok(TermRun::Stillborn {
+                reason: format!("'{}' does not parse", path.to_string_lossy()),
+                output: None,
+            });
+        }
     }
-    if base_path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
-        return Err(ApiError::ProjectError(format!(
-            "Rust mutant import expects a .rs base file (got '{}')",
-            base_path.display()
-        )));
+
+    if let Some(build) = commands.build {
+        match project.run_with_env(build, env, commands.build_timeout)? {
+            Some(output) if output.status.success() => {}
+            Some(output) => {
+                return Ok(TermRun::Stillborn {
+                    reason: match output.status.code() {
+                        Some(code) => format!("build command failed with exit code {}", code),
+                        None => "build command was terminated by a signal".to_string(),
+                    },
+                    output: Some(output),
+                })
+            }
+            None => {
+                return Ok(TermRun::Stillborn {
+                    reason: "build command timed out".to_string(),
+                    output: None,
+                })
+            }
+        }
     }
 
-    let base_source = normalize_external_mutant_source(&std::fs::read_to_string(base_path)?);
-    let base_name = base_path
-        .file_name()
-        .and_then(|name| name.to_str())
-        .ok_or_else(|| ApiError::ProjectError("base file name is not valid unicode".to_string()))?;
+    let start = Instant::now();
+    let output = project.run_with_env(commands.test, env, commands.timeout)?;
+    Ok(TermRun::Tested {
+        output,
+        duration: start.elapsed(),
+    })
+}
 
-    let base_canonical = std::fs::canonicalize(base_path).ok();
-    let base_rel_to_cwd = std::env::current_dir()
-        .ok()
-        .and_then(|cwd| base_path.strip_prefix(cwd).ok().map(|p| p.to_path_buf()));
+/// Runs the commands with the variants of `term` activated through the environment
+/// variables of functional mutations, without touching the files.
+fn run_term_with_env(
+    project: &Project,
+    term: &[String],
+    env_vars: &HashMap<String, String>,
+    commands: &TermCommands,
+) -> TermResult {
+    let env: Vec<(String, String)> = term
+        .iter()
+        .filter_map(|variant| env_vars.get(variant))
+        .map(|env_var| (env_var.clone(), "active".to_string()))
+        .collect();
+    let start = Instant::now();
+    let run = run_applied(project, term, commands, &env);
+    TermResult::new(term, run, start.elapsed())
+}
 
-    let mut preferred = Vec::<PathBuf>::new();
-    let mut fallback = Vec::<PathBuf>::new();
+/// Returns the environment variable activating each targeted variant, if every variation
+/// targeted by `terms` is written in functional syntax.
+fn functional_env_vars(
+    project: &Project,
+    terms: &[Vec<String>],
+) -> Option<HashMap<String, String>> {
+    let targeted: HashSet<&String> = terms.iter().flatten().collect();
+    if targeted.is_empty() {
+        return None;
+    }
 
-    for entry in walk_files_recursive(mutants_dir)? {
-        if entry.extension().and_then(|ext| ext.to_str()) != Some("rs") {
-            continue;
-        }
-        if entry.file_name().and_then(|name| name.to_str()) != Some(base_name) {
-            continue;
-        }
-        if let Some(base_canonical) = &base_canonical {
-            if let Ok(candidate_canonical) = std::fs::canonicalize(&entry) {
-                if &candidate_canonical == base_canonical {
-                    continue;
+    let mut env_vars = HashMap::new();
+    for file in &project.files {
+        let variations: Vec<_> = file
+            .code
+            .spans
+            .iter()
+            .filter_map(|span| match &span.content {
+                SpanContent::Variation(v)
+                    if v.variants
+                        .iter()
+                        .any(|variant| targeted.contains(&variant.name)) =>
+                {
+                    Some(v)
                 }
-            }
-        }
-
-        let source = match std::fs::read_to_string(&entry) {
-            Ok(source) => normalize_external_mutant_source(&source),
-            Err(_) => continue,
-        };
-        if source == base_source {
-            continue;
-        }
-        if !is_parseable_rust_source(&source) {
+                _ => None,
+            })
+            .collect();
+        if variations.is_empty() {
             continue;
         }
 
-        if let Some(base_rel) = &base_rel_to_cwd {
-            if path_ends_with(&entry, base_rel) {
-                preferred.push(entry);
-            } else {
-                fallback.push(entry);
+        let language = file.code.functional?;
+        for variation in variations {
+            for variant in &variation.variants {
+                env_vars.insert(
+                    variant.name.clone(),
+                    crate::syntax::functional::mutation_env_var(
+                        language,
+                        variation.name.as_deref(),
+                        &variant.name,
+                    ),
+                );
             }
-        } else {
-            fallback.push(entry);
         }
     }
 
-    let mut candidates = if preferred.is_empty() {
-        fallback
-    } else {
-        preferred
-    };
-    candidates.sort();
+    targeted
+        .iter()
+        .all(|variant| env_vars.contains_key(*variant))
+        .then_some(env_vars)
+}
 
-    // Keep deterministic ordering but collapse exact content duplicates.
-    let mut unique = Vec::new();
-    let mut seen_contents = HashSet::new();
-    for candidate in candidates {
-        let source = normalize_external_mutant_source(&std::fs::read_to_string(&candidate)?);
-        if seen_contents.insert(source) {
-            unique.push(candidate);
+/// Runs the terms on `jobs` worker threads.
+///
+/// `on_result` is called in completion order, the returned results are in term order.
+fn run_terms_in_parallel(
+    terms: &[Vec<String>],
+    jobs: usize,
+    run: impl Fn(&[String]) -> TermResult + Sync,
+    on_result: &mut impl FnMut(&TermResult),
+) -> Vec<TermResult> {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let mut results: Vec<Option<TermResult>> = vec![None; terms.len()];
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(terms.len()) {
+            let sender = sender.clone();
+            let next = &next;
+            let run = &run;
+            scope.spawn(move || loop {
+                if recovery::interrupted() {
+                    break;
+                }
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(term) = terms.get(index) else {
+                    break;
+                };
+                let result = run(term);
+                // A term cut short by the interruption has no meaningful outcome.
+                if recovery::interrupted() || sender.send((index, result)).is_err() {
+                    break;
+                }
+            });
         }
-    }
+        drop(sender);
 
-    if unique.is_empty() {
-        return Err(ApiError::ProjectError(format!(
-            "no mutant files for '{}' were found in '{}'",
-            base_path.display(),
-            mutants_dir.display()
-        )));
-    }
+        for (index, result) in receiver {
+            on_result(&result);
+            results[index] = Some(result);
+        }
+    });
 
-    Ok(unique)
+    results.into_iter().flatten().collect()
 }
 
-fn walk_files_recursive(root: &Path) -> Result<Vec<PathBuf>, ApiError> {
-    let mut stack = vec![root.to_path_buf()];
-    let mut out = Vec::new();
+impl TermResult {
+    /// The result of `run`, which took `duration` in total. Tested terms only count the
+    /// duration of the test command, so that baseline-derived timeouts leave out copying,
+    /// applying and building the mutant.
+    fn new(term: &[String], run: anyhow::Result<TermRun>, duration: Duration) -> TermResult {
+        let variants = term.to_vec();
+        let duration_ms = match &run {
+            Ok(TermRun::Tested { duration, .. }) => duration,
+            _ => &duration,
+        }
+        .as_millis() as u64;
+        match run {
+            Ok(TermRun::Stillborn { reason, output }) => TermResult {
+                variants,
+                is_baseline: false,
+                exit_code: output.as_ref().and_then(|output| output.status.code()),
+                duration_ms,
+                stdout: output
+                    .as_ref()
+                    .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+                    .unwrap_or_default(),
+                stderr: output
+                    .as_ref()
+                    .map(|output| String::from_utf8_lossy(&output.stderr).to_string())
+                    .unwrap_or_default(),
+                outcome: TestOutcome::Stillborn,
+                error: Some(reason),
+            },
+            Ok(TermRun::Tested { output: None, .. }) => TermResult {
+                variants,
+                is_baseline: false,
+                exit_code: None,
+                duration_ms,
+                stdout: String::new(),
+                stderr: String::new(),
+                outcome: TestOutcome::TimedOut,
+                error: None,
+            },
+            Ok(TermRun::Tested {
+                output: Some(output),
+                ..
+            }) => TermResult {
+                variants,
+                is_baseline: false,
+                exit_code: output.status.code(),
+                duration_ms,
+                stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+                outcome: if output.status.success() {
+                    TestOutcome::Survived
+                } else {
+                    TestOutcome::Killed
+                },
+                error: None,
+            },
+            Err(err) => TermResult {
+                variants,
+                is_baseline: false,
+                exit_code: None,
+                duration_ms,
+                stdout: String::new(),
+                stderr: String::new(),
+                outcome: TestOutcome::Errored,
+                error: Some(format!("{:#}", err)),
+            },
+        }
+    }
+}
 
-    while let Some(dir) = stack.pop() {
-        let entries = std::fs::read_dir(&dir).map_err(|e| {
-            ApiError::ProjectError(format!(
-                "failed to read directory '{}': {}",
-                dir.display(),
-                e
-            ))
-        })?;
+/// Converts a file's mutation syntax in place.
+///
+/// Currently supported:
+/// - Rust comment syntax -> Rust functional syntax.
+/// - Rust functional syntax -> comment syntax.
+/// - Comment syntax -> preprocessor syntax.
+/// - Comment syntax -> patch syntax.
+/// - Comment syntax -> match-replace syntax.
+/// - Preprocessor syntax -> comment syntax.
+/// - Patch syntax -> comment syntax.
+/// - Match-replace syntax -> comment syntax.
+pub fn convert_file(path: &Path, target: ConversionTarget) -> Result<PathBuf, ApiError> {
+    let (plan, output) = plan_conversion(path, target)?;
 
-        for entry in entries {
-            let entry = entry.map_err(|e| ApiError::ProjectError(e.to_string()))?;
-            let path = entry.path();
-            if path.is_dir() {
-                stack.push(path);
-            } else if path.is_file() {
-                out.push(path);
-            }
-        }
+    let files: Vec<PathBuf> = plan.files.iter().map(|(file, _)| file.clone()).collect();
+    let dirs: Vec<PathBuf> = plan.bundle.iter().map(|(dir, _)| dir.clone()).collect();
+    let snapshot = take_snapshot(&History::root_for(path), &files, &dirs);
+
+    // The bundle is written first, so the mutations survive an interruption before the
+    // source is stripped.
+    if let Some((bundle_dir, rendered)) = &plan.bundle {
+        crate::syntax::patch::write_patch_bundle(bundle_dir, rendered)
+            .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+    }
+    for (file, content) in &plan.files {
+        crate::atomic::write(file, content)?;
     }
 
-    Ok(out)
+    record_history(
+        snapshot,
+        &format!("convert {} to {:?}", path.to_string_lossy(), target),
+        vec![],
+    );
+    Ok(output)
 }
 
-#[derive(Clone, Debug)]
-struct UnifiedFilePatch {
-    old_path: String,
-    new_path: String,
-    hunks: Vec<UnifiedHunk>,
+/// Files written by a conversion, computed before any of them is touched.
+#[derive(Default)]
+struct ConversionPlan {
+    /// Patch bundle directory replaced as a whole
+    bundle: Option<(PathBuf, crate::syntax::patch::PatchBundleRender)>,
+    /// Files replaced with new content, in order
+    files: Vec<(PathBuf, String)>,
 }
 
-#[derive(Clone, Debug)]
-struct UnifiedHunk {
-    old_start: usize,
-    old_count: usize,
-    lines: Vec<String>,
+impl ConversionPlan {
+    fn write(&mut self, path: &Path, content: impl Into<String>) {
+        self.files.push((path.to_path_buf(), content.into()));
+    }
 }
 
-fn collect_mutant_sources_from_cargo_mutants_output(
-    base_path: &Path,
-    cargo_mutants_dir: &Path,
-    base_source: &str,
-) -> Result<Vec<String>, ApiError> {
-    let diff_root = cargo_mutants_diff_root(cargo_mutants_dir);
+/// The language whose comment syntax a converted file is written in, resolved with the
+/// `file_languages` and custom languages of the closest `marauder.toml` above it.
+fn comment_language(path: &Path, content: &str) -> Result<Language, ApiError> {
+    let (matchers, custom_languages) = match ProjectConfig::find(path)? {
+        Some((root, config)) => (
+            config.file_language_matchers(&root)?,
+            config.custom_languages,
+        ),
+        None => (vec![], vec![]),
+    };
+    mapped_language(&matchers, path)
+        .or_else(|| Code::detect_language(path, content, &custom_languages))
+        .ok_or_else(|| {
+            ApiError::ProjectError(format!(
+                "unknown language for '{}', add it to the custom languages of marauder.toml",
+                path.display()
+            ))
+        })
+}
 
-    let mut diff_files = walk_files_recursive(&diff_root)?
-        .into_iter()
-        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("diff"))
-        .collect::<Vec<_>>();
-    diff_files.sort();
-    if diff_files.is_empty() {
+/// Computes the files written by converting `path` to `target`, and the path to report.
+fn plan_conversion(
+    path: &Path,
+    target: ConversionTarget,
+) -> Result<(ConversionPlan, PathBuf), ApiError> {
+    if !path.is_file() {
         return Err(ApiError::ProjectError(format!(
-            "no .diff files found in '{}'",
-            diff_root.display()
+            "path '{}' is not a file",
+            path.display()
         )));
     }
 
-    let base_name = base_path
-        .file_name()
-        .and_then(|name| name.to_str())
-        .ok_or_else(|| ApiError::ProjectError("base file name is not valid unicode".to_string()))?;
-    let base_rel_to_cwd = std::env::current_dir()
-        .ok()
-        .and_then(|cwd| base_path.strip_prefix(cwd).ok().map(|p| p.to_path_buf()));
-    let base_lines = base_source
-        .lines()
-        .map(ToString::to_string)
-        .collect::<Vec<_>>();
-    let base_has_trailing_newline = base_source.ends_with('\n');
+    let mut plan = ConversionPlan::default();
+    match target {
+        ConversionTarget::RustFunctional => {
+            let extension = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .ok_or_else(|| {
+                    ApiError::ProjectError("file extension is not valid unicode".to_string())
+                })?;
 
-    let mut sources = Vec::new();
-    let mut seen = HashSet::new();
+            if extension != "rs" {
+                return Err(ApiError::ProjectError(format!(
+                    "Rust functional conversion is only supported for .rs files (got '.{}')",
+                    extension
+                )));
+            }
 
-    for diff_file in diff_files {
-        let diff_text = match std::fs::read_to_string(&diff_file) {
-            Ok(text) => text,
-            Err(_) => continue,
-        };
-        let patches = match parse_unified_diff(&diff_text) {
-            Ok(patches) => patches,
-            Err(_) => continue,
-        };
-        let patch = match select_patch_for_base(&patches, base_name, base_rel_to_cwd.as_deref()) {
-            Some(patch) => patch,
-            None => continue,
-        };
+            let content = std::fs::read_to_string(path)?;
+            let language = crate::syntax::functional::functional_language_for_extension(extension)
+                .ok_or_else(|| {
+                    ApiError::ProjectError(format!(
+                        "no functional mutation backend is available for '.{}'",
+                        extension
+                    ))
+                })?;
+            let spans = crate::syntax::comment::parse_code(&content, &Language::Rust)
+                .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+            let converted =
+                crate::syntax::functional::render_functional_code(language, &content, &spans)
+                    .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+            plan.write(path, converted);
+        }
+        ConversionTarget::RustComment => {
+            let extension = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .ok_or_else(|| {
+                    ApiError::ProjectError("file extension is not valid unicode".to_string())
+                })?;
 
-        let mutated_lines = match apply_unified_patch(&base_lines, &patch.hunks) {
-            Ok(lines) => lines,
-            Err(_) => continue,
-        };
-        let mut mutated = mutated_lines.join("\n");
-        if base_has_trailing_newline {
-            mutated.push('\n');
+            if extension != "rs" {
+                return Err(ApiError::ProjectError(format!(
+                    "Rust comment conversion is only supported for .rs files (got '.{}')",
+                    extension
+                )));
+            }
+
+            let content = std::fs::read_to_string(path)?;
+            let language = crate::syntax::functional::functional_language_for_extension(extension)
+                .ok_or_else(|| {
+                    ApiError::ProjectError(format!(
+                        "no functional mutation backend is available for '.{}'",
+                        extension
+                    ))
+                })?;
+            let converted =
+                crate::syntax::functional::render_comment_code_from_functional(language, &content)
+                    .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+            plan.write(path, converted);
         }
-        mutated = normalize_external_mutant_source(&mutated);
-        if mutated == base_source {
-            continue;
+        ConversionTarget::Preprocessor => {
+            let content = std::fs::read_to_string(path)?;
+            let spans =
+                crate::syntax::comment::parse_code(&content, &comment_language(path, &content)?)
+                    .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+            let converted =
+                crate::syntax::preprocessor::render_preprocessor_code_from_comment(&spans)
+                    .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+            plan.write(path, converted);
         }
-        if !is_parseable_rust_source(&mutated) {
-            continue;
+        ConversionTarget::Patch => {
+            let content = std::fs::read_to_string(path)?;
+            let spans =
+                crate::syntax::comment::parse_code(&content, &comment_language(path, &content)?)
+                    .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+            let rendered = crate::syntax::patch::render_patch_bundle_from_comment(
+                &spans,
+                &path.to_string_lossy(),
+            )
+            .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+            let bundle_dir = crate::syntax::patch::patch_bundle_dir_for_source(path)
+                .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+            let manifest_path = crate::syntax::patch::manifest_path(&bundle_dir);
+            plan.write(path, rendered.base_source.clone());
+            plan.bundle = Some((bundle_dir, rendered));
+            return Ok((plan, manifest_path));
         }
-        if seen.insert(mutated.clone()) {
-            sources.push(mutated);
+        ConversionTarget::MatchReplace => {
+            let content = std::fs::read_to_string(path)?;
+            let spans =
+                crate::syntax::comment::parse_code(&content, &comment_language(path, &content)?)
+                    .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+            let converted = crate::syntax::match_replace::render_match_replace_code_from_comment(
+                &spans,
+                &path.to_string_lossy(),
+            )
+            .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+
+            // Keep the original source file as the base program (with mutations stripped).
+            let mut base_source = String::new();
+            for span in &spans {
+                match &span.content {
+                    SpanContent::Line(line) => base_source.push_str(line),
+                    SpanContent::Variation(variation) => {
+                        for line in variation.base.lines() {
+                            base_source.push_str(&line);
+                            base_source.push('\n');
+                        }
+                    }
+                }
+            }
+            let file_name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| ApiError::ProjectError("invalid source filename".to_string()))?;
+            let out_path = path.with_file_name(format!("{file_name}.match_replace.json"));
+            plan.write(&out_path, converted);
+            plan.write(path, base_source);
+            return Ok((plan, out_path));
         }
-    }
+        ConversionTarget::Comment => {
+            let extension = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .ok_or_else(|| {
+                    ApiError::ProjectError("file extension is not valid unicode".to_string())
+                })?;
+            let content = std::fs::read_to_string(path)?;
 
-    Ok(sources)
-}
+            if extension == "rs" {
+                if let Some(language) =
+                    crate::syntax::functional::functional_language_for_extension(extension)
+                {
+                    if crate::syntax::functional::looks_like_mutations(language, &content) {
+                        let converted =
+                            crate::syntax::functional::render_comment_code_from_functional(
+                                language, &content,
+                            )
+                            .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+                        plan.write(path, converted);
+                        return Ok((plan, path.to_path_buf()));
+                    }
+                }
+            }
 
-fn find_cargo_project_root(base_path: &Path) -> Option<PathBuf> {
-    let mut current = base_path.parent()?.to_path_buf();
-    loop {
-        if current.join("Cargo.toml").is_file() {
-            return Some(current);
-        }
-        if !current.pop() {
-            break;
+            if crate::syntax::preprocessor::looks_like_mutations(&content) {
+                let language = comment_language(path, &content)?;
+                let converted = crate::syntax::preprocessor::render_comment_code_from_preprocessor(
+                    language, &content,
+                )
+                .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+                plan.write(path, converted);
+            } else if crate::syntax::patch::looks_like_mutations(&content) {
+                let (source_path, converted) =
+                    crate::syntax::patch::render_comment_code_from_patch(path, &content)
+                        .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+                plan.write(&source_path, converted);
+                return Ok((plan, source_path));
+            } else if crate::syntax::match_replace::looks_like_mutations(&content) {
+                let (source_path, converted) =
+                    crate::syntax::match_replace::render_comment_code_from_match_replace(&content)
+                        .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+                plan.write(&source_path, converted);
+                return Ok((plan, source_path));
+            } else {
+                return Err(ApiError::ProjectError(
+                    "unable to detect source syntax for conversion to comment".to_string(),
+                ));
+            }
         }
     }
-    None
+
+    Ok((plan, path.to_path_buf()))
 }
 
-fn create_single_file_cargo_project(
-    input_file: &Path,
-    project_root: &Path,
-) -> Result<PathBuf, ApiError> {
-    std::fs::create_dir_all(project_root)?;
-    let file_name = input_file
-        .file_name()
-        .and_then(|name| name.to_str())
-        .ok_or_else(|| {
-            ApiError::ProjectError("input file name is not valid unicode".to_string())
-        })?;
-    let copied_file = project_root.join(file_name);
-    std::fs::copy(input_file, &copied_file).map_err(|e| {
+/// Imports externally generated Rust mutants into Marauders comment mutation syntax.
+///
+/// The `base_path` must point to the original `.rs` file.
+/// Each path in `mutant_paths` must point to a mutated variant of the same file.
+pub fn import_rust_mutants(
+    base_path: &Path,
+    mutant_paths: &[PathBuf],
+    output_path: Option<&Path>,
+    name_prefix: &str,
+) -> Result<PathBuf, ApiError> {
+    if !base_path.is_file() {
+        return Err(ApiError::ProjectError(format!(
+            "base path '{}' is not a file",
+            base_path.display()
+        )));
+    }
+    if base_path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+        return Err(ApiError::ProjectError(format!(
+            "Rust mutant import expects a .rs base file (got '{}')",
+            base_path.display()
+        )));
+    }
+    if mutant_paths.is_empty() {
+        return Err(ApiError::ProjectError(
+            "expected at least one mutant path".to_string(),
+        ));
+    }
+
+    let base_source = normalize_external_mutant_source(&std::fs::read_to_string(base_path)?);
+    let mut mutant_sources = Vec::new();
+    for mutant_path in mutant_paths {
+        if !mutant_path.is_file() {
+            return Err(ApiError::ProjectError(format!(
+                "mutant path '{}' is not a file",
+                mutant_path.display()
+            )));
+        }
+        if mutant_path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+            return Err(ApiError::ProjectError(format!(
+                "Rust mutant import expects .rs mutants (got '{}')",
+                mutant_path.display()
+            )));
+        }
+        mutant_sources.push(normalize_external_mutant_source(&std::fs::read_to_string(
+            mutant_path,
+        )?));
+    }
+
+    let converted = crate::syntax::functional::import_rust_mutants_from_files(
+        &base_source,
+        &mutant_sources,
+        name_prefix,
+    )
+    .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+
+    let destination = output_path.unwrap_or(base_path);
+    crate::atomic::write(destination, converted)?;
+    Ok(destination.to_path_buf())
+}
+
+/// Imports Rust mutants directly from a cargo-mutants output directory.
+///
+/// This reads unified diff files from `<cargo_mutants_dir>/diff` (or directly from
+/// `cargo_mutants_dir` if no `diff` subdirectory exists), applies matching diffs to
+/// `base_path`, and imports the resulting mutants into Marauders comment syntax.
+pub fn import_rust_mutants_from_cargo_mutants_output(
+    base_path: &Path,
+    cargo_mutants_dir: &Path,
+    output_path: Option<&Path>,
+    name_prefix: &str,
+) -> Result<PathBuf, ApiError> {
+    if !base_path.is_file() {
+        return Err(ApiError::ProjectError(format!(
+            "base path '{}' is not a file",
+            base_path.display()
+        )));
+    }
+    if base_path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+        return Err(ApiError::ProjectError(format!(
+            "Rust mutant import expects a .rs base file (got '{}')",
+            base_path.display()
+        )));
+    }
+    if !cargo_mutants_dir.is_dir() {
+        return Err(ApiError::ProjectError(format!(
+            "cargo-mutants dir '{}' is not a directory",
+            cargo_mutants_dir.display()
+        )));
+    }
+
+    let base_source = normalize_external_mutant_source(&std::fs::read_to_string(base_path)?);
+    let mutant_sources = collect_mutant_sources_from_cargo_mutants_output(
+        base_path,
+        cargo_mutants_dir,
+        &base_source,
+    )?;
+    if mutant_sources.is_empty() {
+        return Err(ApiError::ProjectError(format!(
+            "no applicable Rust mutants for '{}' were found in '{}'",
+            base_path.display(),
+            cargo_mutants_dir.display()
+        )));
+    }
+
+    let converted = crate::syntax::functional::import_rust_mutants_from_files(
+        &base_source,
+        &mutant_sources,
+        name_prefix,
+    )
+    .map_err(|e| ApiError::ProjectError(e.to_string()))?;
+
+    let destination = output_path.unwrap_or(base_path);
+    crate::atomic::write(destination, converted)?;
+    Ok(destination.to_path_buf())
+}
+
+/// Runs `cargo mutants` for the project containing `base_path` and imports the generated
+/// mutants into Marauders comment mutation syntax.
+///
+/// This is a convenience wrapper for fully automated external-mutant import:
+/// 1) discover Cargo project root from `base_path`,
+/// 2) run `cargo mutants --check --output <tmpdir>`,
+/// 3) import diff output for `base_path` into `output_path`.
+pub fn auto_generate_and_import_rust_mutants(
+    base_path: &Path,
+    output_path: &Path,
+    name_prefix: &str,
+    write_diffs_to_workdir: bool,
+) -> Result<PathBuf, ApiError> {
+    if !base_path.is_file() {
+        return Err(ApiError::ProjectError(format!(
+            "base path '{}' is not a file",
+            base_path.display()
+        )));
+    }
+    if base_path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+        return Err(ApiError::ProjectError(format!(
+            "Rust mutant import expects a .rs base file (got '{}')",
+            base_path.display()
+        )));
+    }
+
+    let pid = std::process::id();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let synthetic_project_root =
+        std::env::temp_dir().join(format!("marauders_single_file_project_{pid}_{nanos}"));
+    let (project_root, project_file_rel, cleanup_synthetic_project) =
+        if let Some(project_root) = find_cargo_project_root(base_path) {
+            (
+                project_root.clone(),
+                base_path
+                    .strip_prefix(&project_root)
+                    .ok()
+                    .map(PathBuf::from),
+                false,
+            )
+        } else {
+            let rel = create_single_file_cargo_project(base_path, &synthetic_project_root)?;
+            (synthetic_project_root.clone(), Some(rel), true)
+        };
+
+    let cargo_mutants_out =
+        std::env::temp_dir().join(format!("marauders_cargo_mutants_{pid}_{nanos}"));
+    std::fs::create_dir_all(&cargo_mutants_out)?;
+
+    let mut command = Command::new("cargo");
+    command
+        .arg("mutants")
+        .arg("--check")
+        .arg("--baseline")
+        .arg("skip")
+        .arg("--output")
+        .arg(&cargo_mutants_out);
+    if let Some(rel) = &project_file_rel {
+        command.arg("--file").arg(rel);
+    } else if let Ok(rel) = base_path.strip_prefix(&project_root) {
+        command.arg("--file").arg(rel);
+    }
+    let run = command.current_dir(&project_root).output().map_err(|e| {
         ApiError::ProjectError(format!(
-            "failed to copy '{}' into synthetic Cargo project '{}': {}",
-            input_file.display(),
+            "failed to run 'cargo mutants' in '{}': {}",
             project_root.display(),
             e
         ))
     })?;
+    let copied_diffs = if write_diffs_to_workdir {
+        Some(copy_cargo_mutants_diffs_to_workdir(&cargo_mutants_out))
+    } else {
+        None
+    };
+
+    let result = import_rust_mutants_from_cargo_mutants_output(
+        base_path,
+        &cargo_mutants_out,
+        Some(output_path),
+        name_prefix,
+    );
+
+    if let Some(Err(copy_err)) = copied_diffs {
+        let _ = std::fs::remove_dir_all(&cargo_mutants_out);
+        if cleanup_synthetic_project {
+            let _ = std::fs::remove_dir_all(&project_root);
+        }
+        return Err(copy_err);
+    }
+
+    if result.is_err() && !run.status.success() {
+        let stderr = String::from_utf8_lossy(&run.stderr);
+        let stdout = String::from_utf8_lossy(&run.stdout);
+        let detail = if !stderr.trim().is_empty() {
+            stderr.trim()
+        } else {
+            stdout.trim()
+        };
+        let _ = std::fs::remove_dir_all(&cargo_mutants_out);
+        if cleanup_synthetic_project {
+            let _ = std::fs::remove_dir_all(&project_root);
+        }
+        return Err(ApiError::ProjectError(format!(
+            "'cargo mutants' failed for '{}': {}",
+            project_root.display(),
+            detail
+        )));
+    }
+
+    let _ = std::fs::remove_dir_all(&cargo_mutants_out);
+    if cleanup_synthetic_project {
+        let _ = std::fs::remove_dir_all(&project_root);
+    }
+    result
+}
+
+/// Collects Rust mutant file paths from a directory (e.g. `cargo-mutants` output).
+///
+/// Files are filtered to `.rs` files with the same file name as `base_path`,
+/// parseable Rust source, and source content different from the base file.
+pub fn collect_rust_mutants_from_dir(
+    base_path: &Path,
+    mutants_dir: &Path,
+) -> Result<Vec<PathBuf>, ApiError> {
+    if !base_path.is_file() {
+        return Err(ApiError::ProjectError(format!(
+            "base path '{}' is not a file",
+            base_path.display()
+        )));
+    }
+    if !mutants_dir.is_dir() {
+        return Err(ApiError::ProjectError(format!(
+            "mutants dir '{}' is not a directory",
+            mutants_dir.display()
+        )));
+    }
+    if base_path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+        return Err(ApiError::ProjectError(format!(
+            "Rust mutant import expects a .rs base file (got '{}')",
+            base_path.display()
+        )));
+    }
+
+    let base_source = normalize_external_mutant_source(&std::fs::read_to_string(base_path)?);
+    let base_name = base_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| ApiError::ProjectError("base file name is not valid unicode".to_string()))?;
+
+    let base_canonical = std::fs::canonicalize(base_path).ok();
+    let base_rel_to_cwd = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| base_path.strip_prefix(cwd).ok().map(|p| p.to_path_buf()));
+
+    let mut preferred = Vec::<PathBuf>::new();
+    let mut fallback = Vec::<PathBuf>::new();
+
+    for entry in walk_files_recursive(mutants_dir)? {
+        if entry.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+            continue;
+        }
+        if entry.file_name().and_then(|name| name.to_str()) != Some(base_name) {
+            continue;
+        }
+        if let Some(base_canonical) = &base_canonical {
+            if let Ok(candidate_canonical) = std::fs::canonicalize(&entry) {
+                if &candidate_canonical == base_canonical {
+                    continue;
+                }
+            }
+        }
+
+        let source = match std::fs::read_to_string(&entry) {
+            Ok(source) => normalize_external_mutant_source(&source),
+            Err(_) => continue,
+        };
+        if source == base_source {
+            continue;
+        }
+        if !is_parseable_rust_source(&source) {
+            continue;
+        }
+
+        if let Some(base_rel) = &base_rel_to_cwd {
+            if path_ends_with(&entry, base_rel) {
+                preferred.push(entry);
+            } else {
+                fallback.push(entry);
+            }
+        } else {
+            fallback.push(entry);
+        }
+    }
+
+    let mut candidates = if preferred.is_empty() {
+        fallback
+    } else {
+        preferred
+    };
+    candidates.sort();
+
+    // Keep deterministic ordering but collapse exact content duplicates.
+    let mut unique = Vec::new();
+    let mut seen_contents = HashSet::new();
+    for candidate in candidates {
+        let source = normalize_external_mutant_source(&std::fs::read_to_string(&candidate)?);
+        if seen_contents.insert(source) {
+            unique.push(candidate);
+        }
+    }
+
+    if unique.is_empty() {
+        return Err(ApiError::ProjectError(format!(
+            "no mutant files for '{}' were found in '{}'",
+            base_path.display(),
+            mutants_dir.display()
+        )));
+    }
+
+    Ok(unique)
+}
+
+fn walk_files_recursive(root: &Path) -> Result<Vec<PathBuf>, ApiError> {
+    let mut stack = vec![root.to_path_buf()];
+    let mut out = Vec::new();
+
+    while let Some(dir) = stack.pop() {
+        let entries = std::fs::read_dir(&dir).map_err(|e| {
+            ApiError::ProjectError(format!(
+                "failed to read directory '{}': {}",
+                dir.display(),
+                e
+            ))
+        })?;
+
+        for entry in entries {
+            let entry = entry.map_err(|e| ApiError::ProjectError(e.to_string()))?;
+            let path = entry.path();
+            if path.is_dir() {
+                stack.push(path);
+            } else if path.is_file() {
+                out.push(path);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+#[derive(Clone, Debug)]
+struct UnifiedFilePatch {
+    old_path: String,
+    new_path: String,
+    hunks: Vec<UnifiedHunk>,
+}
+
+#[derive(Clone, Debug)]
+struct UnifiedHunk {
+    old_start: usize,
+    old_count: usize,
+    lines: Vec<String>,
+}
+
+fn collect_mutant_sources_from_cargo_mutants_output(
+    base_path: &Path,
+    cargo_mutants_dir: &Path,
+    base_source: &str,
+) -> Result<Vec<String>, ApiError> {
+    let diff_root = cargo_mutants_diff_root(cargo_mutants_dir);
+
+    let mut diff_files = walk_files_recursive(&diff_root)?
+        .into_iter()
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("diff"))
+        .collect::<Vec<_>>();
+    diff_files.sort();
+    if diff_files.is_empty() {
+        return Err(ApiError::ProjectError(format!(
+            "no .diff files found in '{}'",
+            diff_root.display()
+        )));
+    }
+
+    let base_name = base_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| ApiError::ProjectError("base file name is not valid unicode".to_string()))?;
+    let base_rel_to_cwd = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| base_path.strip_prefix(cwd).ok().map(|p| p.to_path_buf()));
+    let base_lines = base_source
+        .lines()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let base_has_trailing_newline = base_source.ends_with('\n');
+
+    let mut sources = Vec::new();
+    let mut seen = HashSet::new();
+
+    for diff_file in diff_files {
+        let diff_text = match std::fs::read_to_string(&diff_file) {
+            Ok(text) => text,
+            Err(_) => continue,
+        };
+        let patches = match parse_unified_diff(&diff_text) {
+            Ok(patches) => patches,
+            Err(_) => continue,
+        };
+        let patch = match select_patch_for_base(&patches, base_name, base_rel_to_cwd.as_deref()) {
+            Some(patch) => patch,
+            None => continue,
+        };
+
+        let mutated_lines = match apply_unified_patch(&base_lines, &patch.hunks) {
+            Ok(lines) => lines,
+            Err(_) => continue,
+        };
+        let mut mutated = mutated_lines.join("\n");
+        if base_has_trailing_newline {
+            mutated.push('\n');
+        }
+        mutated = normalize_external_mutant_source(&mutated);
+        if mutated == base_source {
+            continue;
+        }
+        if !is_parseable_rust_source(&mutated) {
+            continue;
+        }
+        if seen.insert(mutated.clone()) {
+            sources.push(mutated);
+        }
+    }
+
+    Ok(sources)
+}
+
+fn find_cargo_project_root(base_path: &Path) -> Option<PathBuf> {
+    let mut current = base_path.parent()?.to_path_buf();
+    loop {
+        if current.join("Cargo.toml").is_file() {
+            return Some(current);
+        }
+        if !current.pop() {
+            break;
+        }
+    }
+    None
+}
+
+fn create_single_file_cargo_project(
+    input_file: &Path,
+    project_root: &Path,
+) -> Result<PathBuf, ApiError> {
+    std::fs::create_dir_all(project_root)?;
+    let file_name = input_file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| {
+            ApiError::ProjectError("input file name is not valid unicode".to_string())
+        })?;
+    let copied_file = project_root.join(file_name);
+    std::fs::copy(input_file, &copied_file).map_err(|e| {
+        ApiError::ProjectError(format!(
+            "failed to copy '{}' into synthetic Cargo project '{}': {}",
+            input_file.display(),
+            project_root.display(),
+            e
+        ))
+    })?;
+
+    let escaped_file_name = file_name.replace('\\', "\\\\").replace('"', "\\\"");
+    let cargo_toml = format!(
+        "[package]\nname = \"marauders_single_file\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[lib]\npath = \"{}\"\n",
+        escaped_file_name
+    );
+    std::fs::write(project_root.join("Cargo.toml"), cargo_toml)?;
+
+    Ok(PathBuf::from(file_name))
+}
+
+fn parse_unified_diff(input: &str) -> Result<Vec<UnifiedFilePatch>, ApiError> {
+    let lines = input.lines().collect::<Vec<_>>();
+    let mut patches = Vec::new();
+    let mut idx = 0usize;
+
+    while idx < lines.len() {
+        if !lines[idx].starts_with("--- ") {
+            idx += 1;
+            continue;
+        }
+
+        let old_path = parse_diff_path(lines[idx], "--- ");
+        idx += 1;
+        if idx >= lines.len() || !lines[idx].starts_with("+++ ") {
+            continue;
+        }
+        let new_path = parse_diff_path(lines[idx], "+++ ");
+        idx += 1;
+
+        let mut hunks = Vec::new();
+        while idx < lines.len() {
+            if lines[idx].starts_with("--- ") {
+                break;
+            }
+            if !lines[idx].starts_with("@@") {
+                idx += 1;
+                continue;
+            }
+
+            let (old_start, old_count) = parse_hunk_old_range(lines[idx]).ok_or_else(|| {
+                ApiError::ProjectError("failed to parse unified diff hunk header".to_string())
+            })?;
+            idx += 1;
+
+            let mut hunk_lines = Vec::new();
+            while idx < lines.len() {
+                let current = lines[idx];
+                if current.starts_with("@@") || current.starts_with("--- ") {
+                    break;
+                }
+                if current.starts_with('\\') {
+                    idx += 1;
+                    continue;
+                }
+                match current.chars().next() {
+                    Some(' ') | Some('+') | Some('-') => hunk_lines.push(current.to_string()),
+                    _ => {}
+                }
+                idx += 1;
+            }
+
+            hunks.push(UnifiedHunk {
+                old_start,
+                old_count,
+                lines: hunk_lines,
+            });
+        }
+
+        if !hunks.is_empty() {
+            patches.push(UnifiedFilePatch {
+                old_path,
+                new_path,
+                hunks,
+            });
+        }
+    }
+
+    Ok(patches)
+}
+
+fn parse_diff_path(line: &str, prefix: &str) -> String {
+    let raw = line
+        .strip_prefix(prefix)
+        .unwrap_or(line)
+        .split_whitespace()
+        .next()
+        .unwrap_or_default();
+    normalize_diff_path(raw)
+}
+
+fn normalize_diff_path(path: &str) -> String {
+    if path == "/dev/null" {
+        return String::new();
+    }
+    path.strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .or_else(|| path.strip_prefix("./"))
+        .unwrap_or(path)
+        .to_string()
+}
+
+fn parse_hunk_old_range(header: &str) -> Option<(usize, usize)> {
+    let middle = header.split("@@").nth(1)?.trim();
+    let old = middle.split_whitespace().next()?;
+    parse_hunk_range(old, '-')
+}
+
+fn parse_hunk_range(token: &str, prefix: char) -> Option<(usize, usize)> {
+    let range = token.strip_prefix(prefix)?;
+    let (start, count) = if let Some((start, count)) = range.split_once(',') {
+        (start, count)
+    } else {
+        (range, "1")
+    };
+    Some((start.parse().ok()?, count.parse().ok()?))
+}
+
+fn select_patch_for_base<'a>(
+    patches: &'a [UnifiedFilePatch],
+    base_name: &str,
+    base_rel: Option<&Path>,
+) -> Option<&'a UnifiedFilePatch> {
+    patches
+        .iter()
+        .filter_map(|patch| {
+            let score = patch_match_score(patch, base_name, base_rel);
+            (score > 0).then_some((score, patch))
+        })
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, patch)| patch)
+}
+
+fn patch_match_score(patch: &UnifiedFilePatch, base_name: &str, base_rel: Option<&Path>) -> usize {
+    [patch.old_path.as_str(), patch.new_path.as_str()]
+        .into_iter()
+        .map(|path| single_patch_path_score(path, base_name, base_rel))
+        .max()
+        .unwrap_or(0)
+}
+
+fn single_patch_path_score(path: &str, base_name: &str, base_rel: Option<&Path>) -> usize {
+    if path.is_empty() {
+        return 0;
+    }
+
+    let candidate = Path::new(path);
+    if let Some(base_rel) = base_rel {
+        if candidate == base_rel {
+            return 100;
+        }
+        if path_ends_with(candidate, base_rel) {
+            return 80;
+        }
+    }
+
+    if candidate.file_name().and_then(|name| name.to_str()) == Some(base_name) {
+        return 10;
+    }
+
+    0
+}
+
+fn apply_unified_patch(
+    base_lines: &[String],
+    hunks: &[UnifiedHunk],
+) -> Result<Vec<String>, ApiError> {
+    let mut out = Vec::new();
+    let mut cursor = 0usize;
+
+    for hunk in hunks {
+        if hunk.old_start == 0 {
+            return Err(ApiError::ProjectError(
+                "invalid unified diff hunk line number".to_string(),
+            ));
+        }
+        let target_start = hunk.old_start - 1;
+        if target_start < cursor || target_start > base_lines.len() {
+            return Err(ApiError::ProjectError(
+                "invalid unified diff hunk range".to_string(),
+            ));
+        }
+
+        out.extend_from_slice(&base_lines[cursor..target_start]);
+        cursor = target_start;
+        let mut consumed = 0usize;
+
+        for line in &hunk.lines {
+            let marker = line.chars().next().unwrap_or(' ');
+            let body = line.get(1..).unwrap_or_default().to_string();
+            match marker {
+                ' ' => {
+                    if base_lines.get(cursor) != Some(&body) {
+                        return Err(ApiError::ProjectError(
+                            "unified diff context line mismatch".to_string(),
+                        ));
+                    }
+                    out.push(body);
+                    cursor += 1;
+                    consumed += 1;
+                }
+                '-' => {
+                    if base_lines.get(cursor) != Some(&body) {
+                        return Err(ApiError::ProjectError(
+                            "unified diff removed line mismatch".to_string(),
+                        ));
+                    }
+                    cursor += 1;
+                    consumed += 1;
+                }
+                '+' => out.push(body),
+                _ => {}
+            }
+        }
+
+        if consumed != hunk.old_count {
+            return Err(ApiError::ProjectError(
+                "unified diff hunk consumed line count mismatch".to_string(),
+            ));
+        }
+    }
+
+    out.extend_from_slice(&base_lines[cursor..]);
+    Ok(out)
+}
+
+fn path_ends_with(path: &Path, suffix: &Path) -> bool {
+    let path_components = path.components().collect::<Vec<_>>();
+    let suffix_components = suffix.components().collect::<Vec<_>>();
+    if suffix_components.len() > path_components.len() {
+        return false;
+    }
+
+    let start = path_components.len() - suffix_components.len();
+    path_components[start..] == suffix_components[..]
+}
+
+fn cargo_mutants_diff_root(cargo_mutants_dir: &Path) -> PathBuf {
+    let direct = cargo_mutants_dir.join("diff");
+    if direct.is_dir() {
+        return direct;
+    }
+
+    let nested_once = cargo_mutants_dir.join("mutants.out").join("diff");
+    if nested_once.is_dir() {
+        return nested_once;
+    }
+
+    let nested_twice = cargo_mutants_dir
+        .join("mutants.out")
+        .join("mutants.out")
+        .join("diff");
+    if nested_twice.is_dir() {
+        return nested_twice;
+    }
+
+    cargo_mutants_dir.to_path_buf()
+}
+
+fn copy_cargo_mutants_diffs_to_workdir(
+    cargo_mutants_dir: &Path,
+) -> Result<Option<PathBuf>, ApiError> {
+    let workdir = std::env::current_dir().map_err(|e| {
+        ApiError::ProjectError(format!("failed to determine current directory: {}", e))
+    })?;
+    copy_cargo_mutants_diffs_to_dir(cargo_mutants_dir, &workdir)
+}
 
-    let escaped_file_name = file_name.replace('\\', "\\\\").replace('"', "\\\"");
-    let cargo_toml = format!(
-        "[package]\nname = \"marauders_single_file\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[lib]\npath = \"{}\"\n",
-        escaped_file_name
-    );
-    std::fs::write(project_root.join("Cargo.toml"), cargo_toml)?;
+fn copy_cargo_mutants_diffs_to_dir(
+    cargo_mutants_dir: &Path,
+    destination_root: &Path,
+) -> Result<Option<PathBuf>, ApiError> {
+    let diff_root = cargo_mutants_diff_root(cargo_mutants_dir);
+    let mut diff_files = walk_files_recursive(&diff_root)?
+        .into_iter()
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("diff"))
+        .collect::<Vec<_>>();
+    diff_files.sort();
+    if diff_files.is_empty() {
+        return Ok(None);
+    }
 
-    Ok(PathBuf::from(file_name))
-}
+    let pid = std::process::id();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
 
-fn parse_unified_diff(input: &str) -> Result<Vec<UnifiedFilePatch>, ApiError> {
-    let lines = input.lines().collect::<Vec<_>>();
-    let mut patches = Vec::new();
-    let mut idx = 0usize;
+    let mut out = destination_root.join("diffs");
+    if out.exists() {
+        out = destination_root.join(format!("diffs_{pid}_{nanos}"));
+    }
+    std::fs::create_dir_all(&out)?;
 
-    while idx < lines.len() {
-        if !lines[idx].starts_with("--- ") {
-            idx += 1;
-            continue;
+    for file in diff_files {
+        let rel = file.strip_prefix(&diff_root).map_err(|_| {
+            ApiError::ProjectError(format!(
+                "failed to relativize diff file '{}' to '{}'",
+                file.display(),
+                diff_root.display()
+            ))
+        })?;
+        let destination = out.join(rel);
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
         }
+        std::fs::copy(&file, &destination).map_err(|e| {
+            ApiError::ProjectError(format!(
+                "failed to copy diff '{}' to '{}': {}",
+                file.display(),
+                destination.display(),
+                e
+            ))
+        })?;
+    }
 
-        let old_path = parse_diff_path(lines[idx], "--- ");
-        idx += 1;
-        if idx >= lines.len() || !lines[idx].starts_with("+++ ") {
-            continue;
-        }
-        let new_path = parse_diff_path(lines[idx], "+++ ");
-        idx += 1;
+    Ok(Some(out))
+}
 
-        let mut hunks = Vec::new();
-        while idx < lines.len() {
-            if lines[idx].starts_with("--- ") {
-                break;
-            }
-            if !lines[idx].starts_with("@@") {
-                idx += 1;
-                continue;
-            }
+fn normalize_external_mutant_source(input: &str) -> String {
+    // cargo-mutants may annotate changed sites with this inline comment marker.
+    // Strip it so generated Marauders block comments stay syntactically valid.
+    const MARKER: &str = "/* ~ changed by cargo-mutants ~ */";
+    if input.contains(MARKER) {
+        input.replace(MARKER, "")
+    } else {
+        input.to_string()
+    }
+}
 
-            let (old_start, old_count) = parse_hunk_old_range(lines[idx]).ok_or_else(|| {
-                ApiError::ProjectError("failed to parse unified diff hunk header".to_string())
-            })?;
-            idx += 1;
+/// Initializes a project configuration file.
+///
+/// # Arguments
+///
+/// * `path` - The directory where the config file should be created
+/// * `language` - The primary language for the project (e.g., "rust", "python")
+/// * `use_gitignore` - Whether to respect .gitignore when scanning files
+///
+/// # Returns
+///
+/// * `Ok(PathBuf)` - The path to the created config file
+/// * `Err(ApiError)` - If the config file could not be created
+pub fn init_project(path: &Path, language: &str, use_gitignore: bool) -> Result<PathBuf, ApiError> {
+    let project_config = ProjectConfig {
+        languages: Language::name_to_language(language, &[]).map_or(vec![], |l| vec![l]),
+        custom_languages: vec![],
+        file_languages: Default::default(),
+        ignore: vec![],
+        use_gitignore,
+        timeout: None,
+        build: None,
+    };
 
-            let mut hunk_lines = Vec::new();
-            while idx < lines.len() {
-                let current = lines[idx];
-                if current.starts_with("@@") || current.starts_with("--- ") {
-                    break;
-                }
-                if current.starts_with('\\') {
-                    idx += 1;
-                    continue;
-                }
-                match current.chars().next() {
-                    Some(' ') | Some('+') | Some('-') => hunk_lines.push(current.to_string()),
-                    _ => {}
-                }
-                idx += 1;
-            }
+    let config_path = path.join("marauder.toml");
+    let config_content =
+        toml::to_string(&project_config).map_err(|e| ApiError::ProjectError(e.to_string()))?;
 
-            hunks.push(UnifiedHunk {
-                old_start,
-                old_count,
-                lines: hunk_lines,
-            });
-        }
+    crate::atomic::write(&config_path, config_content)?;
 
-        if !hunks.is_empty() {
-            patches.push(UnifiedFilePatch {
-                old_path,
-                new_path,
-                hunks,
-            });
+    Ok(config_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_variations() {
+        let project = Project::new(Path::new("test"), None).unwrap();
+        let variations = list_variations(&project);
+
+        // Should find variations in test files
+        assert!(!variations.is_empty());
+
+        // Check that each variation has the expected structure
+        for info in &variations {
+            assert!(info.path.exists() || info.path.starts_with("test/"));
+            assert!(info.line > 0);
         }
     }
 
-    Ok(patches)
-}
+    #[test]
+    fn test_mutation_summary() {
+        let result = |variants: &[&str], outcome| TermResult {
+            variants: variants.iter().map(|v| v.to_string()).collect(),
+            is_baseline: variants.is_empty(),
+            exit_code: None,
+            duration_ms: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            outcome,
+            error: None,
+        };
+        let tag_map = HashMap::from([("easy".to_string(), vec!["add".to_string()])]);
+        let variation_map = HashMap::from([(
+            "add".to_string(),
+            vec!["add_1".to_string(), "add_2".to_string()],
+        )]);
+        let results = [
+            result(&["add_1"], TestOutcome::Killed),
+            result(&["add_2"], TestOutcome::Survived),
+            result(&["neg_1"], TestOutcome::Survived),
+            result(&["add_1", "neg_1"], TestOutcome::Killed),
+        ];
+
+        let summary = MutationSummary::new(
+            &result(&[], TestOutcome::Survived),
+            &results,
+            &tag_map,
+            &variation_map,
+        );
+        assert_eq!((summary.total, summary.killed, summary.survived), (4, 2, 2));
+        assert!(!summary.baseline_failed);
+        assert_eq!(summary.score, Some(0.5));
+        assert_eq!(
+            summary.survivors_by_variation,
+            BTreeMap::from([
+                ("add".to_string(), vec!["add_2".to_string()]),
+                ("anonymous".to_string(), vec!["neg_1".to_string()]),
+            ])
+        );
+        assert_eq!(
+            summary.survivors_by_tag,
+            BTreeMap::from([("easy".to_string(), vec!["add_2".to_string()])])
+        );
 
-fn parse_diff_path(line: &str, prefix: &str) -> String {
-    let raw = line
-        .strip_prefix(prefix)
-        .unwrap_or(line)
-        .split_whitespace()
-        .next()
-        .unwrap_or_default();
-    normalize_diff_path(raw)
-}
+        // Every mutant is killed when the baseline already fails, so there is no score
+        let summary = MutationSummary::new(
+            &result(&[], TestOutcome::Killed),
+            &results,
+            &tag_map,
+            &variation_map,
+        );
+        assert!(summary.baseline_failed);
+        assert_eq!(summary.killed, 2);
+        assert_eq!(summary.score, None);
+    }
 
-fn normalize_diff_path(path: &str) -> String {
-    if path == "/dev/null" {
-        return String::new();
+    #[test]
+    fn test_variation_info_structure() {
+        let info = VariationInfo {
+            path: PathBuf::from("test.rs"),
+            line: 10,
+            name: Some("my_variation".to_string()),
+            variants: vec!["variant_a".to_string(), "variant_b".to_string()],
+            active: 0,
+            tags: vec!["tag1".to_string()],
+        };
+
+        assert_eq!(info.path, PathBuf::from("test.rs"));
+        assert_eq!(info.line, 10);
+        assert_eq!(info.name, Some("my_variation".to_string()));
+        assert_eq!(info.variants.len(), 2);
+        assert_eq!(info.active, 0);
+        assert_eq!(info.tags.len(), 1);
     }
-    path.strip_prefix("a/")
-        .or_else(|| path.strip_prefix("b/"))
-        .or_else(|| path.strip_prefix("./"))
-        .unwrap_or(path)
-        .to_string()
-}
 
-fn parse_hunk_old_range(header: &str) -> Option<(usize, usize)> {
-    let middle = header.split("@@").nth(1)?.trim();
-    let old = middle.split_whitespace().next()?;
-    parse_hunk_range(old, '-')
-}
+    #[test]
+    fn test_set_result_structure() {
+        let result = SetResult {
+            file: PathBuf::from("test.rs"),
+            variation: Some("my_variation".to_string()),
+            previous_active: 0,
+            new_active: 1,
+        };
 
-fn parse_hunk_range(token: &str, prefix: char) -> Option<(usize, usize)> {
-    let range = token.strip_prefix(prefix)?;
-    let (start, count) = if let Some((start, count)) = range.split_once(',') {
-        (start, count)
-    } else {
-        (range, "1")
-    };
-    Some((start.parse().ok()?, count.parse().ok()?))
+        assert_eq!(result.file, PathBuf::from("test.rs"));
+        assert_eq!(result.variation, Some("my_variation".to_string()));
+        assert_eq!(result.previous_active, 0);
+        assert_eq!(result.new_active, 1);
+    }
+
+    #[test]
+    fn test_api_error_display() {
+        let err = ApiError::VariantNotFound {
+            variant: "foo".to_string(),
+            available: vec!["bar".to_string(), "baz".to_string()],
+        };
+        let msg = format!("{}", err);
+        assert!(msg.contains("foo"));
+        assert!(msg.contains("bar"));
+
+        let err = ApiError::AlreadyActive {
+            variant: "foo".to_string(),
+        };
+        let msg = format!("{}", err);
+        assert!(msg.contains("already active"));
+    }
+
+    #[test]
+    fn test_campaign_steps_through_terms() {
+        let root =
+            std::env::temp_dir().join(format!("marauders_campaign_steps_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let source = r#"fn calc(a: i32, b: i32) -> i32 {
+    /*| add */
+    a + b
+    /*|| add_1 */
+    /*|
+    a - b
+    */
+    /*|| add_2 */
+    /*|
+    a * b
+    */
+    /* |*/
 }
+"#;
+        std::fs::write(root.join("calc.rs"), source).unwrap();
+
+        let mut project = Project::new(&root, None).unwrap();
+        let campaign = start_campaign(&project, "add", ConflictPolicy::Drop).unwrap();
+        assert_eq!(campaign.terms.len(), 2);
+        assert_eq!(campaign.cursor, None);
+
+        let step = next_term(&mut project).unwrap().unwrap();
+        assert_eq!((step.index, step.total), (0, 2));
+        assert_eq!(project.active_variants(), vec!["add_1"]);
+
+        // A fresh project sees the persisted cursor and resumes from there.
+        let mut project = Project::new(&root, None).unwrap();
+        assert_eq!(campaign_status(&project).unwrap().unwrap().cursor, Some(0));
+        let step = next_term(&mut project).unwrap().unwrap();
+        assert_eq!(step.term, vec!["add_2".to_string()]);
+        assert_eq!(project.active_variants(), vec!["add_2"]);
+
+        let step = prev_term(&mut project).unwrap().unwrap();
+        assert_eq!(step.index, 0);
+        assert_eq!(project.active_variants(), vec!["add_1"]);
+
+        next_term(&mut project).unwrap();
+        assert_eq!(next_term(&mut project).unwrap(), None);
+        assert!(project.active_variants().is_empty());
+
+        stop_campaign(&mut project).unwrap();
+        assert_eq!(campaign_status(&project).unwrap(), None);
+        assert_eq!(
+            std::fs::read_to_string(root.join("calc.rs")).unwrap(),
+            source
+        );
 
-fn select_patch_for_base<'a>(
-    patches: &'a [UnifiedFilePatch],
-    base_name: &str,
-    base_rel: Option<&Path>,
-) -> Option<&'a UnifiedFilePatch> {
-    patches
-        .iter()
-        .filter_map(|patch| {
-            let score = patch_match_score(patch, base_name, base_rel);
-            (score > 0).then_some((score, patch))
-        })
-        .max_by_key(|(score, _)| *score)
-        .map(|(_, patch)| patch)
-}
+        let _ = std::fs::remove_dir_all(root);
+    }
 
-fn patch_match_score(patch: &UnifiedFilePatch, base_name: &str, base_rel: Option<&Path>) -> usize {
-    [patch.old_path.as_str(), patch.new_path.as_str()]
-        .into_iter()
-        .map(|path| single_patch_path_score(path, base_name, base_rel))
-        .max()
-        .unwrap_or(0)
+    #[test]
+    fn test_run_tests_in_parallel() {
+        let root = std::env::temp_dir().join(format!(
+            "marauders_run_tests_parallel_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let source = r#"fn calc(a: i32, b: i32) -> i32 {
+    /*| add */
+    a + b
+    /*|| add_1 */
+    /*|
+    a - b
+    */
+    /*|| add_2 */
+    /*|
+    a * b
+    */
+    /*|| add_3 */
+    /*|
+    a / b
+    */
+    /*|| add_4 */
+    /*|
+    a % b
+    */
+    /* |*/
 }
+"#;
+        std::fs::write(root.join("calc.rs"), source).unwrap();
+        // Every run logs its directory, and `add_1` takes longest so it completes last.
+        let log = root.with_extension("log");
+        let command = format!(
+            "pwd >> {}; grep -A1 'add_1' calc.rs | grep -q '/\\*|$' || sleep 0.3",
+            log.to_string_lossy()
+        );
 
-fn single_patch_path_score(path: &str, base_name: &str, base_rel: Option<&Path>) -> usize {
-    if path.is_empty() {
-        return 0;
-    }
+        let mut project = Project::new(&root, None).unwrap();
+        let mut seen = Vec::new();
+        let options = RunOptions {
+            jobs: 3,
+            ..Default::default()
+        };
+        let report = run_tests(&mut project, "add", &command, &options, |result| {
+            seen.push(result.variants.clone())
+        })
+        .unwrap();
 
-    let candidate = Path::new(path);
-    if let Some(base_rel) = base_rel {
-        if candidate == base_rel {
-            return 100;
-        }
-        if path_ends_with(candidate, base_rel) {
-            return 80;
+        let variants: Vec<_> = report.results.iter().map(|r| r.variants.clone()).collect();
+        let expected: Vec<_> = (1..=4).map(|i| vec![format!("add_{i}")]).collect();
+        assert_eq!(variants, expected);
+        assert_eq!(seen.len(), 5);
+        assert_ne!(seen[1..], expected[..]);
+
+        let dirs = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(dirs.lines().count(), 5);
+        for dir in dirs.lines() {
+            assert!(
+                !Path::new(dir).exists(),
+                "workspace '{}' was left behind",
+                dir
+            );
         }
-    }
+        assert_eq!(
+            std::fs::read_to_string(root.join("calc.rs")).unwrap(),
+            source
+        );
 
-    if candidate.file_name().and_then(|name| name.to_str()) == Some(base_name) {
-        return 10;
+        let _ = std::fs::remove_file(log);
+        let _ = std::fs::remove_dir_all(root);
     }
 
-    0
+    #[test]
+    fn test_run_tests_reports_outcomes() {
+        let root = std::env::temp_dir().join(format!("marauders_run_tests_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let source = r#"fn calc(a: i32, b: i32) -> i32 {
+    /*| add [arith] */
+    a + b
+    /*|| add_1 */
+    /*|
+    a - b
+    */
+    /*|| add_2 */
+    /*|
+    a * b
+    */
+    /* |*/
 }
+"#;
+        std::fs::write(root.join("calc.rs"), source).unwrap();
+        // Passes as long as `add_1` is inactive.
+        let command = "grep -A1 'add_1' calc.rs | grep -q '/\\*|$'";
+
+        for (copy, jobs) in [(false, 1), (true, 1), (false, 2)] {
+            let mut project = Project::new(&root, None).unwrap();
+            let mut seen = Vec::new();
+            let report = run_tests(
+                &mut project,
+                "add",
+                command,
+                &RunOptions {
+                    copy,
+                    jobs,
+                    ..Default::default()
+                },
+                |result| seen.push(result.variants.clone()),
+            )
+            .unwrap();
+            // Concurrent terms are reported as they complete, but the report keeps term order.
+            seen.sort();
 
-fn apply_unified_patch(
-    base_lines: &[String],
-    hunks: &[UnifiedHunk],
-) -> Result<Vec<String>, ApiError> {
-    let mut out = Vec::new();
-    let mut cursor = 0usize;
-
-    for hunk in hunks {
-        if hunk.old_start == 0 {
-            return Err(ApiError::ProjectError(
-                "invalid unified diff hunk line number".to_string(),
-            ));
-        }
-        let target_start = hunk.old_start - 1;
-        if target_start < cursor || target_start > base_lines.len() {
-            return Err(ApiError::ProjectError(
-                "invalid unified diff hunk range".to_string(),
-            ));
+            let outcomes: Vec<_> = report
+                .results
+                .iter()
+                .map(|result| (result.variants.clone(), result.outcome, result.exit_code))
+                .collect();
+            assert_eq!(
+                outcomes,
+                vec![
+                    (vec!["add_1".to_string()], TestOutcome::Killed, Some(1)),
+                    (vec!["add_2".to_string()], TestOutcome::Survived, Some(0)),
+                ]
+            );
+            assert_eq!(
+                seen,
+                vec![vec![], vec!["add_1".to_string()], vec!["add_2".to_string()]]
+            );
+            assert_eq!(report.baseline.outcome, TestOutcome::Survived);
+            assert_eq!(report.summary.score, Some(0.5));
+            assert_eq!(
+                report.summary.survivors_by_variation,
+                BTreeMap::from([("add".to_string(), vec!["add_2".to_string()])])
+            );
+            assert_eq!(
+                report.summary.survivors_by_tag,
+                BTreeMap::from([("arith".to_string(), vec!["add_2".to_string()])])
+            );
+            assert_eq!(
+                std::fs::read_to_string(root.join("calc.rs")).unwrap(),
+                source
+            );
+
+            let json = serde_json::to_value(&report).unwrap();
+            assert_eq!(json["results"][0]["outcome"], "killed");
+            assert!(json["results"][0].get("error").is_none());
         }
 
-        out.extend_from_slice(&base_lines[cursor..target_start]);
-        cursor = target_start;
-        let mut consumed = 0usize;
+        // A failing baseline aborts the campaign, unless explicitly allowed, in which case
+        // it is flagged rather than scored.
+        let mut project = Project::new(&root, None).unwrap();
+        let mut seen = 0;
+        let err = run_tests(&mut project, "add", "false", &RunOptions::default(), |_| {
+            seen += 1
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("fails on the base program"));
+        assert_eq!(seen, 1);
 
-        for line in &hunk.lines {
-            let marker = line.chars().next().unwrap_or(' ');
-            let body = line.get(1..).unwrap_or_default().to_string();
-            match marker {
-                ' ' => {
-                    if base_lines.get(cursor) != Some(&body) {
-                        return Err(ApiError::ProjectError(
-                            "unified diff context line mismatch".to_string(),
-                        ));
-                    }
-                    out.push(body);
-                    cursor += 1;
-                    consumed += 1;
-                }
-                '-' => {
-                    if base_lines.get(cursor) != Some(&body) {
-                        return Err(ApiError::ProjectError(
-                            "unified diff removed line mismatch".to_string(),
-                        ));
-                    }
-                    cursor += 1;
-                    consumed += 1;
-                }
-                '+' => out.push(body),
-                _ => {}
-            }
-        }
+        let options = RunOptions {
+            allow_failing_baseline: true,
+            ..Default::default()
+        };
+        let report = run_tests(&mut project, "add", "false", &options, |_| {}).unwrap();
+        assert!(report.baseline.is_baseline);
+        assert!(report.summary.baseline_failed);
+        assert_eq!(report.summary.killed, 2);
+        assert_eq!(report.summary.score, None);
+        assert_eq!(report.timeout_ms, 10_000);
+
+        // A mutant that hangs is killed once the timeout expires.
+        let options = RunOptions {
+            timeout: Some(Duration::from_millis(500)),
+            ..Default::default()
+        };
+        let hang = format!("{} || exec sleep 30", command);
+        let report = run_tests(&mut project, "add", &hang, &options, |_| {}).unwrap();
+        assert_eq!(report.results[0].outcome, TestOutcome::TimedOut);
+        assert_eq!(report.results[1].outcome, TestOutcome::Survived);
+        assert_eq!(report.summary.timed_out, 1);
+        assert_eq!(report.summary.score, Some(0.5));
+        assert_eq!(
+            std::fs::read_to_string(root.join("calc.rs")).unwrap(),
+            source
+        );
 
-        if consumed != hunk.old_count {
-            return Err(ApiError::ProjectError(
-                "unified diff hunk consumed line count mismatch".to_string(),
-            ));
-        }
+        let _ = std::fs::remove_dir_all(root);
     }
 
-    out.extend_from_slice(&base_lines[cursor..]);
-    Ok(out)
+    #[test]
+    #[cfg(feature = "rust-ast")]
+    fn test_run_tests_reports_stillborn() {
+        let root = std::env::temp_dir().join(format!(
+            "marauders_run_tests_stillborn_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let source = r#"fn calc(a: i32, b: i32) -> i32 {
+    /*| add */
+    a + b
+    /*|| add_1 */
+    /*|
+    a +
+    */
+    /*|| add_2 */
+    /*|
+    a * b
+    */
+    /*|| add_3 */
+    /*|
+    a - b
+    */
+    /* |*/
 }
+"#;
+        std::fs::write(root.join("calc.rs"), source).unwrap();
 
-fn path_ends_with(path: &Path, suffix: &Path) -> bool {
-    let path_components = path.components().collect::<Vec<_>>();
-    let suffix_components = suffix.components().collect::<Vec<_>>();
-    if suffix_components.len() > path_components.len() {
-        return false;
+        // Builds as long as `add_2` is inactive, and `add_3` is the only mutant the tests kill.
+        let options = RunOptions {
+            build: Some("sleep 0.3; grep -A1 'add_2' calc.rs | grep -q '/\\*|$'".to_string()),
+            ..Default::default()
+        };
+        let command = "grep -A1 'add_3' calc.rs | grep -q '/\\*|$'";
+        let mut project = Project::new(&root, None).unwrap();
+        let report = run_tests(&mut project, "add", command, &options, |_| {}).unwrap();
+
+        let outcomes: Vec<_> = report
+            .results
+            .iter()
+            .map(|result| (result.variants.clone(), result.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (vec!["add_1".to_string()], TestOutcome::Stillborn),
+                (vec!["add_2".to_string()], TestOutcome::Stillborn),
+                (vec!["add_3".to_string()], TestOutcome::Killed),
+            ]
+        );
+        assert_eq!(
+            report.results[0].error.as_deref(),
+            Some("'calc.rs' does not parse")
+        );
+        assert_eq!(report.results[1].exit_code, Some(1));
+        assert_eq!(report.summary.stillborn, 2);
+        assert_eq!(report.summary.score, Some(1.0));
+        // Only the test command is timed, not the build
+        assert!(report.baseline.duration_ms < 300);
+        assert_eq!(
+            std::fs::read_to_string(root.join("calc.rs")).unwrap(),
+            source
+        );
+
+        let _ = std::fs::remove_dir_all(root);
     }
 
-    let start = path_components.len() - suffix_components.len();
-    path_components[start..] == suffix_components[..]
+    #[test]
+    #[cfg(feature = "rust-ast")]
+    fn test_run_tests_skips_equivalent() {
+        let root = std::env::temp_dir().join(format!(
+            "marauders_run_tests_equivalent_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let source = r#"fn calc(a: i32, b: i32) -> i32 {
+    /*| add */
+    a + b
+    /*|| add_1 */
+    /*|
+    (a + b)
+    */
+    /*|| add_2 */
+    /*|
+    a - b
+    */
+    /* |*/
 }
+"#;
+        std::fs::write(root.join("calc.rs"), source).unwrap();
 
-fn cargo_mutants_diff_root(cargo_mutants_dir: &Path) -> PathBuf {
-    let direct = cargo_mutants_dir.join("diff");
-    if direct.is_dir() {
-        return direct;
-    }
+        let mut project = Project::new(&root, None).unwrap();
+        assert_eq!(
+            equivalent_variants_of(&project),
+            vec![EquivalentVariant {
+                path: root.join("calc.rs"),
+                line: 2,
+                variant: "add_1".to_string(),
+                equivalence: Equivalence::Base,
+            }]
+        );
 
-    let nested_once = cargo_mutants_dir.join("mutants.out").join("diff");
-    if nested_once.is_dir() {
-        return nested_once;
-    }
+        let options = RunOptions {
+            skip_equivalent: true,
+            ..Default::default()
+        };
+        let report = run_tests(&mut project, "add", "true", &options, |_| {}).unwrap();
+        assert_eq!(report.skipped, vec![vec!["add_1".to_string()]]);
+        assert_eq!(report.results.len(), 1);
+        assert_eq!(report.results[0].variants, vec!["add_2".to_string()]);
+
+        // A variant sharing its name with a real mutant of another file is still run.
+        std::fs::write(root.join("other.rs"), source.replace("(a + b)", "a * b")).unwrap();
+        let mut project = Project::new(&root, None).unwrap();
+        let equivalent = equivalent_variants_of(&project);
+        assert_eq!(equivalent.len(), 1);
+        assert_eq!(equivalent[0].path, root.join("calc.rs"));
+        let report = run_tests(&mut project, "add", "true", &options, |_| {}).unwrap();
+        assert!(report.skipped.is_empty());
+        assert_eq!(report.results.len(), 2);
 
-    let nested_twice = cargo_mutants_dir
-        .join("mutants.out")
-        .join("mutants.out")
-        .join("diff");
-    if nested_twice.is_dir() {
-        return nested_twice;
+        let _ = std::fs::remove_dir_all(root);
     }
 
-    cargo_mutants_dir.to_path_buf()
-}
-
-fn copy_cargo_mutants_diffs_to_workdir(
-    cargo_mutants_dir: &Path,
-) -> Result<Option<PathBuf>, ApiError> {
-    let workdir = std::env::current_dir().map_err(|e| {
-        ApiError::ProjectError(format!("failed to determine current directory: {}", e))
-    })?;
-    copy_cargo_mutants_diffs_to_dir(cargo_mutants_dir, &workdir)
-}
-
-fn copy_cargo_mutants_diffs_to_dir(
-    cargo_mutants_dir: &Path,
-    destination_root: &Path,
-) -> Result<Option<PathBuf>, ApiError> {
-    let diff_root = cargo_mutants_diff_root(cargo_mutants_dir);
-    let mut diff_files = walk_files_recursive(&diff_root)?
-        .into_iter()
-        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("diff"))
-        .collect::<Vec<_>>();
-    diff_files.sort();
-    if diff_files.is_empty() {
-        return Ok(None);
-    }
+    #[test]
+    #[cfg(all(feature = "rust-ast", feature = "syntax-rust-functional"))]
+    fn test_equivalent_variants_of_functional() {
+        // The variations of the functional fixture have no lines to compare, and its variant
+        // names are shared with the comment syntax fixture.
+        let root = std::env::temp_dir().join(format!(
+            "marauders_equivalent_functional_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        for name in ["bst.rs", "bst_functional.rs"] {
+            std::fs::copy(Path::new("test/rust").join(name), root.join(name)).unwrap();
+        }
 
-    let pid = std::process::id();
-    let nanos = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|d| d.as_nanos())
-        .unwrap_or(0);
+        let project = Project::new(&root, None).unwrap();
+        assert!(project.parse_errors.is_empty());
+        assert!(project
+            .files
+            .iter()
+            .any(|file| file.code.functional.is_some()));
+        assert_eq!(equivalent_variants_of(&project), vec![]);
 
-    let mut out = destination_root.join("diffs");
-    if out.exists() {
-        out = destination_root.join(format!("diffs_{pid}_{nanos}"));
+        let _ = std::fs::remove_dir_all(root);
     }
-    std::fs::create_dir_all(&out)?;
 
-    for file in diff_files {
-        let rel = file.strip_prefix(&diff_root).map_err(|_| {
-            ApiError::ProjectError(format!(
-                "failed to relativize diff file '{}' to '{}'",
-                file.display(),
-                diff_root.display()
-            ))
-        })?;
-        let destination = out.join(rel);
-        if let Some(parent) = destination.parent() {
-            std::fs::create_dir_all(parent)?;
+    #[test]
+    #[cfg(feature = "syntax-rust-functional")]
+    fn test_run_tests_functional_uses_env_vars() {
+        let root = std::env::temp_dir().join(format!(
+            "marauders_run_tests_functional_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let source = r#"fn add(a: i32, b: i32) -> i32 {
+    /* marauders:variation=add */
+    match () {
+        _ if matches!(std::env::var("M_add_1").as_deref(), Ok("active")) => {
+            a - b
+        },
+        _ => {
+            a + b
+        },
+    }
+}
+"#;
+        std::fs::write(root.join("calc.rs"), source).unwrap();
+
+        let mut project = Project::new(&root, None).unwrap();
+        let terms = vec![vec!["add_1".to_string()]];
+        assert_eq!(
+            functional_env_vars(&project, &terms),
+            Some(HashMap::from([(
+                "add_1".to_string(),
+                "M_add_1".to_string()
+            )]))
+        );
+
+        for jobs in [1, 2] {
+            let options = RunOptions {
+                copy: true,
+                jobs,
+                ..Default::default()
+            };
+            let report = run_tests(
+                &mut project,
+                "add",
+                r#"test "$M_add_1" != active"#,
+                &options,
+                |_| {},
+            )
+            .unwrap();
+            assert_eq!(report.baseline.outcome, TestOutcome::Survived);
+            assert_eq!(report.results[0].outcome, TestOutcome::Killed);
+            assert_eq!(
+                std::fs::read_to_string(root.join("calc.rs")).unwrap(),
+                source
+            );
         }
-        std::fs::copy(&file, &destination).map_err(|e| {
-            ApiError::ProjectError(format!(
-                "failed to copy diff '{}' to '{}': {}",
-                file.display(),
-                destination.display(),
-                e
-            ))
-        })?;
+
+        // The unchanged sources are built once for the baseline and every term
+        let log = root.with_extension("log");
+        let options = RunOptions {
+            build: Some(format!("echo built >> {}", log.to_string_lossy())),
+            ..Default::default()
+        };
+        let report = run_tests(&mut project, "add", "true", &options, |_| {}).unwrap();
+        assert_eq!(report.results[0].outcome, TestOutcome::Survived);
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "built\n");
+        let _ = std::fs::remove_file(log);
+
+        let options = RunOptions {
+            build: Some("false".to_string()),
+            ..Default::default()
+        };
+        let err = run_tests(&mut project, "add", "true", &options, |_| {}).unwrap_err();
+        assert!(err.to_string().contains("build command fails"));
+
+        let _ = std::fs::remove_dir_all(root);
     }
 
-    Ok(Some(out))
+    #[test]
+    fn test_recover_restores_interrupted_term() {
+        let root = std::env::temp_dir().join(format!("marauders_recover_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let source = r#"fn calc(a: i32, b: i32) -> i32 {
+    /*| add */
+    a + b
+    /*|| add_1 */
+    /*|
+    a - b
+    */
+    /* |*/
 }
+"#;
+        std::fs::write(root.join("calc.rs"), source).unwrap();
 
-fn normalize_external_mutant_source(input: &str) -> String {
-    // cargo-mutants may annotate changed sites with this inline comment marker.
-    // Strip it so generated Marauders block comments stay syntactically valid.
-    const MARKER: &str = "/* ~ changed by cargo-mutants ~ */";
-    if input.contains(MARKER) {
-        input.replace(MARKER, "")
-    } else {
-        input.to_string()
+        // A run killed while `add_1` was applied leaves both the variant and the journal.
+        let mut project = Project::new(&root, None).unwrap();
+        Journal {
+            variants: vec!["add_1".to_string()],
+        }
+        .save(&root)
+        .unwrap();
+        project.set("add_1").unwrap();
+
+        let mut project = Project::new(&root, None).unwrap();
+        let err = run_tests(&mut project, "add", "true", &RunOptions::default(), |_| {})
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("run `marauders recover`"));
+
+        assert_eq!(recover(&mut project).unwrap(), vec!["add_1".to_string()]);
+        assert_eq!(
+            std::fs::read_to_string(root.join("calc.rs")).unwrap(),
+            source
+        );
+        assert_eq!(Journal::load(&root).unwrap(), None);
+        assert!(recover(&mut project).unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(root);
     }
+
+    #[test]
+    fn test_repair_project() {
+        let root = std::env::temp_dir().join(format!("marauders_repair_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join(".marauders")).unwrap();
+        let source = r#"fn calc(a: i32, b: i32) -> i32 {
+    /*| add */
+    /*|
+    a + b
+    */
+    /*|| add_1 */
+    a - b
+    /*|| add_2 */
+    a * b
+    /* |*/
 }
+"#;
+        std::fs::write(root.join("calc.rs"), source).unwrap();
 
-/// Initializes a project configuration file.
-///
-/// # Arguments
-///
-/// * `path` - The directory where the config file should be created
-/// * `language` - The primary language for the project (e.g., "rust", "python")
-/// * `use_gitignore` - Whether to respect .gitignore when scanning files
-///
-/// # Returns
-///
-/// * `Ok(PathBuf)` - The path to the created config file
-/// * `Err(ApiError)` - If the config file could not be created
-pub fn init_project(path: &Path, language: &str, use_gitignore: bool) -> Result<PathBuf, ApiError> {
-    let project_config = ProjectConfig {
-        languages: Language::name_to_language(language, &vec![]).map_or(vec![], |l| vec![l]),
-        custom_languages: vec![],
-        ignore: vec![],
-        use_gitignore,
-    };
+        let mut project = Project::new(&root, None).unwrap();
+        assert!(project.files.is_empty());
+        assert_eq!(project.parse_errors.len(), 1);
 
-    let config_path = path.join("marauder.toml");
-    let config_content =
-        toml::to_string(&project_config).map_err(|e| ApiError::ProjectError(e.to_string()))?;
+        // A run would silently leave out the mutants of the files that were not read.
+        let err = run_tests(&mut project, "add", "true", &RunOptions::default(), |_| {})
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("failed to parse 1 file(s)"));
+        assert_eq!(
+            project.malformed,
+            vec![MalformedVariation {
+                path: root.join("calc.rs"),
+                line: 2,
+                name: Some("add".to_string()),
+                active: vec!["add_1".to_string(), "add_2".to_string()],
+            }]
+        );
 
-    std::fs::write(&config_path, config_content)?;
+        assert_eq!(repair_project(&project).unwrap(), project.malformed);
+        let project = Project::new(&root, None).unwrap();
+        assert!(project.parse_errors.is_empty());
+        assert_eq!(list_variations(&project)[0].active, 0);
 
-    Ok(config_path)
-}
+        // The repair is recorded in the history like any other rewrite.
+        assert_eq!(undo(&root).unwrap().unwrap().operation, "repair");
+        assert_eq!(
+            std::fs::read_to_string(root.join("calc.rs")).unwrap(),
+            source
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let _ = std::fs::remove_dir_all(root);
+    }
 
     #[test]
-    fn test_list_variations() {
-        let project = Project::new(Path::new("test"), None).unwrap();
-        let variations = list_variations(&project);
+    fn test_lint_project() {
+        let root = std::env::temp_dir().join(format!("marauders_lint_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join("a.rs"),
+            r#"fn add(a: i32, b: i32) -> i32 {
+    /*| add */
+    a + b
+    /*|| add_1 */
+    /*|
+    a   +   b
+    */
+    /* |*/
+}
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("b.rs"),
+            r#"fn sub(a: i32, b: i32) -> i32 {
+    /*| add */
+    a - b
+    /*|| add_1 */
+    /*|
+    a + b
+    */
+    /* |*/
+    /*| [easy] */
+    /*|| log_1 */
+    /*|
+    println!("{}", a);
+    */
+    /* |*/
+}
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("c.rs"),
+            r#"fn neg(a: i32) -> i32 {
+    /*| upto */
+    -a
+    /*|| neg_1 */
+    /*|
+    a
+    */
+    /* |*/
+}
+"#,
+        )
+        .unwrap();
 
-        // Should find variations in test files
-        assert!(!variations.is_empty());
+        let project = Project::new(&root, None).unwrap();
+        let issues = lint_project(&project);
+        let kinds: Vec<(LintSeverity, LintKind, usize)> = issues
+            .iter()
+            .map(|issue| (issue.severity, issue.kind, issue.line))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (LintSeverity::Error, LintKind::DuplicateVariant, 2),
+                (LintSeverity::Error, LintKind::ReservedName, 2),
+                (LintSeverity::Warning, LintKind::VariantSameAsBase, 2),
+                (LintSeverity::Warning, LintKind::DuplicateVariation, 2),
+                (LintSeverity::Warning, LintKind::UnreachableVariation, 9),
+                (LintSeverity::Warning, LintKind::TagsOnAnonymousVariation, 9),
+                (LintSeverity::Warning, LintKind::EmptyBase, 9),
+            ]
+        );
+        assert!(issues[0].path.ends_with("a.rs"));
+        assert!(issues[0]
+            .message
+            .contains("variant name 'add_1' is defined 2 times"));
 
-        // Check that each variation has the expected structure
-        for info in &variations {
-            assert!(info.path.exists() || info.path.starts_with("test/"));
-            assert!(info.line > 0);
-        }
+        let _ = std::fs::remove_dir_all(root);
     }
 
     #[test]
-    fn test_variation_info_structure() {
-        let info = VariationInfo {
-            path: PathBuf::from("test.rs"),
-            line: 10,
-            name: Some("my_variation".to_string()),
-            variants: vec!["variant_a".to_string(), "variant_b".to_string()],
-            active: 0,
-            tags: vec!["tag1".to_string()],
-        };
+    fn test_resolve_terms_drops_conflicts() {
+        let project = Project::new(Path::new("test/rocq"), None).unwrap();
 
-        assert_eq!(info.path, PathBuf::from("test.rs"));
-        assert_eq!(info.line, 10);
-        assert_eq!(info.name, Some("my_variation".to_string()));
-        assert_eq!(info.variants.len(), 2);
-        assert_eq!(info.active, 0);
-        assert_eq!(info.tags.len(), 1);
+        let resolution =
+            resolve_terms(&project, "insert * insert_1", ConflictPolicy::Drop).unwrap();
+        assert_eq!(resolution.terms, vec![vec!["insert_1".to_string()]]);
+        assert_eq!(
+            resolution.dropped,
+            vec![
+                vec!["insert_2".to_string(), "insert_1".to_string()],
+                vec!["insert_3".to_string(), "insert_1".to_string()],
+            ]
+        );
+
+        let err = resolve_terms(&project, "insert * insert_1", ConflictPolicy::Error).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("variation 'insert (test/rocq/BST.v:21)'"));
     }
 
     #[test]
-    fn test_set_result_structure() {
-        let result = SetResult {
-            file: PathBuf::from("test.rs"),
-            variation: Some("my_variation".to_string()),
-            previous_active: 0,
-            new_active: 1,
-        };
+    fn test_explain_expr() {
+        let project = Project::new(Path::new("test/rocq"), None).unwrap();
+        let explanation = explain_expr(&project, "+easy * insert_1").unwrap();
 
-        assert_eq!(result.file, PathBuf::from("test.rs"));
-        assert_eq!(result.variation, Some("my_variation".to_string()));
-        assert_eq!(result.previous_active, 0);
-        assert_eq!(result.new_active, 1);
+        assert_eq!(explanation.parsed, "(+easy * insert_1)");
+        assert_eq!(explanation.tags_distributed, "(insert * insert_1)");
+        assert_eq!(
+            explanation.variations_distributed,
+            "(((insert_1 + insert_2) + insert_3) * insert_1)"
+        );
+        assert_eq!(explanation.terms.len(), 3);
+
+        let first = &explanation.terms[0];
+        assert_eq!(first.variants[0].variation.as_deref(), Some("insert"));
+        assert!(first.variants[0].path.ends_with("BST.v"));
+        assert!(first.conflicts.is_empty());
+
+        let second = &explanation.terms[1];
+        assert_eq!(second.conflicts.len(), 1);
+        assert_eq!(second.conflicts[0].variants, vec!["insert_2", "insert_1"]);
+
+        assert!(explain_expr(&project, "+easy *").is_err());
     }
 
     #[test]
-    fn test_api_error_display() {
-        let err = ApiError::VariantNotFound {
-            variant: "foo".to_string(),
-            available: vec!["bar".to_string(), "baz".to_string()],
-        };
-        let msg = format!("{}", err);
-        assert!(msg.contains("foo"));
-        assert!(msg.contains("bar"));
-
-        let err = ApiError::AlreadyActive {
-            variant: "foo".to_string(),
-        };
-        let msg = format!("{}", err);
-        assert!(msg.contains("already active"));
+    fn test_derive_timeout() {
+        assert_eq!(
+            derive_timeout(Duration::from_millis(100)),
+            MIN_DERIVED_TIMEOUT
+        );
+        assert_eq!(
+            derive_timeout(Duration::from_secs(60)),
+            Duration::from_secs(300)
+        );
     }
 
     #[test]
@@ -1633,6 +4016,124 @@ fn calc(a: i32, b: i32) -> i32 {
         let _ = std::fs::remove_file(&tmp);
     }
 
+    #[test]
+    fn test_convert_file_custom_language() {
+        let root =
+            std::env::temp_dir().join(format!("marauders_convert_{}_custom", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(
+            root.join("marauder.toml"),
+            r##"languages = []
+ignore = []
+use_gitignore = false
+
+[[custom_languages]]
+name = "KelesLisp"
+extension = "kl"
+comment_begin = "#|"
+comment_end = "|#"
+mutation_marker = "!"
+"##,
+        )
+        .unwrap();
+        let file = root.join("src").join("fact.kl");
+        std::fs::copy("test/custom/fact.kl", &file).unwrap();
+
+        convert_file(&file, ConversionTarget::Preprocessor).unwrap();
+        let converted = std::fs::read_to_string(&file).unwrap();
+        assert!(converted.contains("#if defined(M_fact_1)"));
+
+        convert_file(&file, ConversionTarget::Comment).unwrap();
+        let roundtrip = std::fs::read_to_string(&file).unwrap();
+        assert!(roundtrip.contains("#|! fact |#"));
+        assert!(roundtrip.contains("#|!! fact_1 |#"));
+
+        // Without a config the language of the file is unknown
+        std::fs::remove_file(root.join("marauder.toml")).unwrap();
+        assert!(convert_file(&file, ConversionTarget::Preprocessor).is_err());
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_undo_redo_set_and_convert() {
+        let root = std::env::temp_dir().join(format!("marauders_undo_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join(crate::campaign::STATE_DIR)).unwrap();
+        let source = r#"fn calc(a: i32, b: i32) -> i32 {
+    /*| add */
+    a + b
+    /*|| add_1 */
+    /*|
+    a - b
+    */
+    /* |*/
+}
+"#;
+        let file = root.join("calc.rs");
+        std::fs::write(&file, source).unwrap();
+
+        let mut project = Project::new(&root, None).unwrap();
+        set_variant(&mut project, "add_1").unwrap();
+        let mutated = std::fs::read_to_string(&file).unwrap();
+        assert_ne!(mutated, source);
+
+        let entry = undo(&root).unwrap().unwrap();
+        assert_eq!(entry.operation, "set add_1");
+        assert_eq!(entry.activations[0].new_active, 1);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), source);
+        redo(&root).unwrap().unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), mutated);
+        undo(&root).unwrap().unwrap();
+
+        // Undoing a conversion to patches restores the source and removes the bundle.
+        convert_file(&file, ConversionTarget::Patch).unwrap();
+        let bundle = root.join("calc.rs.patches");
+        assert!(bundle.join("manifest.toml").is_file());
+        undo(&file).unwrap().unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), source);
+        assert!(!bundle.exists());
+
+        redo(&file).unwrap().unwrap();
+        assert!(bundle.join("manifest.toml").is_file());
+        assert!(!std::fs::read_to_string(&file).unwrap().contains("add_1"));
+        assert_eq!(redo(&file).unwrap(), None);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_history_kept_in_project_root() {
+        let outer = std::env::temp_dir().join(format!("marauders_outer_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&outer);
+        let root = outer.join("inner");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            outer.join("marauder.toml"),
+            "languages = [\"Rust\"]\nignore = []\nuse_gitignore = false\ncustom_languages = []\n",
+        )
+        .unwrap();
+        let file = root.join("calc.rs");
+        std::fs::write(&file, "fn calc(a: i32, b: i32) -> i32 {\n    /*| add */\n    a + b\n    /*|| add_1 */\n    /*|\n    a - b\n    */\n    /* |*/\n}\n").unwrap();
+
+        // The history of a project opened from `inner` is kept there, not next to the
+        // `marauder.toml` of an enclosing directory, and operations on its files find it.
+        let mut project = Project::new(&root, None).unwrap();
+        set_variant(&mut project, "add_1").unwrap();
+        assert!(root.join(crate::campaign::STATE_DIR).is_dir());
+        assert!(!outer.join(crate::campaign::STATE_DIR).exists());
+        convert_file(&file, ConversionTarget::Patch).unwrap();
+        assert_eq!(History::load(&root).unwrap().entries.len(), 2);
+        assert_eq!(
+            undo(&root).unwrap().unwrap().operation,
+            format!("convert {} to Patch", file.to_string_lossy())
+        );
+        assert_eq!(undo(&root).unwrap().unwrap().operation, "set add_1");
+
+        let _ = std::fs::remove_dir_all(outer);
+    }
+
     #[test]
     fn test_convert_file_patch_roundtrip() {
         let original = r#"
@@ -0,0 +1,162 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Replaces the contents of `path` with `contents` without ever exposing a partially
+/// written file.
+///
+/// The contents are written and synced to a temporary sibling of `path`, which is then
+/// renamed over it, so an interruption leaves either the old or the new file in place.
+/// The permissions of an existing file are kept, and a symlink is written through to the
+/// file it points to.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let path = if path.is_symlink() {
+        fs::canonicalize(path)?
+    } else {
+        path.to_path_buf()
+    };
+    let temp = sibling(&path, "tmp")?;
+
+    let result = (|| {
+        let mut file = File::create(&temp)?;
+        file.write_all(contents.as_ref())?;
+        if let Ok(metadata) = fs::metadata(&path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, &path)?;
+        sync_parent(&path);
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Replaces the directory `path` with one filled by `populate`.
+///
+/// `populate` receives a staging directory next to `path`. Once it succeeds, the previous
+/// directory is moved aside, the staged one renamed into place and the previous one
+/// removed; if `populate` or the swap fails, `path` is left as it was.
+pub fn replace_dir(
+    path: &Path,
+    populate: impl FnOnce(&Path) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let staged = sibling(path, "tmp")?;
+    fs::create_dir_all(&staged)?;
+    if let Err(err) = populate(&staged) {
+        let _ = fs::remove_dir_all(&staged);
+        return Err(err);
+    }
+
+    let previous = if path.exists() {
+        let previous = sibling(path, "old")?;
+        if let Err(err) = fs::rename(path, &previous) {
+            let _ = fs::remove_dir_all(&staged);
+            return Err(err.into());
+        }
+        Some(previous)
+    } else {
+        None
+    };
+
+    if let Err(err) = fs::rename(&staged, path) {
+        if let Some(previous) = &previous {
+            let _ = fs::rename(previous, path);
+        }
+        let _ = fs::remove_dir_all(&staged);
+        return Err(err.into());
+    }
+    sync_parent(path);
+
+    if let Some(previous) = previous {
+        if let Err(err) = fs::remove_dir_all(&previous) {
+            log::warn!(
+                "could not remove previous directory '{}': {}",
+                previous.to_string_lossy(),
+                err
+            );
+        }
+    }
+    Ok(())
+}
+
+/// A unique hidden path in the directory of `path`, so that renaming it over `path` never
+/// crosses file systems
+fn sibling(path: &Path, kind: &str) -> io::Result<PathBuf> {
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' has no file name", path.to_string_lossy()),
+        )
+    })?;
+    let pid = std::process::id();
+    let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    Ok(path.with_file_name(format!(
+        ".{}.marauders-{kind}-{pid}-{counter}",
+        name.to_string_lossy()
+    )))
+}
+
+/// Makes a rename in the parent directory of `path` durable, where the platform allows it
+#[cfg(unix)]
+fn sync_parent(path: &Path) {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = File::open(parent) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_replace_dir() {
+        let root = std::env::temp_dir().join(format!("marauders_atomic_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        let file = root.join("calc.rs");
+        write(&file, "a + b\n").unwrap();
+        write(&file, "a - b\n").unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "a - b\n");
+
+        let bundle = root.join("calc.rs.patches");
+        replace_dir(&bundle, |dir| Ok(fs::write(dir.join("old.diff"), "old")?)).unwrap();
+        replace_dir(&bundle, |dir| Ok(fs::write(dir.join("new.diff"), "new")?)).unwrap();
+        assert!(!bundle.join("old.diff").exists());
+        assert_eq!(fs::read_to_string(bundle.join("new.diff")).unwrap(), "new");
+
+        // A failed replacement leaves the previous directory untouched.
+        let err = replace_dir(&bundle, |dir| {
+            fs::write(dir.join("partial.diff"), "partial")?;
+            anyhow::bail!("interrupted")
+        });
+        assert!(err.is_err());
+        assert!(bundle.join("new.diff").is_file());
+        assert!(!bundle.join("partial.diff").exists());
+
+        // No temporary files are left behind.
+        let mut entries: Vec<_> = fs::read_dir(&root)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, vec!["calc.rs", "calc.rs.patches"]);
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
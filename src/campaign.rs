@@ -0,0 +1,115 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+/// Directory, relative to the project root, where marauders keeps its state.
+pub const STATE_DIR: &str = ".marauders";
+const STATE_FILE: &str = "state";
+
+/// Persisted state of an incremental mutation campaign.
+///
+/// A campaign enumerates the sum-of-products terms of a mutation expression once, and
+/// then applies them one at a time, recording which term is currently active so that
+/// the campaign can be resumed after an interruption.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Campaign {
+    /// The mutation expression the campaign was started with
+    pub expr: String,
+    /// The enumerated terms, each a set of variants applied together
+    pub terms: Vec<Vec<String>>,
+    /// Index of the currently applied term (`None` when the project is at base)
+    pub cursor: Option<usize>,
+    /// Terms left out because they select several variants of one variation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dropped: Vec<Vec<String>>,
+}
+
+impl Campaign {
+    pub fn new(expr: &str, terms: Vec<Vec<String>>) -> Self {
+        Campaign {
+            expr: expr.to_string(),
+            terms,
+            cursor: None,
+            dropped: vec![],
+        }
+    }
+
+    /// Path of the state file for a project rooted at `root`
+    pub fn state_path(root: &Path) -> PathBuf {
+        root.join(STATE_DIR).join(STATE_FILE)
+    }
+
+    /// Loads the campaign of the project rooted at `root`, if one was started
+    pub fn load(root: &Path) -> anyhow::Result<Option<Self>> {
+        let path = Self::state_path(root);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)?;
+        let campaign = serde_json::from_str(&content).with_context(|| {
+            format!(
+                "could not parse campaign state '{}'",
+                path.to_string_lossy()
+            )
+        })?;
+        Ok(Some(campaign))
+    }
+
+    /// Saves the campaign into the state directory of the project rooted at `root`
+    pub fn save(&self, root: &Path) -> anyhow::Result<()> {
+        let path = Self::state_path(root);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        crate::atomic::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Removes the campaign state of the project rooted at `root`
+    pub fn remove(root: &Path) -> anyhow::Result<()> {
+        let path = Self::state_path(root);
+        if path.is_file() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the currently applied term
+    pub fn current(&self) -> Option<&Vec<String>> {
+        self.cursor.and_then(|index| self.terms.get(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_campaign_roundtrip() {
+        let root = std::env::temp_dir().join(format!(
+            "marauders_campaign_roundtrip_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        assert_eq!(Campaign::load(&root).unwrap(), None);
+
+        let mut campaign = Campaign::new(
+            "insert",
+            vec![vec!["insert_1".to_string()], vec!["insert_2".to_string()]],
+        );
+        campaign.cursor = Some(1);
+        campaign.save(&root).unwrap();
+
+        let loaded = Campaign::load(&root).unwrap().unwrap();
+        assert_eq!(loaded, campaign);
+        assert_eq!(loaded.current(), Some(&vec!["insert_2".to_string()]));
+
+        Campaign::remove(&root).unwrap();
+        assert_eq!(Campaign::load(&root).unwrap(), None);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
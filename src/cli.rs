@@ -1,10 +1,13 @@
 use std::{
     fs,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use clap::{Parser, ValueEnum};
-use marauders::{algebra, api, ApiError, CustomLanguage, Language, Project, ProjectConfig};
+use marauders::{
+    algebra::ConflictPolicy, api, ApiError, CustomLanguage, Language, Project, ProjectConfig,
+};
 
 fn main() -> anyhow::Result<()> {
     env_logger::init();
@@ -75,6 +78,133 @@ pub(crate) enum Command {
         #[clap(short, long, default_value = "false")]
         /// Whether to print the output or not
         nocapture: bool,
+        #[clap(long, default_value = "false")]
+        /// Run each mutant in an isolated copy of the project instead of the working tree
+        copy: bool,
+        #[clap(short, long, default_value = "1")]
+        /// Number of mutants to run concurrently, each in its own copy of the project
+        jobs: usize,
+        #[clap(long, value_enum, default_value = "text")]
+        /// Format of the results report
+        format: ReportFormat,
+        #[clap(short, long)]
+        /// Write the results report to a file instead of standard output
+        output: Option<PathBuf>,
+        #[clap(long, default_value = "false")]
+        /// Run the mutants even if the test command fails on the base program
+        allow_failing_baseline: bool,
+        #[clap(long)]
+        /// Time limit in seconds for each mutant, overrides the `timeout` key of marauder.toml
+        /// (defaults to 5x the baseline duration)
+        timeout: Option<u64>,
+        #[clap(long, value_enum, default_value = "drop")]
+        /// What to do with terms that select several variants of one variation
+        conflicts: ConflictMode,
+        #[clap(long)]
+        /// Command run before the tests of each mutant, overrides the `build` key of
+        /// marauder.toml; mutants it fails on are reported as not compiling
+        build: Option<String>,
+        #[clap(long, default_value = "false")]
+        /// Skip the terms selecting a variant equivalent to its base or to another variant
+        skip_equivalent: bool,
+    },
+    #[clap(
+        name = "expr",
+        about = "Explain how a mutation expression expands, without applying it"
+    )]
+    Expr {
+        #[clap(short, long)]
+        /// The mutation expression to explain
+        expr: String,
+        #[clap(short, long, default_value = ".")]
+        path: PathBuf,
+        #[clap(long, value_enum, default_value = "text")]
+        /// Format of the explanation
+        format: ReportFormat,
+    },
+    #[clap(
+        name = "check",
+        about = "Check the variations of a project for duplicate names and other mistakes"
+    )]
+    Check {
+        #[clap(short, long, default_value = ".")]
+        path: PathBuf,
+        #[clap(long, value_enum, default_value = "text")]
+        /// Format of the findings
+        format: ReportFormat,
+        #[clap(long, default_value = "false")]
+        /// Reset the variations that do not have exactly one active variant to base
+        repair: bool,
+    },
+    #[clap(
+        name = "start",
+        about = "Start an incremental campaign that steps through a mutation expression"
+    )]
+    Start {
+        #[clap(short, long)]
+        /// The mutation expression to step through
+        expr: String,
+        #[clap(short, long, default_value = ".")]
+        path: PathBuf,
+        #[clap(long, value_enum, default_value = "drop")]
+        /// What to do with terms that select several variants of one variation
+        conflicts: ConflictMode,
+    },
+    #[clap(
+        name = "next",
+        about = "Apply the next term of the incremental campaign"
+    )]
+    Next {
+        #[clap(short, long, default_value = ".")]
+        path: PathBuf,
+    },
+    #[clap(
+        name = "prev",
+        about = "Apply the previous term of the incremental campaign"
+    )]
+    Prev {
+        #[clap(short, long, default_value = ".")]
+        path: PathBuf,
+    },
+    #[clap(
+        name = "status",
+        about = "Show the progress of the incremental campaign"
+    )]
+    Status {
+        #[clap(short, long, default_value = ".")]
+        path: PathBuf,
+    },
+    #[clap(
+        name = "stop",
+        about = "Stop the incremental campaign and reset its term to base"
+    )]
+    Stop {
+        #[clap(short, long, default_value = ".")]
+        path: PathBuf,
+    },
+    #[clap(
+        name = "undo",
+        about = "Revert the last set, unset, reset or convert operation"
+    )]
+    Undo {
+        #[clap(short, long, default_value = ".")]
+        path: PathBuf,
+    },
+    #[clap(
+        name = "redo",
+        about = "Apply again the last operation reverted by undo"
+    )]
+    Redo {
+        #[clap(short, long, default_value = ".")]
+        path: PathBuf,
+    },
+    #[clap(
+        name = "recover",
+        about = "Restore the variants left active by an interrupted test run to base"
+    )]
+    Recover {
+        #[clap(short, long, default_value = ".")]
+        path: PathBuf,
     },
     #[clap(
         name = "convert",
@@ -97,6 +227,29 @@ enum ConvertTarget {
     MatchReplace,
 }
 
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ConflictMode {
+    /// Skip the term and report it
+    Drop,
+    /// Refuse the whole expression
+    Error,
+}
+
+impl From<ConflictMode> for ConflictPolicy {
+    fn from(mode: ConflictMode) -> Self {
+        match mode {
+            ConflictMode::Drop => ConflictPolicy::Drop,
+            ConflictMode::Error => ConflictPolicy::Error,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ReportFormat {
+    Text,
+    Json,
+}
+
 #[derive(Parser)]
 pub(crate) enum ConfigCommand {
     #[clap(name = "use-gitignore", about = "Use .gitignore for ignoring files")]
@@ -141,12 +294,17 @@ pub(crate) enum ConfigCommand {
         path: Option<PathBuf>,
         #[clap(short, long)]
         name: String,
+        /// Leave out for languages whose files are only found by `--pattern`
         #[clap(short = 'x', long)]
-        extension: String,
+        extension: Option<String>,
+        /// Glob matched against file names, such as `*.mli` or `Makefile`, can be repeated
+        #[clap(long = "pattern")]
+        patterns: Vec<String>,
         #[clap(short = 'b', long)]
         comment_begin: String,
+        /// Leave out for languages that only have line comments
         #[clap(short = 'e', long)]
-        comment_end: String,
+        comment_end: Option<String>,
         #[clap(short = 'm', long)]
         mutation_marker: String,
     },
@@ -228,9 +386,74 @@ pub(crate) fn run(opts: Opts) -> anyhow::Result<()> {
             path,
             command,
             nocapture,
+            copy,
+            jobs,
+            format,
+            output,
+            allow_failing_baseline,
+            timeout,
+            conflicts,
+            build,
+            skip_equivalent,
         } => {
             log::info!("running tests at '{}'", path.to_string_lossy());
-            run_run_command(expr, path, command, *nocapture)?;
+            let options = api::RunOptions {
+                copy: *copy,
+                jobs: *jobs,
+                timeout: timeout.map(Duration::from_secs),
+                allow_failing_baseline: *allow_failing_baseline,
+                conflicts: (*conflicts).into(),
+                build: build.clone(),
+                skip_equivalent: *skip_equivalent,
+            };
+            run_run_command(
+                expr,
+                path,
+                command,
+                *nocapture,
+                &options,
+                *format,
+                output.as_deref(),
+            )?;
+        }
+        Command::Start {
+            expr,
+            path,
+            conflicts,
+        } => {
+            log::info!("starting campaign at '{}'", path.to_string_lossy());
+            run_start_command(expr, path, (*conflicts).into())?;
+        }
+        Command::Next { path } => {
+            run_step_command(path, api::next_term)?;
+        }
+        Command::Prev { path } => {
+            run_step_command(path, api::prev_term)?;
+        }
+        Command::Expr { expr, path, format } => {
+            run_expr_command(expr, path, *format)?;
+        }
+        Command::Check {
+            path,
+            format,
+            repair,
+        } => {
+            run_check_command(path, *format, *repair)?;
+        }
+        Command::Status { path } => {
+            run_status_command(path)?;
+        }
+        Command::Stop { path } => {
+            run_stop_command(path)?;
+        }
+        Command::Undo { path } => {
+            run_history_command(path, api::undo, "undo", "undid")?;
+        }
+        Command::Redo { path } => {
+            run_history_command(path, api::redo, "redo", "redid")?;
+        }
+        Command::Recover { path } => {
+            run_recover_command(path)?;
         }
         Command::Convert { path, to } => {
             run_convert_command(path, to)?;
@@ -269,6 +492,7 @@ fn run_list_command(path: &Path, pattern: Option<&str>) -> anyhow::Result<()> {
     let project = Project::new(path, pattern)?;
     ensure_project_parseable(&project)?;
 
+    let equivalent_variants = api::equivalent_variants_of(&project);
     for info in api::list_variations(&project) {
         let name = info.name.as_deref().unwrap_or("anonymous");
         let active = if info.active == 0 {
@@ -276,14 +500,25 @@ fn run_list_command(path: &Path, pattern: Option<&str>) -> anyhow::Result<()> {
         } else {
             info.variants[info.active - 1].clone()
         };
+        let equivalent: Vec<String> = equivalent_variants
+            .iter()
+            .filter(|e| e.path == info.path && e.line == info.line)
+            .map(|e| format!("{} ({})", e.variant, e.equivalence))
+            .collect();
+        let equivalent = if equivalent.is_empty() {
+            String::new()
+        } else {
+            format!(", equivalent: [{}]", equivalent.join(", "))
+        };
         println!(
-            "{}:{} (name: {}, active: {}, variants: {:?}, tags: {:?})",
+            "{}:{} (name: {}, active: {}, variants: {:?}, tags: {:?}{})",
             info.path.to_string_lossy(),
             info.line,
             name,
             active,
             info.variants,
-            info.tags
+            info.tags,
+            equivalent
         );
     }
 
@@ -485,48 +720,364 @@ fn run_init_command(path: &Path, language: &str, use_gitignore: bool) -> anyhow:
     Ok(())
 }
 
-fn run_run_command(expr: &str, path: &Path, command: &str, nocapture: bool) -> anyhow::Result<()> {
-    let path = path.to_path_buf();
+fn run_run_command(
+    expr: &str,
+    path: &Path,
+    command: &str,
+    nocapture: bool,
+    options: &api::RunOptions,
+    format: ReportFormat,
+    output: Option<&Path>,
+) -> anyhow::Result<()> {
+    let mut project = Project::new(path, None)?;
+    ensure_project_parseable(&project)?;
+    marauders::recovery::install_interrupt_handler()?;
+
+    // Progress goes to stdout unless stdout is reserved for the JSON report.
+    let show_progress = format == ReportFormat::Text || output.is_some();
+    let report = api::run_tests(&mut project, expr, command, options, |result| {
+        if !show_progress {
+            return;
+        }
+        if nocapture {
+            print!("{}", result.stdout);
+            eprintln!("{}", result.stderr);
+        }
+        print!("{}", format_term_result(result));
+    })?;
+
+    let rendered = match format {
+        ReportFormat::Text => std::iter::once(&report.baseline)
+            .chain(&report.results)
+            .map(format_term_result)
+            .chain(std::iter::once(format_dropped(&report.dropped)))
+            .chain(std::iter::once(format_skipped(&report.skipped)))
+            .chain(std::iter::once(format_summary(&report.summary)))
+            .collect(),
+        ReportFormat::Json => serde_json::to_string_pretty(&report)?,
+    };
+
+    match output {
+        Some(output) => {
+            fs::write(output, rendered)?;
+            println!("results written to '{}'", output.to_string_lossy());
+        }
+        None if format == ReportFormat::Json => println!("{}", rendered),
+        None => print!(
+            "{}{}{}",
+            format_dropped(&report.dropped),
+            format_skipped(&report.skipped),
+            format_summary(&report.summary)
+        ),
+    }
+
+    Ok(())
+}
+
+fn format_term_result(result: &api::TermResult) -> String {
+    if result.is_baseline {
+        return match result.outcome {
+            api::TestOutcome::Survived => "Baseline passed\n".to_string(),
+            _ => "Baseline failed\n".to_string(),
+        };
+    }
+    match result.outcome {
+        api::TestOutcome::Survived => format!("Test passed: {:?}\n", result.variants),
+        api::TestOutcome::Killed => format!("Test failed: {:?}\n", result.variants),
+        api::TestOutcome::TimedOut => format!("Test timed out: {:?}\n", result.variants),
+        api::TestOutcome::Errored => format!(
+            "Test failed: {:?}\nError: {}\n",
+            result.variants,
+            result.error.as_deref().unwrap_or_default()
+        ),
+        api::TestOutcome::Stillborn => format!(
+            "Mutant does not compile: {:?}\nReason: {}\n",
+            result.variants,
+            result.error.as_deref().unwrap_or_default()
+        ),
+    }
+}
+
+fn format_dropped(dropped: &[Vec<String>]) -> String {
+    format_terms(
+        &format!(
+            "Dropped {} term(s) that select several variants of one variation",
+            dropped.len()
+        ),
+        dropped,
+    )
+}
+
+fn format_skipped(skipped: &[Vec<String>]) -> String {
+    format_terms(
+        &format!(
+            "Skipped {} term(s) that select an equivalent variant",
+            skipped.len()
+        ),
+        skipped,
+    )
+}
+
+fn format_terms(title: &str, terms: &[Vec<String>]) -> String {
+    let mut out = String::new();
+    if !terms.is_empty() {
+        out.push_str(&format!("{}:\n", title));
+        for term in terms {
+            out.push_str(&format!("  {:?}\n", term));
+        }
+    }
+    out
+}
+
+fn format_summary(summary: &api::MutationSummary) -> String {
+    let mut out = String::new();
+    match summary.score {
+        Some(score) => out.push_str(&format!(
+            "Mutation score: {}/{} killed ({:.2}%)\n",
+            summary.killed + summary.timed_out,
+            summary.total - summary.stillborn,
+            score * 100.0
+        )),
+        None if summary.baseline_failed => out
+            .push_str("Mutation score: unavailable, the test command fails on the base program\n"),
+        None if summary.stillborn > 0 => {
+            out.push_str("Mutation score: unavailable, no term compiled\n")
+        }
+        None => out.push_str("Mutation score: no terms were tested\n"),
+    }
+    if summary.timed_out > 0 {
+        out.push_str(&format!(
+            "{} term(s) timed out and were counted as killed\n",
+            summary.timed_out
+        ));
+    }
+    if summary.errored > 0 {
+        out.push_str(&format!("{} term(s) could not be run\n", summary.errored));
+    }
+    if summary.stillborn > 0 {
+        out.push_str(&format!(
+            "{} term(s) did not compile and were excluded from the score\n",
+            summary.stillborn
+        ));
+    }
+    for (title, groups) in [
+        ("variation", &summary.survivors_by_variation),
+        ("tag", &summary.survivors_by_tag),
+    ] {
+        if groups.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("Survivors by {}:\n", title));
+        for (group, variants) in groups {
+            out.push_str(&format!("  {}: {}\n", group, variants.join(", ")));
+        }
+    }
+    out
+}
+
+fn run_check_command(path: &Path, format: ReportFormat, repair: bool) -> anyhow::Result<()> {
+    let mut project = Project::new(path, None)?;
+    if repair && !project.malformed.is_empty() {
+        for variation in api::repair_project(&project)? {
+            eprintln!("reset to base: {}", variation);
+        }
+        project = Project::new(path, None)?;
+    }
+    // Syntax errors and malformed variations are reported as located issues, other files that
+    // could not be read have no location to point at.
+    let mut located: Vec<&Path> = project
+        .diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.path.as_path())
+        .chain(
+            project
+                .malformed
+                .iter()
+                .map(|variation| variation.path.as_path()),
+        )
+        .collect();
+    located.sort();
+    located.dedup();
+    if project.parse_errors.len() > located.len() {
+        ensure_project_parseable(&project)?;
+    }
+
+    let issues = api::lint_project(&project);
+    if format == ReportFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&issues)?);
+    } else {
+        for issue in &issues {
+            let severity = match issue.severity {
+                api::LintSeverity::Error => "error",
+                api::LintSeverity::Warning => "warning",
+            };
+            let column = issue
+                .column
+                .map(|column| format!(":{}", column))
+                .unwrap_or_default();
+            println!(
+                "{}: {}:{}{}: {}",
+                severity,
+                issue.path.to_string_lossy(),
+                issue.line,
+                column,
+                issue.message
+            );
+        }
+    }
+
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == api::LintSeverity::Error)
+        .count();
+    if format == ReportFormat::Text {
+        println!("{} error(s), {} warning(s)", errors, issues.len() - errors);
+    }
+    anyhow::ensure!(errors == 0, "the project has {} error(s)", errors);
+
+    Ok(())
+}
+
+fn run_expr_command(expr: &str, path: &Path, format: ReportFormat) -> anyhow::Result<()> {
+    let project = Project::new(path, None)?;
+    ensure_project_parseable(&project)?;
+
+    let explanation = api::explain_expr(&project, expr)?;
+    if format == ReportFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&explanation)?);
+        return Ok(());
+    }
+
+    println!("expression:              {}", explanation.parsed);
+    println!("tags distributed:        {}", explanation.tags_distributed);
+    println!(
+        "variations distributed:  {}",
+        explanation.variations_distributed
+    );
+    println!("{} term(s):", explanation.terms.len());
+    for (index, term) in explanation.terms.iter().enumerate() {
+        let variants = if term.variants.is_empty() {
+            "base".to_string()
+        } else {
+            term.variants
+                .iter()
+                .map(|variant| {
+                    format!(
+                        "{} ({}, {}:{})",
+                        variant.name,
+                        variant.variation.as_deref().unwrap_or("anonymous"),
+                        variant.path.to_string_lossy(),
+                        variant.line
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" * ")
+        };
+        println!("  [{}] {}", index + 1, variants);
+        for conflict in &term.conflicts {
+            println!(
+                "      conflict: variation '{}' at {}:{} can only activate one of {:?}",
+                conflict.variation.as_deref().unwrap_or("anonymous"),
+                conflict.path.to_string_lossy(),
+                conflict.line,
+                conflict.variants
+            );
+        }
+    }
 
-    let mut project = marauders::Project::new(&path, None)?;
+    Ok(())
+}
 
-    let active_variants = project.active_variants();
+fn run_start_command(expr: &str, path: &Path, conflicts: ConflictPolicy) -> anyhow::Result<()> {
+    let project = Project::new(path, None)?;
+    ensure_project_parseable(&project)?;
 
-    anyhow::ensure!(
-        active_variants.is_empty(),
-        r#"test command is only available when there are no active variants in the project, if you would like run the test command, please first run `marauders reset`."#
+    let campaign = api::start_campaign(&project, expr, conflicts)?;
+    print!("{}", format_dropped(&campaign.dropped));
+    println!(
+        "started campaign '{}' with {} term(s)",
+        campaign.expr,
+        campaign.terms.len()
     );
 
-    let tag_map = project.tag_map();
-    let variation_map = project.variation_map();
-    let variant_list = project.all_variants();
+    Ok(())
+}
+
+fn run_step_command(
+    path: &Path,
+    step: fn(&mut Project) -> Result<Option<api::CampaignStep>, ApiError>,
+) -> anyhow::Result<()> {
+    let mut project = Project::new(path, None)?;
+    ensure_project_parseable(&project)?;
 
-    let tests = algebra::compute_mutations(expr, &tag_map, &variation_map, &variant_list)?;
+    match step(&mut project)? {
+        Some(step) => println!("[{}/{}] {:?}", step.index + 1, step.total, step.term),
+        None => println!("base (no term applied)"),
+    }
 
-    for test in tests {
-        project.set_many(&test)?;
-        let result = project.run(command);
-        match result {
-            Ok(output) => {
-                if nocapture {
-                    print!("{}", String::from_utf8_lossy(&output.stdout));
-                    eprintln!("{}", String::from_utf8_lossy(&output.stderr));
-                }
+    Ok(())
+}
+
+fn run_status_command(path: &Path) -> anyhow::Result<()> {
+    let project = Project::new(path, None)?;
 
-                if output.status.success() {
-                    println!("Test passed: {:?}", test);
-                } else {
-                    println!("Test failed: {:?}", test);
+    match api::campaign_status(&project)? {
+        Some(campaign) => {
+            println!("expression: {}", campaign.expr);
+            match (campaign.cursor, campaign.current()) {
+                (Some(index), Some(term)) => {
+                    println!("term: [{}/{}] {:?}", index + 1, campaign.terms.len(), term)
                 }
+                _ => println!("term: base (0/{})", campaign.terms.len()),
             }
-            Err(e) => {
-                println!("Test failed: {:?}", test);
-                println!("Error: {:?}", e);
+        }
+        None => println!("no campaign in progress"),
+    }
+
+    Ok(())
+}
+
+fn run_stop_command(path: &Path) -> anyhow::Result<()> {
+    let mut project = Project::new(path, None)?;
+    ensure_project_parseable(&project)?;
+
+    let campaign = api::stop_campaign(&mut project)?;
+    log::info!("stopped campaign '{}'", campaign.expr);
+
+    Ok(())
+}
+
+fn run_history_command(
+    path: &Path,
+    step: fn(&Path) -> Result<Option<marauders::HistoryEntry>, ApiError>,
+    action: &str,
+    done: &str,
+) -> anyhow::Result<()> {
+    match step(path)? {
+        Some(entry) => {
+            println!("{} '{}'", done, entry.operation);
+            for file in &entry.files {
+                log::info!("restored '{}'", file.path.to_string_lossy());
             }
         }
+        None => println!("nothing to {}", action),
     }
 
-    project.reset()
+    Ok(())
+}
+
+fn run_recover_command(path: &Path) -> anyhow::Result<()> {
+    let mut project = Project::new(path, None)?;
+    ensure_project_parseable(&project)?;
+
+    let restored = api::recover(&mut project)?;
+    if restored.is_empty() {
+        println!("nothing to recover");
+    } else {
+        println!("restored {:?} to base", restored);
+    }
+
+    Ok(())
 }
 
 fn run_config_command(config_command: &ConfigCommand) -> anyhow::Result<()> {
@@ -571,11 +1122,17 @@ fn run_config_command(config_command: &ConfigCommand) -> anyhow::Result<()> {
         ConfigCommand::AddCustomLanguage {
             name,
             extension,
+            patterns,
             comment_begin,
             comment_end,
             mutation_marker,
             ..
         } => {
+            if extension.is_none() && patterns.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "language '{name}' needs an extension or a pattern"
+                ));
+            }
             if project_config
                 .custom_languages
                 .iter()
@@ -586,7 +1143,8 @@ fn run_config_command(config_command: &ConfigCommand) -> anyhow::Result<()> {
 
             project_config.custom_languages.push(CustomLanguage {
                 name: name.clone(),
-                extension: extension.clone(),
+                extension: extension.clone().unwrap_or_default(),
+                patterns: patterns.clone(),
                 comment_begin: comment_begin.clone(),
                 comment_end: comment_end.clone(),
                 mutation_marker: mutation_marker.clone(),
@@ -600,8 +1158,8 @@ fn run_config_command(config_command: &ConfigCommand) -> anyhow::Result<()> {
         }
     }
 
-    fs::write(
-        path.join("marauder.toml"),
+    marauders::atomic::write(
+        &path.join("marauder.toml"),
         toml::to_string(&project_config)?,
     )?;
 
@@ -4,9 +4,11 @@ use std::{
 };
 
 use anyhow::Context;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     languages::{CustomLanguage, Language},
+    syntax::functional::FunctionalLanguage,
     variation::Variation,
 };
 
@@ -15,6 +17,8 @@ pub struct Code {
     pub language: Language,
     pub spans: Vec<Span>,
     pub path: PathBuf,
+    /// Functional syntax the variations were read from, `None` for comment syntax
+    pub(crate) functional: Option<FunctionalLanguage>,
 }
 
 impl Code {
@@ -23,6 +27,7 @@ impl Code {
             language,
             spans,
             path,
+            functional: None,
         }
     }
 }
@@ -55,12 +60,133 @@ pub enum SpanContent {
     Line(String),
 }
 
+/// A variation whose markers are well-formed, but that does not have exactly one active
+/// variant, as happens when a file is edited by hand.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MalformedVariation {
+    /// File containing the variation
+    pub path: PathBuf,
+    /// Line where the variation starts (1-indexed)
+    pub line: usize,
+    /// Name of the variation, if it has one
+    pub name: Option<String>,
+    /// Names of the active variants, `base` included
+    pub active: Vec<String>,
+}
+
+impl MalformedVariation {
+    /// What went wrong, without the location
+    pub fn message(&self) -> String {
+        let active = if self.active.is_empty() {
+            "has no active variant".to_string()
+        } else {
+            format!(
+                "has {} active variants ({})",
+                self.active.len(),
+                self.active.join(", ")
+            )
+        };
+        format!(
+            "variation '{}' {}, exactly one of its base and variants must be active",
+            self.name.as_deref().unwrap_or("anonymous"),
+            active
+        )
+    }
+}
+
+impl Display for MalformedVariation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.path.to_string_lossy(),
+            self.line,
+            self.message()
+        )
+    }
+}
+
+/// A syntax error in the mutation markers of a file, located at the position where the
+/// parser gave up.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParseDiagnostic {
+    /// File containing the error
+    pub path: PathBuf,
+    /// Line of the error (1-indexed)
+    pub line: usize,
+    /// Column of the error, in characters (1-indexed)
+    pub column: usize,
+    /// Constructs the parser expected at that position, such as
+    /// "variation end marker `/* |*/`"
+    pub expected: Vec<String>,
+    /// The source line containing the error
+    pub source_line: String,
+}
+
+impl ParseDiagnostic {
+    /// What went wrong, without the location
+    pub fn message(&self) -> String {
+        match self.expected.as_slice() {
+            [] => "unexpected input".to_string(),
+            [expected] => format!("expected {}", expected),
+            [expected @ .., last] => format!("expected {} or {}", expected.join(", "), last),
+        }
+    }
+}
+
+/// Renders the diagnostic with the source line and a caret under the error.
+impl Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        // Keep the tabs of the source line so that the caret lines up with it.
+        let padding: String = self
+            .source_line
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        writeln!(
+            f,
+            "{}:{}:{}: {}",
+            self.path.to_string_lossy(),
+            self.line,
+            self.column,
+            self.message()
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", number, self.source_line)?;
+        write!(f, "{} | {}^", gutter, padding)
+    }
+}
+
+impl std::error::Error for ParseDiagnostic {}
+
+/// The error of parsing a file with malformed variations, see [`MalformedVariation`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MalformedVariations(pub Vec<MalformedVariation>);
+
+impl Display for MalformedVariations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let messages: Vec<String> = self.0.iter().map(|v| v.to_string()).collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for MalformedVariations {}
+
 impl Display for Code {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut content = String::new();
         for part in &self.spans {
             match &part.content {
                 SpanContent::Line(c) => content.push_str(c),
+                SpanContent::Variation(v) if self.language.uses_line_comments() => {
+                    for line in crate::syntax::line_comment::render_variation(&self.language, v) {
+                        content.push_str(&line);
+                        content.push('\n');
+                    }
+                }
                 SpanContent::Variation(v) => {
                     let mut variation_title = String::new();
 
@@ -129,40 +255,65 @@ impl Display for Code {
 }
 
 impl Code {
+    /// Parses a file in `language`, or in the language detected from its path and content
     pub(crate) fn from_file(
         filepath: &Path,
+        language: Option<Language>,
         custom_languages: &Vec<CustomLanguage>,
     ) -> anyhow::Result<Code> {
+        let (code, malformed) = Code::parse_file(filepath, language, custom_languages)?;
+        if !malformed.is_empty() {
+            return Err(MalformedVariations(malformed).into());
+        }
+        Ok(code)
+    }
+
+    /// Resets the variations of a file that do not have exactly one active variant to their
+    /// base, rewriting the file if there were any.
+    ///
+    /// Returns the variations that were reset.
+    pub(crate) fn repair_file(
+        filepath: &Path,
+        language: Option<Language>,
+        custom_languages: &Vec<CustomLanguage>,
+    ) -> anyhow::Result<Vec<MalformedVariation>> {
+        let (code, malformed) = Code::parse_file(filepath, language, custom_languages)?;
+        if !malformed.is_empty() {
+            code.save_to_file(filepath)?;
+        }
+        Ok(malformed)
+    }
+
+    /// Parses a file, resetting its malformed variations to base
+    fn parse_file(
+        filepath: &Path,
+        language: Option<Language>,
+        custom_languages: &Vec<CustomLanguage>,
+    ) -> anyhow::Result<(Code, Vec<MalformedVariation>)> {
         // read the file and parse it
         let file_content = std::fs::read_to_string(filepath)?;
-        let extension = filepath.extension().context(format!(
-            "file extension is not recognized for '{}'",
-            filepath.to_string_lossy()
-        ))?;
-
-        // todo: add support for custom languages
-        let language = Language::extension_to_language(
-            extension.to_str().context(format!(
-                "extension is not valid unicode {}",
-                extension.to_string_lossy()
-            ))?,
-            custom_languages,
-        );
+        let language = language
+            .or_else(|| Code::detect_language(filepath, &file_content, custom_languages))
+            .context(format!(
+                "no language is configured for '{}'",
+                filepath.to_string_lossy()
+            ))?;
         log::trace!(
-            "detected language for '{}': {}",
+            "detected language for '{}': {:?}",
             filepath.to_string_lossy(),
-            language.as_ref().map_or("unknown", |l| l.file_extension())
+            language
         );
 
-        if language.is_none() {
-            anyhow::bail!(
-                "unsupported file extension '{}'",
-                filepath.extension().unwrap().to_str().unwrap()
-            );
-        }
-        let language = language.unwrap();
-
-        let spans = match language {
+        let parse_comments = || {
+            crate::syntax::comment::parse_code_repairing(&file_content, &language).map_err(
+                |diagnostic| ParseDiagnostic {
+                    path: filepath.to_path_buf(),
+                    ..diagnostic
+                },
+            )
+        };
+        let mut functional = None;
+        let (spans, mut malformed) = match language {
             Language::Rust => {
                 if let Some(functional_language) =
                     crate::syntax::functional::functional_language_for_extension("rs")
@@ -176,39 +327,57 @@ impl Code {
                             &file_content,
                         );
                         if functional_spans.is_empty() {
-                            crate::syntax::comment::parse_code(&file_content)?
+                            parse_comments()?
                         } else {
-                            functional_spans
+                            functional = Some(functional_language);
+                            (functional_spans, vec![])
                         }
                     } else {
-                        crate::syntax::comment::parse_code(&file_content)?
+                        parse_comments()?
                     }
                 } else {
-                    crate::syntax::comment::parse_code(&file_content)?
+                    parse_comments()?
                 }
             }
-            _ => crate::syntax::comment::parse_code(&file_content)?,
+            _ => parse_comments()?,
         };
+        for variation in &mut malformed {
+            variation.path = filepath.to_path_buf();
+        }
         log::debug!(
             "parsed {} spans from file '{}'",
             spans.len(),
             filepath.to_string_lossy()
         );
         log::trace!("spans: {:#?}", spans);
-        Ok(Code::new(language, spans, filepath.to_path_buf()))
+        let code = Code {
+            functional,
+            ..Code::new(language, spans, filepath.to_path_buf())
+        };
+        Ok((code, malformed))
     }
 
     pub(crate) fn save_to_file(&self, filepath: &Path) -> anyhow::Result<()> {
         // write the code to a file
         let content = format!("{}", self);
-        std::fs::write(filepath, content)
+        crate::atomic::write(filepath, content)
             .map_err(|e| anyhow::anyhow!("could not write to file: {}", e))
     }
 
-    pub(crate) fn detect_language(filepath: &str) -> Language {
-        let ext = filepath.split('.').next_back().unwrap().to_string();
-        // todo: add support for custom languages
-        Language::extension_to_language(&ext, &vec![]).unwrap()
+    /// The language of a file, or `None` if neither the built-in nor the custom languages
+    /// cover it. Files without an extension are also recognized by a shebang or modeline.
+    pub(crate) fn detect_language(
+        filepath: &Path,
+        content: &str,
+        custom_languages: &Vec<CustomLanguage>,
+    ) -> Option<Language> {
+        Language::path_to_language(filepath, custom_languages).or_else(|| {
+            filepath
+                .extension()
+                .is_none()
+                .then(|| Language::content_to_language(content, custom_languages))
+                .flatten()
+        })
     }
 
     pub(crate) fn get_all_variants(&self) -> Vec<String> {
@@ -228,19 +397,6 @@ impl Code {
 }
 
 impl Code {
-    fn longest_common_prefix(strs: &[String]) -> String {
-        // Credits for this function goes to
-        // https://users.rust-lang.org/t/is-this-code-idiomatic/51798/14
-        for (idx, c) in strs[0].char_indices() {
-            // Because `s[..idx]` represents a common prefix,
-            // `idx` must be a valid character boundary in all the strings
-            if !strs[1..].iter().all(|s| s[idx..].starts_with(c)) {
-                return strs[0][..idx].to_string();
-            }
-        }
-        strs[0].to_string()
-    }
-
     pub(crate) fn set_active_variant(
         &mut self,
         variation_index: usize,
@@ -0,0 +1,309 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{languages::Language, variation::Variation};
+
+/// What a variant was found to be trivially equivalent to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Equivalence {
+    /// The variant is the base of its variation written differently
+    Base,
+    /// The variant is an earlier variant of its variation written differently
+    Variant(String),
+}
+
+impl Display for Equivalence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Equivalence::Base => write!(f, "same as base"),
+            Equivalence::Variant(variant) => write!(f, "same as {}", variant),
+        }
+    }
+}
+
+/// Finds the variants of `variation` that cannot change the behavior of the program, because
+/// they only differ from the base, or from an earlier variant, by whitespace, comments,
+/// redundant parentheses or the order of the operands of a commutative operator
+/// (`+ * == != & | ^`), such as `c * (b + a)` and `(a + b) * c`.
+///
+/// Only Rust variations are checked, using the `rust-ast` feature, and only their variants
+/// with a body: the base and variants of functional variations have no lines. Operands are
+/// only reordered when both are free of side effects, that is literals, variables, fields and
+/// operators applied to them, and the operator is assumed to be commutative for their types
+/// as it is for the built-in ones.
+///
+/// # Returns
+///
+/// The name of each equivalent variant, in order, with what it is equivalent to.
+pub fn equivalent_variants(
+    language: &Language,
+    variation: &Variation,
+) -> Vec<(String, Equivalence)> {
+    if *language != Language::Rust {
+        return vec![];
+    }
+    let Some(base) = normalize(&variation.base.lines()) else {
+        return vec![];
+    };
+    if base.is_empty() {
+        return vec![];
+    }
+
+    let mut seen: Vec<(String, &str)> = vec![];
+    let mut equivalent = vec![];
+    for variant in &variation.variants {
+        let Some(code) = normalize(&variant.lines()).filter(|code| !code.is_empty()) else {
+            continue;
+        };
+        if code == base {
+            equivalent.push((variant.name.clone(), Equivalence::Base));
+        } else if let Some((_, first)) = seen.iter().find(|(seen, _)| *seen == code) {
+            equivalent.push((
+                variant.name.clone(),
+                Equivalence::Variant(first.to_string()),
+            ));
+        } else {
+            seen.push((code, &variant.name));
+        }
+    }
+    equivalent
+}
+
+/// The canonical form of a piece of Rust code, or `None` if it cannot be tokenized
+#[cfg(feature = "rust-ast")]
+fn normalize(lines: &[String]) -> Option<String> {
+    rust::normalize(&lines.join("\n"))
+}
+
+#[cfg(not(feature = "rust-ast"))]
+fn normalize(_lines: &[String]) -> Option<String> {
+    None
+}
+
+#[cfg(feature = "rust-ast")]
+mod rust {
+    use quote::ToTokens;
+    use syn::{
+        visit_mut::{self, VisitMut},
+        BinOp, Expr,
+    };
+
+    /// Parses `code` as an expression, a sequence of statements or a list of items, in that
+    /// order, and prints its normalized syntax tree. Fragments that are none of these are
+    /// compared token by token.
+    pub(super) fn normalize(code: &str) -> Option<String> {
+        if let Ok(mut expr) = syn::parse_str::<Expr>(code) {
+            Normalizer.visit_expr_mut(&mut expr);
+            return Some(expr.to_token_stream().to_string());
+        }
+        if let Ok(mut block) = syn::parse_str::<syn::Block>(&format!("{{\n{}\n}}", code)) {
+            Normalizer.visit_block_mut(&mut block);
+            return Some(block.to_token_stream().to_string());
+        }
+        if let Ok(mut file) = syn::parse_file(code) {
+            Normalizer.visit_file_mut(&mut file);
+            return Some(file.to_token_stream().to_string());
+        }
+        code.parse::<proc_macro2::TokenStream>()
+            .ok()
+            .map(|tokens| tokens.to_string())
+    }
+
+    /// Removes the parentheses written in the source, puts them back around every compound
+    /// operand so that the printed tree stays unambiguous, and sorts the pure operands of
+    /// commutative operators.
+    struct Normalizer;
+
+    impl VisitMut for Normalizer {
+        fn visit_expr_mut(&mut self, expr: &mut Expr) {
+            while let Expr::Paren(paren) = expr {
+                *expr = std::mem::replace(&mut *paren.expr, Expr::Verbatim(Default::default()));
+            }
+            visit_mut::visit_expr_mut(self, expr);
+
+            match expr {
+                Expr::Binary(binary) => {
+                    if is_commutative(&binary.op)
+                        && is_pure(&binary.left)
+                        && is_pure(&binary.right)
+                        && key(&binary.right) < key(&binary.left)
+                    {
+                        std::mem::swap(&mut binary.left, &mut binary.right);
+                    }
+                    parenthesize(&mut binary.left);
+                    parenthesize(&mut binary.right);
+                }
+                Expr::Unary(unary) => parenthesize(&mut unary.expr),
+                Expr::Reference(reference) => parenthesize(&mut reference.expr),
+                Expr::Cast(cast) => parenthesize(&mut cast.expr),
+                Expr::MethodCall(call) => parenthesize(&mut call.receiver),
+                Expr::Field(field) => parenthesize(&mut field.base),
+                Expr::Index(index) => parenthesize(&mut index.expr),
+                Expr::Call(call) => parenthesize(&mut call.func),
+                Expr::Try(try_expr) => parenthesize(&mut try_expr.expr),
+                Expr::Await(await_expr) => parenthesize(&mut await_expr.base),
+                Expr::Range(range) => {
+                    if let Some(start) = &mut range.start {
+                        parenthesize(start);
+                    }
+                    if let Some(end) = &mut range.end {
+                        parenthesize(end);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn is_commutative(op: &BinOp) -> bool {
+        matches!(
+            op,
+            BinOp::Add(_)
+                | BinOp::Mul(_)
+                | BinOp::Eq(_)
+                | BinOp::Ne(_)
+                | BinOp::BitAnd(_)
+                | BinOp::BitOr(_)
+                | BinOp::BitXor(_)
+        )
+    }
+
+    /// Whether evaluating `expr` has no side effects, so that it can be moved past another
+    /// pure expression: a literal, a variable or constant, a field of a pure expression, or
+    /// an operator applied to pure operands
+    fn is_pure(expr: &Expr) -> bool {
+        match expr {
+            Expr::Lit(_) | Expr::Path(_) => true,
+            Expr::Paren(paren) => is_pure(&paren.expr),
+            Expr::Field(field) => is_pure(&field.base),
+            Expr::Unary(unary) => is_pure(&unary.expr),
+            Expr::Binary(binary) => {
+                !is_assignment(&binary.op) && is_pure(&binary.left) && is_pure(&binary.right)
+            }
+            _ => false,
+        }
+    }
+
+    fn is_assignment(op: &BinOp) -> bool {
+        matches!(
+            op,
+            BinOp::AddAssign(_)
+                | BinOp::SubAssign(_)
+                | BinOp::MulAssign(_)
+                | BinOp::DivAssign(_)
+                | BinOp::RemAssign(_)
+                | BinOp::BitXorAssign(_)
+                | BinOp::BitAndAssign(_)
+                | BinOp::BitOrAssign(_)
+                | BinOp::ShlAssign(_)
+                | BinOp::ShrAssign(_)
+        )
+    }
+
+    fn key(expr: &Expr) -> String {
+        expr.to_token_stream().to_string()
+    }
+
+    fn parenthesize(expr: &mut Expr) {
+        if matches!(
+            expr,
+            Expr::Binary(_)
+                | Expr::Unary(_)
+                | Expr::Reference(_)
+                | Expr::Cast(_)
+                | Expr::Range(_)
+                | Expr::Assign(_)
+                | Expr::Closure(_)
+                | Expr::Let(_)
+                | Expr::Return(_)
+                | Expr::Break(_)
+        ) {
+            let inner = std::mem::replace(expr, Expr::Verbatim(Default::default()));
+            *expr = syn::parse_quote!((#inner));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::SpanContent;
+
+    #[test]
+    #[cfg(feature = "rust-ast")]
+    fn test_equivalent_variants() {
+        let source = r#"fn calc(a: i32, b: i32, c: i32) -> i32 {
+    /*| calc */
+    (a + b) * c
+    /*|| spaced */
+    /*|
+    (a+b)   *  c
+    */
+    /*|| swapped */
+    /*|
+    c * (b + a)
+    */
+    /*|| precedence */
+    /*|
+    a + b * c
+    */
+    /*|| subtract */
+    /*|
+    (a - b) * c
+    */
+    /*|| reordered */
+    /*|
+    ((a - b)) * c
+    */
+    /*|| literals */
+    /*|
+    (a + b) * (2 + 1) * c
+    */
+    /*|| literals_swapped */
+    /*|
+    (a + b) * (1 + 2) * c
+    */
+    /*|| unswappable */
+    /*|
+    c * (b - a)
+    */
+    /*|| call */
+    /*|
+    (a + b) * f(c)
+    */
+    /*|| call_swapped */
+    /*|
+    f(c) * (a + b)
+    */
+    /* |*/
+}
+"#;
+        let spans = crate::syntax::comment::parse_code(source, &Language::Rust).unwrap();
+        let variation = spans
+            .iter()
+            .find_map(|span| match &span.content {
+                SpanContent::Variation(variation) => Some(variation),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(
+            equivalent_variants(&Language::Rust, variation),
+            vec![
+                ("spaced".to_string(), Equivalence::Base),
+                ("swapped".to_string(), Equivalence::Base),
+                (
+                    "reordered".to_string(),
+                    Equivalence::Variant("subtract".to_string())
+                ),
+                (
+                    "literals_swapped".to_string(),
+                    Equivalence::Variant("literals".to_string())
+                ),
+            ]
+        );
+        assert_eq!(equivalent_variants(&Language::Python, variation), vec![]);
+    }
+}
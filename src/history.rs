@@ -0,0 +1,505 @@
+use std::{
+    collections::HashSet,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{api::SetResult, campaign::STATE_DIR};
+
+const HISTORY_DIR: &str = "history";
+const LOG_FILE: &str = "log.json";
+const OBJECTS_DIR: &str = "objects";
+/// Number of operations kept in the history, older ones can no longer be undone
+pub const MAX_HISTORY_ENTRIES: usize = 100;
+
+/// The journal of operations that changed files of a project, with the cursor that
+/// separates the operations that can be undone from those that can be redone.
+///
+/// File contents are stored once per content hash under `.marauders/history/objects`, so
+/// every recorded state of a file can be restored. Only the last [`MAX_HISTORY_ENTRIES`]
+/// operations are kept, and the contents no kept operation refers to are removed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    /// Recorded operations, oldest first
+    pub entries: Vec<HistoryEntry>,
+    /// Number of entries currently applied, `entries[cursor..]` can be redone
+    pub cursor: usize,
+}
+
+/// An operation recorded in the history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Description of the operation, e.g. `set insert_1`
+    pub operation: String,
+    /// Variations whose active variant was changed by the operation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub activations: Vec<SetResult>,
+    /// Files written or removed by the operation
+    pub files: Vec<FileChange>,
+}
+
+/// Content of a file before and after an operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileChange {
+    /// Path of the file, relative to the history root when it lies below it
+    pub path: PathBuf,
+    /// Hash of the content before the operation, `None` if the file did not exist
+    pub before: Option<String>,
+    /// Hash of the content after the operation, `None` if the file was removed
+    pub after: Option<String>,
+}
+
+/// Contents of the files an operation is about to change, taken before it runs.
+#[derive(Debug)]
+pub struct Snapshot {
+    root: PathBuf,
+    files: Vec<(PathBuf, Option<Vec<u8>>)>,
+    dirs: Vec<PathBuf>,
+}
+
+impl Snapshot {
+    /// Reads the current content of `files` and of the files below `dirs`, to be recorded
+    /// once the operation completes. Files the operation creates below `dirs` are recorded too.
+    ///
+    /// The history is kept in `root`, the root of the project the operation changes.
+    pub fn take(root: &Path, files: &[PathBuf], dirs: &[PathBuf]) -> anyhow::Result<Self> {
+        let mut snapshot = Snapshot {
+            root: root.to_path_buf(),
+            files: vec![],
+            dirs: dirs.to_vec(),
+        };
+        let mut paths = files.to_vec();
+        for dir in dirs {
+            paths.extend(files_below(dir)?);
+        }
+        for path in paths {
+            snapshot.add(path)?;
+        }
+        Ok(snapshot)
+    }
+
+    fn add(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        if !self.files.iter().any(|(existing, _)| existing == &path) {
+            let content = read(&path)?;
+            self.files.push((path, content));
+        }
+        Ok(())
+    }
+
+    /// Records the operation in the history, unless it did not change any file.
+    ///
+    /// Operations that were undone are discarded, they can no longer be redone.
+    pub fn record(mut self, operation: &str, activations: Vec<SetResult>) -> anyhow::Result<()> {
+        for dir in self.dirs.clone() {
+            for path in files_below(&dir)? {
+                if !self.files.iter().any(|(existing, _)| existing == &path) {
+                    self.files.push((path, None));
+                }
+            }
+        }
+
+        let mut changes = vec![];
+        for (path, before) in &self.files {
+            let after = read(path)?;
+            if &after == before {
+                continue;
+            }
+            changes.push(FileChange {
+                path: relative_to(&self.root, path),
+                before: before
+                    .as_deref()
+                    .map(|content| store(&self.root, content))
+                    .transpose()?,
+                after: after
+                    .as_deref()
+                    .map(|content| store(&self.root, content))
+                    .transpose()?,
+            });
+        }
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let mut history = History::load(&self.root)?;
+        history.entries.truncate(history.cursor);
+        history.entries.push(HistoryEntry {
+            operation: operation.to_string(),
+            activations,
+            files: changes,
+        });
+        let excess = history.entries.len().saturating_sub(MAX_HISTORY_ENTRIES);
+        history.entries.drain(..excess);
+        history.cursor = history.entries.len();
+        history.save(&self.root)?;
+        history.collect_garbage(&self.root)
+    }
+}
+
+impl History {
+    /// The directory whose state directory keeps the history for `path`, for operations
+    /// that are not given a [`Project`](crate::Project).
+    ///
+    /// A directory is the root of its project, as for [`Project::new`](crate::Project::new).
+    /// For a file, this is the closest ancestor with a `marauder.toml` or a state directory,
+    /// else the current directory when the file lies below it, which is the project commands
+    /// open by default, else the directory of the file.
+    pub fn root_for(path: &Path) -> PathBuf {
+        if path.is_dir() {
+            return path.to_path_buf();
+        }
+        let start = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        if let Some(root) = start
+            .ancestors()
+            .find(|dir| dir.join("marauder.toml").is_file() || dir.join(STATE_DIR).is_dir())
+        {
+            return root.to_path_buf();
+        }
+        let below_current = std::env::current_dir()
+            .and_then(std::fs::canonicalize)
+            .and_then(|current| Ok(std::fs::canonicalize(start)?.starts_with(current)));
+        if below_current.unwrap_or(false) {
+            PathBuf::from(".")
+        } else {
+            start.to_path_buf()
+        }
+    }
+
+    fn dir(root: &Path) -> PathBuf {
+        root.join(STATE_DIR).join(HISTORY_DIR)
+    }
+
+    /// Loads the history kept in `root`, empty if nothing was recorded
+    pub fn load(root: &Path) -> anyhow::Result<Self> {
+        let path = Self::dir(root).join(LOG_FILE);
+        if !path.is_file() {
+            return Ok(History::default());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("could not parse history '{}'", path.to_string_lossy()))
+    }
+
+    fn save(&self, root: &Path) -> anyhow::Result<()> {
+        let dir = Self::dir(root);
+        std::fs::create_dir_all(&dir)?;
+        crate::atomic::write(&dir.join(LOG_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Removes the stored contents that no entry refers to anymore, such as those of
+    /// operations that were discarded or dropped from the history.
+    fn collect_garbage(&self, root: &Path) -> anyhow::Result<()> {
+        let referenced: HashSet<&str> = self
+            .entries
+            .iter()
+            .flat_map(|entry| &entry.files)
+            .flat_map(|change| [&change.before, &change.after])
+            .filter_map(|hash| hash.as_deref())
+            .collect();
+        let objects = Self::dir(root).join(OBJECTS_DIR);
+        if !objects.is_dir() {
+            return Ok(());
+        }
+        for entry in std::fs::read_dir(&objects)? {
+            let path = entry?.path();
+            let unreferenced = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| !referenced.contains(name));
+            if unreferenced {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reverts the last applied operation of the history kept in `root`.
+    ///
+    /// Fails without touching anything if one of its files was changed since.
+    pub fn undo(root: &Path) -> anyhow::Result<Option<HistoryEntry>> {
+        let mut history = History::load(root)?;
+        if history.cursor == 0 {
+            return Ok(None);
+        }
+        let entry = history.entries[history.cursor - 1].clone();
+        restore(
+            root,
+            &entry,
+            |change| (&change.after, &change.before),
+            "undo",
+        )?;
+        history.cursor -= 1;
+        history.save(root)?;
+        Ok(Some(entry))
+    }
+
+    /// Applies again the last undone operation of the history kept in `root`.
+    ///
+    /// Fails without touching anything if one of its files was changed since.
+    pub fn redo(root: &Path) -> anyhow::Result<Option<HistoryEntry>> {
+        let mut history = History::load(root)?;
+        let Some(entry) = history.entries.get(history.cursor).cloned() else {
+            return Ok(None);
+        };
+        restore(
+            root,
+            &entry,
+            |change| (&change.before, &change.after),
+            "redo",
+        )?;
+        history.cursor += 1;
+        history.save(root)?;
+        Ok(Some(entry))
+    }
+}
+
+/// Moves every file of `entry` from the `(expected, target)` states chosen by `states`
+fn restore(
+    root: &Path,
+    entry: &HistoryEntry,
+    states: impl Fn(&FileChange) -> (&Option<String>, &Option<String>),
+    action: &str,
+) -> anyhow::Result<()> {
+    for change in &entry.files {
+        let (expected, _) = states(change);
+        let current = read(&root.join(&change.path))?.map(|content| hash(&content));
+        anyhow::ensure!(
+            &current == expected,
+            "cannot {} '{}', '{}' was changed since",
+            action,
+            entry.operation,
+            root.join(&change.path).to_string_lossy()
+        );
+    }
+
+    for change in &entry.files {
+        let path = root.join(&change.path);
+        match states(change).1 {
+            Some(hash) => {
+                let content = std::fs::read(History::dir(root).join(OBJECTS_DIR).join(hash))
+                    .with_context(|| {
+                        format!(
+                            "the recorded content of '{}' is missing",
+                            path.to_string_lossy()
+                        )
+                    })?;
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                crate::atomic::write(&path, content)?;
+            }
+            None => {
+                std::fs::remove_file(&path)?;
+                // Remove the directories the operation created, such as a patch bundle.
+                for dir in path.ancestors().skip(1) {
+                    if dir == root || std::fs::remove_dir(dir).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn files_below(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(files_below(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn read(path: &Path) -> anyhow::Result<Option<Vec<u8>>> {
+    match std::fs::read(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => {
+            Err(err).with_context(|| format!("could not read '{}'", path.to_string_lossy()))
+        }
+    }
+}
+
+fn hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Stores `content` in the objects of the history kept in `root`, returning its hash
+fn store(root: &Path, content: &[u8]) -> anyhow::Result<String> {
+    let hash = hash(content);
+    let path = History::dir(root).join(OBJECTS_DIR).join(&hash);
+    if !path.is_file() {
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        crate::atomic::write(&path, content)?;
+    }
+    Ok(hash)
+}
+
+/// `path` relative to `root` when it lies below it, so the history survives moving the
+/// project; otherwise `path` itself
+fn relative_to(root: &Path, path: &Path) -> PathBuf {
+    let absolute = |path: &Path| -> Option<PathBuf> {
+        match std::fs::canonicalize(path) {
+            Ok(path) => Some(path),
+            // A file the operation removed, resolve its directory instead.
+            Err(_) => Some(
+                std::fs::canonicalize(path.parent()?)
+                    .ok()?
+                    .join(path.file_name()?),
+            ),
+        }
+    };
+    match (absolute(root), absolute(path)) {
+        (Some(root), Some(path)) => match path.strip_prefix(&root) {
+            Ok(relative)
+                if relative
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_))) =>
+            {
+                relative.to_path_buf()
+            }
+            _ => path,
+        },
+        (_, Some(path)) => path,
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_redo() {
+        let root = std::env::temp_dir().join(format!("marauders_history_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("marauder.toml"), "").unwrap();
+        let file = root.join("calc.rs");
+        let bundle = root.join("calc.rs.patches/manifest.toml");
+        std::fs::write(&file, "a + b\n").unwrap();
+
+        let snapshot = Snapshot::take(
+            &root,
+            std::slice::from_ref(&file),
+            &[root.join("calc.rs.patches")],
+        )
+        .unwrap();
+        std::fs::write(&file, "a - b\n").unwrap();
+        std::fs::create_dir_all(bundle.parent().unwrap()).unwrap();
+        std::fs::write(&bundle, "manifest").unwrap();
+        snapshot.record("convert", vec![]).unwrap();
+
+        let history = History::load(&root).unwrap();
+        assert_eq!(history.cursor, 1);
+        assert_eq!(history.entries[0].files[0].path, PathBuf::from("calc.rs"));
+
+        let entry = History::undo(&root).unwrap().unwrap();
+        assert_eq!(entry.operation, "convert");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "a + b\n");
+        assert!(!root.join("calc.rs.patches").exists());
+        assert_eq!(History::undo(&root).unwrap(), None);
+
+        History::redo(&root).unwrap().unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "a - b\n");
+        assert_eq!(std::fs::read_to_string(&bundle).unwrap(), "manifest");
+        assert_eq!(History::redo(&root).unwrap(), None);
+
+        // A file edited since the operation is never overwritten.
+        std::fs::write(&file, "a * b\n").unwrap();
+        let err = History::undo(&root).unwrap_err().to_string();
+        assert!(err.contains("cannot undo 'convert'"));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "a * b\n");
+
+        // Recording a new operation discards the ones that were undone.
+        std::fs::write(&file, "a - b\n").unwrap();
+        History::undo(&root).unwrap();
+        let snapshot = Snapshot::take(&root, std::slice::from_ref(&file), &[]).unwrap();
+        std::fs::write(&file, "a / b\n").unwrap();
+        snapshot.record("edit", vec![]).unwrap();
+        let history = History::load(&root).unwrap();
+        assert_eq!(history.entries.len(), 1);
+        assert_eq!(history.entries[0].operation, "edit");
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_history_retention() {
+        let root = std::env::temp_dir().join(format!("marauders_retention_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let file = root.join("calc.rs");
+        std::fs::write(&file, "0\n").unwrap();
+
+        for step in 1..=MAX_HISTORY_ENTRIES + 5 {
+            let snapshot = Snapshot::take(&root, std::slice::from_ref(&file), &[]).unwrap();
+            std::fs::write(&file, format!("{step}\n")).unwrap();
+            snapshot.record(&format!("edit {step}"), vec![]).unwrap();
+        }
+        let history = History::load(&root).unwrap();
+        assert_eq!(history.entries.len(), MAX_HISTORY_ENTRIES);
+        assert_eq!(history.cursor, MAX_HISTORY_ENTRIES);
+        assert_eq!(history.entries[0].operation, "edit 6");
+
+        // Only the contents of the kept operations are stored, from "5" to the current one.
+        let objects = || std::fs::read_dir(History::dir(&root).join(OBJECTS_DIR)).unwrap();
+        assert_eq!(objects().count(), MAX_HISTORY_ENTRIES + 1);
+        assert!(!History::dir(&root)
+            .join(OBJECTS_DIR)
+            .join(hash(b"4\n"))
+            .exists());
+
+        // Contents of undone operations are removed once a new operation discards them.
+        History::undo(&root).unwrap();
+        let snapshot = Snapshot::take(&root, std::slice::from_ref(&file), &[]).unwrap();
+        std::fs::write(&file, "edited\n").unwrap();
+        snapshot.record("edit", vec![]).unwrap();
+        let last = format!("{}\n", MAX_HISTORY_ENTRIES + 5);
+        assert!(!History::dir(&root)
+            .join(OBJECTS_DIR)
+            .join(hash(last.as_bytes()))
+            .exists());
+        assert_eq!(objects().count(), MAX_HISTORY_ENTRIES + 1);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_root_for() {
+        let root = std::env::temp_dir().join(format!("marauders_root_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("inner")).unwrap();
+        std::fs::write(root.join("marauder.toml"), "").unwrap();
+        std::fs::write(root.join("inner/calc.rs"), "").unwrap();
+
+        // A directory is the root of its own project, like the project opened from it.
+        assert_eq!(History::root_for(&root.join("inner")), root.join("inner"));
+        assert_eq!(History::root_for(&root.join("inner/calc.rs")), root);
+        std::fs::create_dir_all(root.join("inner").join(STATE_DIR)).unwrap();
+        assert_eq!(
+            History::root_for(&root.join("inner/calc.rs")),
+            root.join("inner")
+        );
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -1,6 +1,9 @@
+use std::path::Path;
+
+use globset::Glob;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum Language {
     Rocq,
     Haskell,
@@ -8,6 +11,13 @@ pub enum Language {
     Rust,
     OCaml,
     Python,
+    C,
+    Cpp,
+    Java,
+    JavaScript,
+    TypeScript,
+    Go,
+    Lean,
     #[serde(untagged)]
     Custom(CustomLanguage),
 }
@@ -15,23 +25,83 @@ pub enum Language {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct CustomLanguage {
     pub name: String,
+    /// Extension of the language's files, empty if they are only found by `patterns`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub extension: String,
+    /// Further globs matched against file names, such as `*.mli` or `Makefile`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<String>,
     pub comment_begin: String,
-    pub comment_end: String,
+    /// End of a block comment, or `None` if `comment_begin` starts a comment that runs to
+    /// the end of the line, in which case every inactive line of a variation is commented
+    /// out on its own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment_end: Option<String>,
     pub mutation_marker: String,
 }
 
 impl Language {
+    /// The usual extension of the language's files
     pub fn file_extension(&self) -> &str {
+        self.file_extensions().first().copied().unwrap_or_default()
+    }
+
+    /// Every extension of the language's files, the usual one first
+    pub fn file_extensions(&self) -> Vec<&str> {
         match self {
-            Language::Rocq => "v",
-            Language::Haskell => "hs",
-            Language::Racket => "rkt",
-            Language::Rust => "rs",
-            Language::OCaml => "ml",
-            Language::Python => "py",
-            Language::Custom(custom) => custom.extension.as_str(),
+            Language::Rocq => vec!["v"],
+            Language::Haskell => vec!["hs", "lhs"],
+            Language::Racket => vec!["rkt", "rktl", "scrbl"],
+            Language::Rust => vec!["rs"],
+            Language::OCaml => vec!["ml", "mli"],
+            Language::Python => vec!["py", "pyi"],
+            Language::C => vec!["c", "h"],
+            Language::Cpp => vec!["cpp", "cc", "cxx", "hpp", "hh", "hxx"],
+            Language::Java => vec!["java"],
+            Language::JavaScript => vec!["js", "mjs", "cjs", "jsx"],
+            Language::TypeScript => vec!["ts", "tsx", "mts", "cts"],
+            Language::Go => vec!["go"],
+            Language::Lean => vec!["lean"],
+            Language::Custom(custom) if custom.extension.is_empty() => vec![],
+            Language::Custom(custom) => vec![custom.extension.as_str()],
+        }
+    }
+
+    /// Globs matched against file names to find the language's files, such as `*.hs`
+    pub fn file_patterns(&self) -> Vec<String> {
+        let mut patterns: Vec<String> = self
+            .file_extensions()
+            .iter()
+            .map(|extension| format!("*.{extension}"))
+            .collect();
+        if let Language::Custom(custom) = self {
+            patterns.extend(custom.patterns.iter().cloned());
         }
+        patterns
+    }
+
+    /// The language of the file at `path`, by its extension or else by the `patterns` of
+    /// the custom languages
+    pub fn path_to_language(
+        path: &Path,
+        custom_languages: &Vec<CustomLanguage>,
+    ) -> Option<Language> {
+        if let Some(language) = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| Language::extension_to_language(extension, custom_languages))
+        {
+            return Some(language);
+        }
+        let file_name = path.file_name()?;
+        custom_languages
+            .iter()
+            .find(|custom| {
+                custom.patterns.iter().any(|pattern| {
+                    Glob::new(pattern).is_ok_and(|glob| glob.compile_matcher().is_match(file_name))
+                })
+            })
+            .map(|custom| Language::Custom(custom.clone()))
     }
 
     pub fn extension_to_language(
@@ -40,14 +110,21 @@ impl Language {
     ) -> Option<Language> {
         match ext {
             "v" => Some(Language::Rocq),
-            "hs" => Some(Language::Haskell),
-            "rkt" => Some(Language::Racket),
+            "hs" | "lhs" => Some(Language::Haskell),
+            "rkt" | "rktl" | "scrbl" => Some(Language::Racket),
             "rs" => Some(Language::Rust),
-            "ml" => Some(Language::OCaml),
-            "py" => Some(Language::Python),
+            "ml" | "mli" => Some(Language::OCaml),
+            "py" | "pyi" => Some(Language::Python),
+            "c" | "h" => Some(Language::C),
+            "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => Some(Language::Cpp),
+            "java" => Some(Language::Java),
+            "js" | "mjs" | "cjs" | "jsx" => Some(Language::JavaScript),
+            "ts" | "tsx" | "mts" | "cts" => Some(Language::TypeScript),
+            "go" => Some(Language::Go),
+            "lean" => Some(Language::Lean),
             _ => {
                 for custom in custom_languages {
-                    if custom.extension == ext {
+                    if !custom.extension.is_empty() && custom.extension == ext {
                         return Some(Language::Custom(custom.clone()));
                     }
                 }
@@ -56,10 +133,15 @@ impl Language {
         }
     }
 
-    pub fn name_to_language(
-        name: &str,
-        custom_languages: &Vec<CustomLanguage>,
-    ) -> Option<Language> {
+    pub fn name_to_language(name: &str, custom_languages: &[CustomLanguage]) -> Option<Language> {
+        let language = Language::find_by_name(name, custom_languages);
+        if language.is_none() {
+            log::warn!("unknown language: {}", name);
+        }
+        language
+    }
+
+    fn find_by_name(name: &str, custom_languages: &[CustomLanguage]) -> Option<Language> {
         match name.to_lowercase().as_str() {
             "rocq" => Some(Language::Rocq),
             "haskell" => Some(Language::Haskell),
@@ -67,18 +149,50 @@ impl Language {
             "rust" => Some(Language::Rust),
             "ocaml" => Some(Language::OCaml),
             "python" => Some(Language::Python),
-            _ => {
-                for custom in custom_languages {
-                    if custom.name == name {
-                        return Some(Language::Custom(custom.clone()));
-                    }
-                }
-                log::warn!("unknown language: {}", name);
-                None
-            }
+            "c" => Some(Language::C),
+            "cpp" | "c++" => Some(Language::Cpp),
+            "java" => Some(Language::Java),
+            "javascript" | "js" => Some(Language::JavaScript),
+            "typescript" | "ts" => Some(Language::TypeScript),
+            "go" => Some(Language::Go),
+            "lean" => Some(Language::Lean),
+            _ => custom_languages
+                .iter()
+                .find(|custom| custom.name == name)
+                .map(|custom| Language::Custom(custom.clone())),
         }
     }
 
+    /// The language a script names in its shebang, such as `#!/usr/bin/env python3`, or in
+    /// an Emacs or Vim modeline, such as `-*- mode: python -*-` or `vim: set ft=python:`
+    pub fn content_to_language(
+        content: &str,
+        custom_languages: &[CustomLanguage],
+    ) -> Option<Language> {
+        let lines: Vec<&str> = content.lines().collect();
+        if let Some(language) = lines
+            .first()
+            .and_then(|line| shebang_interpreter(line))
+            .and_then(|interpreter| script_language(interpreter, custom_languages))
+        {
+            return Some(language);
+        }
+        // Emacs looks for a modeline in the first line, or the second after a shebang, and
+        // Vim in the first and last five lines
+        let emacs = lines.iter().take(2).find_map(|line| emacs_mode(line));
+        let vim = || {
+            let tail = lines.len().saturating_sub(5).max(5);
+            lines
+                .iter()
+                .take(5)
+                .chain(lines.iter().skip(tail))
+                .find_map(|line| vim_filetype(line))
+        };
+        emacs
+            .or_else(vim)
+            .and_then(|name| script_language(name, custom_languages))
+    }
+
     pub fn comment_begin(&self) -> String {
         match self {
             Language::Rocq => "(*".to_string(),
@@ -87,6 +201,13 @@ impl Language {
             Language::Rust => "/*".to_string(),
             Language::OCaml => "(*".to_string(),
             Language::Python => r#"""""#.to_string(),
+            Language::C
+            | Language::Cpp
+            | Language::Java
+            | Language::JavaScript
+            | Language::TypeScript
+            | Language::Go => "/*".to_string(),
+            Language::Lean => "/-".to_string(),
             Language::Custom(custom) => custom.comment_begin.clone(),
         }
     }
@@ -98,12 +219,40 @@ impl Language {
             Language::Racket => "|#".to_string(),
             Language::Rust => "*/".to_string(),
             Language::OCaml => "*)".to_string(),
-            Language::Custom(custom) => custom.comment_end.clone(),
+            Language::Custom(custom) => custom.comment_end.clone().unwrap_or_default(),
             Language::Python => r#"""""#.to_string(),
+            Language::C
+            | Language::Cpp
+            | Language::Java
+            | Language::JavaScript
+            | Language::TypeScript
+            | Language::Go => "*/".to_string(),
+            Language::Lean => "-/".to_string(),
         }
     }
 
+    /// Whether the language only has line comments, see [`CustomLanguage::comment_end`]
+    pub fn uses_line_comments(&self) -> bool {
+        matches!(
+            self,
+            Language::Custom(CustomLanguage {
+                comment_end: None,
+                ..
+            })
+        )
+    }
+
     pub fn variation_begin(&self, name: &str) -> String {
+        if self.uses_line_comments() {
+            return format!(
+                "{}{} {}",
+                self.comment_begin(),
+                self.mutation_marker(),
+                name
+            )
+            .trim_end()
+            .to_string();
+        }
         format!(
             r"{}{} {}{}",
             self.comment_begin(),
@@ -150,8 +299,128 @@ impl Language {
             | Language::Racket
             | Language::OCaml
             | Language::Python => "!",
-            Language::Rust => "|",
+            // `/*!` opens doc comments in Rust and Doxygen, and `/-!` in Lean
+            Language::Rust
+            | Language::C
+            | Language::Cpp
+            | Language::Java
+            | Language::JavaScript
+            | Language::TypeScript
+            | Language::Go
+            | Language::Lean => "|",
             Language::Custom(custom_language) => custom_language.mutation_marker.as_str(),
         }
     }
 }
+
+/// The name of the program running a script, without its directory and version, from a
+/// shebang such as `#!/usr/bin/python3` or `#!/usr/bin/env -S python3 -u`
+fn shebang_interpreter(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+    }
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    (!program.is_empty()).then_some(program)
+}
+
+/// The major mode of an Emacs modeline, `-*- mode: python -*-` or `-*- python -*-`
+fn emacs_mode(line: &str) -> Option<&str> {
+    let (_, rest) = line.split_once("-*-")?;
+    let (variables, _) = rest.split_once("-*-")?;
+    if !variables.contains(':') {
+        return Some(variables.trim()).filter(|mode| !mode.is_empty());
+    }
+    variables.split(';').find_map(|variable| {
+        let (name, value) = variable.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("mode")
+            .then(|| value.trim())
+    })
+}
+
+/// The filetype of a Vim modeline, `vim: set ft=python:` or `vi: filetype=python`
+fn vim_filetype(line: &str) -> Option<&str> {
+    let options = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        let index = line.find(marker)?;
+        (index == 0 || line[..index].ends_with(char::is_whitespace))
+            .then(|| &line[index + marker.len()..])
+    })?;
+    options
+        .split(|c: char| c.is_whitespace() || c == ':')
+        .find_map(|option| {
+            option
+                .strip_prefix("ft=")
+                .or_else(|| option.strip_prefix("filetype="))
+        })
+}
+
+/// The language of an interpreter, Emacs mode or Vim filetype named by a script
+fn script_language(name: &str, custom_languages: &[CustomLanguage]) -> Option<Language> {
+    match name.to_lowercase().as_str() {
+        "coq" => Some(Language::Rocq),
+        "runghc" | "runhaskell" => Some(Language::Haskell),
+        "tuareg" => Some(Language::OCaml),
+        "node" => Some(Language::JavaScript),
+        "deno" | "ts-node" | "tsx" => Some(Language::TypeScript),
+        "lean4" => Some(Language::Lean),
+        _ => Language::find_by_name(name, custom_languages),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(content: &str) -> Option<Language> {
+        Language::content_to_language(content, &[])
+    }
+
+    #[test]
+    fn test_shebang() {
+        assert_eq!(detect("#!/usr/bin/env python3\n"), Some(Language::Python));
+        assert_eq!(detect("#!/usr/bin/python3.11 -u\n"), Some(Language::Python));
+        assert_eq!(
+            detect("#!/usr/bin/env -S NODE_ENV=test node --trace\n"),
+            Some(Language::JavaScript)
+        );
+        assert_eq!(detect("#!/usr/bin/env runghc\n"), Some(Language::Haskell));
+        assert_eq!(detect("#!/bin/sh\n"), None);
+        assert_eq!(detect("#! insert\n"), None);
+    }
+
+    #[test]
+    fn test_modelines() {
+        assert_eq!(detect("# -*- mode: python -*-\n"), Some(Language::Python));
+        assert_eq!(
+            detect("#!/bin/sh\n(* -*- coding: utf-8; mode: coq -*- *)\n"),
+            Some(Language::Rocq)
+        );
+        assert_eq!(detect("// -*- C++ -*-\n"), Some(Language::Cpp));
+        let script = format!("{}// vim: set ts=4 ft=typescript:\n", "x\n".repeat(20));
+        assert_eq!(detect(&script), Some(Language::TypeScript));
+        assert_eq!(detect("# vi:filetype=ocaml\n"), Some(Language::OCaml));
+        // Modelines in the middle of a file are ignored
+        let script = format!("{0}# vim: ft=python\n{0}", "x\n".repeat(10));
+        assert_eq!(detect(&script), None);
+        assert_eq!(detect("let navi: ft=python\n"), None);
+    }
+
+    #[test]
+    fn test_custom_interpreter() {
+        let shell = CustomLanguage {
+            name: "bash".to_string(),
+            extension: "sh".to_string(),
+            patterns: vec![],
+            comment_begin: "#".to_string(),
+            comment_end: None,
+            mutation_marker: "!".to_string(),
+        };
+        let custom_languages = vec![shell];
+        assert_eq!(
+            Language::content_to_language("#!/usr/bin/env bash\n", &custom_languages),
+            Some(Language::Custom(custom_languages[0].clone()))
+        );
+    }
+}
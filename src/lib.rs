@@ -27,20 +27,36 @@
 ///
 /// * `algebra`: Contains the DSL for expressing mutations.
 pub mod algebra;
+/// * `atomic`: Contains crash-safe replacement of files and directories.
+pub mod atomic;
+/// * `campaign`: Contains the persisted state of incremental mutation campaigns.
+pub mod campaign;
+pub use campaign::*;
 /// * `api`: Contains the library API for programmatic access.
 pub mod api;
 pub use api::*;
 /// * `code`: Contains the way marauders handle the code it analyzes and processes.
 pub mod code;
 pub use code::*;
+/// * `equivalence`: Contains the detection of variants that cannot change the program's behavior.
+pub mod equivalence;
+pub use equivalence::*;
+/// * `history`: Contains the undo/redo journal of operations that rewrote files.
+pub mod history;
+pub use history::*;
 /// * `languages`: Contains the language specific details for marauders supported languages.
 pub mod languages;
 pub use languages::*;
 /// * `project`: Contains the logic and structures for handling marauders projects.
 pub mod project;
+/// * `recovery`: Contains interruption handling and the journal of variants a test run activated.
+pub mod recovery;
 /// * `syntax`: Contains the different syntaxes for expressing mutants.
 pub mod syntax;
 pub use project::*;
 /// * `variation`: Contains the logic and structures for about variations.
 pub mod variation;
 pub use variation::*;
+/// * `workspace`: Contains isolated project copies used for running mutants in copy mode.
+pub mod workspace;
+pub use workspace::*;
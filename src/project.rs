@@ -1,26 +1,70 @@
 use std::{
-    collections::HashMap,
-    fs::{self, FileType},
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, FileType},
+    io::{Read, Seek, SeekFrom},
     path::{Path, PathBuf},
-    process::Output,
+    process::{Child, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
 };
 
 use anyhow::Context;
-use ignore::{overrides::OverrideBuilder, WalkBuilder};
+use ignore::{
+    overrides::{Override, OverrideBuilder},
+    WalkBuilder,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    code::Code,
+    code::{Code, MalformedVariation, MalformedVariations, ParseDiagnostic},
     languages::{CustomLanguage, Language},
     SpanContent,
 };
 
+/// How often a command running under a timeout is checked for completion
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+/// Kills the process group led by `child`.
+#[cfg(unix)]
+fn kill_process_group(child: &mut Child) -> std::io::Result<()> {
+    let pgid = child.id() as libc::pid_t;
+    // SAFETY: `kill` has no memory safety requirements; a negative pid targets the group.
+    if unsafe { libc::kill(-pgid, libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(child: &mut Child) -> std::io::Result<()> {
+    child.kill()
+}
+
 #[derive(Debug)]
 pub struct Project {
     pub root: PathBuf,
     pub files: Vec<ProjectFile>,
     pub config: Option<ProjectConfig>,
+    /// Glob the files were selected with, for projects without a config
+    pub pattern: Option<String>,
     pub parse_errors: Vec<String>,
+    /// Variations that do not have exactly one active variant, their files are left out of
+    /// `files` and reported in `parse_errors`
+    pub malformed: Vec<MalformedVariation>,
+    /// Syntax errors in the mutation markers, their files are left out of `files` and
+    /// reported in `parse_errors`
+    pub diagnostics: Vec<ParseDiagnostic>,
 }
 
 #[derive(Debug)]
@@ -30,16 +74,26 @@ pub struct ProjectFile {
 }
 
 /// Project configuration
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ProjectConfig {
     /// List of languages that should be analyzed for mutations
     pub languages: Vec<Language>,
     /// List of glob strings to ignore
     pub ignore: Vec<String>,
-    /// Whether to ignore files based on .gitignore
+    /// Whether to ignore files based on .gitignore, also outside a git checkout
     pub use_gitignore: bool,
     /// Custom languages outside of the standart set
     pub custom_languages: Vec<CustomLanguage>,
+    /// Languages of the files matched by globs relative to the project root, taking
+    /// precedence over their extension, such as `"scripts/deploy" = "Python"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub file_languages: BTreeMap<String, String>,
+    /// Time limit in seconds for running the test command against a single mutant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Command run before the test command for every mutant, a failure marks it stillborn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<String>,
 }
 
 impl Default for ProjectConfig {
@@ -49,10 +103,89 @@ impl Default for ProjectConfig {
             ignore: vec![],
             use_gitignore: true,
             custom_languages: vec![],
+            file_languages: BTreeMap::new(),
+            timeout: None,
+            build: None,
         }
     }
 }
 
+impl ProjectConfig {
+    /// The `marauder.toml` of the closest directory above `path`, with that directory, or
+    /// `None` if no ancestor has one
+    pub(crate) fn find(path: &Path) -> anyhow::Result<Option<(PathBuf, ProjectConfig)>> {
+        let Some(root) = path
+            .ancestors()
+            .skip(1)
+            .map(|dir| {
+                if dir.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    dir
+                }
+            })
+            .find(|dir| dir.join("marauder.toml").is_file())
+        else {
+            return Ok(None);
+        };
+        let config = toml::from_str(&fs::read_to_string(root.join("marauder.toml"))?)?;
+        Ok(Some((root.to_path_buf(), config)))
+    }
+
+    /// Matchers for the globs of `file_languages`, each with the language it gives the files
+    /// of the project at `root`
+    pub(crate) fn file_language_matchers(
+        &self,
+        root: &Path,
+    ) -> anyhow::Result<Vec<(Override, Language)>> {
+        self.file_languages
+            .iter()
+            .map(|(glob, name)| {
+                let language = Language::name_to_language(name, &self.custom_languages)
+                    .context(format!("unknown language '{name}' for '{glob}'"))?;
+                let mut matcher = OverrideBuilder::new(root);
+                matcher.add(glob)?;
+                Ok((matcher.build()?, language))
+            })
+            .collect()
+    }
+
+    /// Whether files in `language` are part of the project
+    fn covers(&self, language: &Language) -> bool {
+        matches!(language, Language::Custom(_)) || self.languages.contains(language)
+    }
+}
+
+/// The language `file_languages` gives to a file, see [`ProjectConfig::file_language_matchers`]
+pub(crate) fn mapped_language(matchers: &[(Override, Language)], path: &Path) -> Option<Language> {
+    matchers
+        .iter()
+        .find(|(matcher, _)| matcher.matched(path, false).is_whitelist())
+        .map(|(_, language)| language.clone())
+}
+
+/// The language a file without an extension names in a shebang or modeline, read from its
+/// first and last few kilobytes. This costs up to 8 KiB of reading per extensionless file, so
+/// it is only called for files the walk kept after the `ignore` globs and `.gitignore`, and
+/// stops after the first 4 KiB of a binary file.
+fn sniff_script_language(path: &Path, custom_languages: &[CustomLanguage]) -> Option<Language> {
+    const WINDOW: u64 = 4096;
+    let mut file = File::open(path).ok()?;
+    let length = file.metadata().ok()?.len();
+    let mut content = Vec::new();
+    file.by_ref().take(WINDOW).read_to_end(&mut content).ok()?;
+    if content.contains(&0) {
+        return None;
+    }
+    if length > WINDOW {
+        file.seek(SeekFrom::Start(length.saturating_sub(WINDOW).max(WINDOW)))
+            .ok()?;
+        content.push(b'\n');
+        file.read_to_end(&mut content).ok()?;
+    }
+    Language::content_to_language(&String::from_utf8_lossy(&content), custom_languages)
+}
+
 impl Project {
     pub fn new(path: &Path, pattern: Option<&str>) -> anyhow::Result<Self> {
         let cfg = if let Ok(cfg_path) = std::env::var("MARAUDER_CONFIG") {
@@ -86,6 +219,8 @@ impl Project {
         let walk = WalkBuilder::new(path).overrides(overrides.build()?).build();
 
         let mut parse_errors = Vec::new();
+        let mut malformed = Vec::new();
+        let mut diagnostics = Vec::new();
         let files = walk
             .filter_map(|entry| {
                 let entry = entry.unwrap();
@@ -97,13 +232,19 @@ impl Project {
                     return None;
                 }
 
-                let code = Code::from_file(entry.path(), &vec![]);
+                let code = Code::from_file(entry.path(), None, &vec![]);
                 match code {
                     Ok(code) => Some(ProjectFile {
                         path: entry.path().to_path_buf(),
                         code,
                     }),
                     Err(err) => {
+                        if let Some(MalformedVariations(variations)) = err.downcast_ref() {
+                            malformed.extend(variations.iter().cloned());
+                        }
+                        if let Some(diagnostic) = err.downcast_ref::<ParseDiagnostic>() {
+                            diagnostics.push(diagnostic.clone());
+                        }
                         let message = format!(
                             "could not read file '{}': {}",
                             entry.path().to_string_lossy(),
@@ -121,49 +262,82 @@ impl Project {
             root,
             files,
             config: None,
+            pattern: pattern.map(str::to_string),
             parse_errors,
+            malformed,
+            diagnostics,
         })
     }
 
     pub fn with_config(path: &Path, config: ProjectConfig) -> anyhow::Result<Self> {
         let root = PathBuf::from(path);
 
-        let mut overrides = OverrideBuilder::new(path);
+        // Files are part of the project when they match a language pattern
+        let mut patterns = OverrideBuilder::new(path);
 
         // Add language patterns
         for lang in &config.languages {
-            overrides.add(format!("**/*.{}", lang.file_extension()).as_str())?;
+            for pattern in lang.file_patterns() {
+                patterns.add(format!("**/{pattern}").as_str())?;
+            }
         }
         // Add custom language patterns
         for custom in &config.custom_languages {
-            overrides.add(format!("**/*.{}", custom.extension).as_str())?;
+            for pattern in Language::Custom(custom.clone()).file_patterns() {
+                patterns.add(format!("**/{pattern}").as_str())?;
+            }
         }
+        let patterns = patterns.build()?;
+        let file_languages = config.file_language_matchers(path)?;
 
         // Add ignore patterns
+        let mut overrides = OverrideBuilder::new(path);
         for ignore in &config.ignore {
             overrides.add(format!("!{ignore}").as_str())?;
         }
 
+        // Excluded files are never yielded, so they are not sniffed for a shebang either
         let walk = WalkBuilder::new(path)
             .git_ignore(config.use_gitignore)
+            .require_git(false)
             .overrides(overrides.build()?)
             .build();
 
         let mut files = Vec::new();
         let mut parse_errors = Vec::new();
+        let mut malformed = Vec::new();
+        let mut diagnostics = Vec::new();
         for entry in walk {
             let entry = entry.unwrap();
             if entry.file_type().unwrap().is_dir() {
                 continue;
             }
+            let language = mapped_language(&file_languages, entry.path());
+            if language.is_none()
+                && !patterns.matched(entry.path(), false).is_whitelist()
+                && (entry.path().extension().is_some()
+                    || !entry
+                        .file_type()
+                        .is_some_and(|file_type| file_type.is_file())
+                    || !sniff_script_language(entry.path(), &config.custom_languages)
+                        .is_some_and(|language| config.covers(&language)))
+            {
+                continue;
+            }
             log::trace!("found file: {}", entry.path().to_string_lossy());
-            let code = Code::from_file(entry.path(), &config.custom_languages);
+            let code = Code::from_file(entry.path(), language, &config.custom_languages);
             match code {
                 Ok(code) => files.push(ProjectFile {
                     path: entry.path().to_path_buf(),
                     code,
                 }),
                 Err(err) => {
+                    if let Some(MalformedVariations(variations)) = err.downcast_ref() {
+                        malformed.extend(variations.iter().cloned());
+                    }
+                    if let Some(diagnostic) = err.downcast_ref::<ParseDiagnostic>() {
+                        diagnostics.push(diagnostic.clone());
+                    }
                     let message = format!(
                         "could not read file '{}': {}",
                         entry.path().to_string_lossy(),
@@ -179,14 +353,17 @@ impl Project {
             root,
             files,
             config: Some(config),
+            pattern: None,
             parse_errors,
+            malformed,
+            diagnostics,
         })
     }
 
     pub fn with_language(path: &Path, lang: &Language) -> anyhow::Result<Self> {
         Self::with_pattern(
             path,
-            Some(format!("**/*.{}", lang.file_extension()).as_str()),
+            Some(format!("**/{{{}}}", lang.file_patterns().join(",")).as_str()),
         )
     }
 }
@@ -328,6 +505,32 @@ impl Project {
         Ok(())
     }
 
+    /// Resets the variation containing the variant back to base
+    pub fn unset(&mut self, variant: &str) -> anyhow::Result<()> {
+        for file in self.files.iter_mut() {
+            let found = file
+                .code
+                .spans
+                .iter()
+                .enumerate()
+                .find_map(|(index, span)| match &span.content {
+                    SpanContent::Variation(v) if v.variants.iter().any(|v| v.name == variant) => {
+                        Some((index, v.active))
+                    }
+                    _ => None,
+                });
+
+            if let Some((variation_index, active)) = found {
+                if active != 0 {
+                    file.code.set_active_variant(variation_index, 0)?;
+                }
+                return Ok(());
+            }
+        }
+
+        anyhow::bail!("variant '{variant}' not found")
+    }
+
     /// Sets the active variants for a test
     pub fn set_many(&mut self, test: &Vec<String>) -> anyhow::Result<()> {
         for variant in test {
@@ -346,6 +549,71 @@ impl Project {
             .context("failed to run command")
     }
 
+    /// Runs a command at the project root, killing it once `timeout` has elapsed
+    ///
+    /// Returns `None` if the command was killed because it ran out of time.
+    pub fn run_with_timeout(
+        &self,
+        command: &str,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Option<Output>> {
+        self.run_with_env(command, &[], timeout)
+    }
+
+    /// Runs a command at the project root with additional environment variables, killing it
+    /// once `timeout` has elapsed or when the run is interrupted, see
+    /// [`crate::recovery::interrupted`]
+    ///
+    /// Returns `None` if the command was killed because it ran out of time.
+    pub fn run_with_env(
+        &self,
+        command: &str,
+        env: &[(String, String)],
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Option<Output>> {
+        let mut process = std::process::Command::new("sh");
+        process
+            .arg("-c")
+            .arg(command)
+            .envs(env.iter().map(|(key, value)| (key, value)))
+            .current_dir(&self.root)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // Run the command in its own process group, so that everything it spawns (build
+        // tools, test binaries) can be killed together when it times out or is interrupted.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut process, 0);
+        let mut child = process.spawn().context("failed to run command")?;
+        // Drain the pipes while waiting, a chatty command would otherwise block on a full pipe.
+        let stdout = drain(child.stdout.take());
+        let stderr = drain(child.stderr.take());
+
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if crate::recovery::interrupted() {
+                kill_process_group(&mut child)?;
+                child.wait()?;
+                anyhow::bail!("interrupted");
+            }
+            if let Some(timeout) = timeout.filter(|timeout| start.elapsed() >= *timeout) {
+                kill_process_group(&mut child)?;
+                child.wait()?;
+                log::debug!("command '{}' timed out after {:?}", command, timeout);
+                return Ok(None);
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+
+        Ok(Some(Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        }))
+    }
+
     /// Resets a project to the base
     pub fn reset(&mut self) -> anyhow::Result<()> {
         for file in self.files.iter_mut() {
@@ -383,6 +651,18 @@ mod tests {
         assert!(file_paths.contains(&PathBuf::from("test/racket/BST.rkt")));
         assert!(file_paths.contains(&PathBuf::from("test/python/bst.py")));
         assert!(file_paths.contains(&PathBuf::from("test/haskell/BST.hs")));
+        for fixture in [
+            "test/c/bst.c",
+            "test/cn/bst.c",
+            "test/cpp/bst.cpp",
+            "test/java/BST.java",
+            "test/javascript/bst.js",
+            "test/typescript/bst.ts",
+            "test/go/bst.go",
+            "test/lean/BST.lean",
+        ] {
+            assert!(file_paths.contains(&PathBuf::from(fixture)));
+        }
         let rust_fixture = PathBuf::from("test/rust/bst.rs");
         let expected_min = if rust_fixture.exists() { 7 } else { 6 };
         if rust_fixture.exists() {
@@ -391,6 +671,24 @@ mod tests {
         assert!(project.files.len() >= expected_min);
     }
 
+    #[test]
+    fn test_project_new_config() {
+        let project = Project::new(Path::new("test"), None).unwrap();
+        let language = |path: &str| {
+            project
+                .files
+                .iter()
+                .find(|f| f.path == Path::new(path))
+                .map(|f| f.code.language.clone())
+                .unwrap()
+        };
+        assert_eq!(language("test/c/bst.c"), Language::C);
+        // The CN fixture keeps line-comment markers, as it sits inside a CN spec block
+        assert!(
+            matches!(language("test/cn/bst.c"), Language::Custom(custom) if custom.name == "cn")
+        );
+    }
+
     #[test]
     fn test_project_recursive() {
         let project = Project::with_pattern(Path::new("."), Some("!src/lib.rs")).unwrap();
@@ -419,6 +717,35 @@ mod tests {
         assert!(file_paths.contains(&PathBuf::from("test/rocq/STLC.v").canonicalize().unwrap()));
     }
 
+    #[test]
+    fn test_project_lang_extensions() {
+        let root = std::env::temp_dir().join(format!("marauders_cpp_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        for name in ["bst.cc", "bst.hpp", "bst.c"] {
+            fs::copy("test/cpp/bst.cpp", root.join(name)).unwrap();
+        }
+
+        let project = Project::with_language(&root, &Language::Cpp).unwrap();
+        let mut file_paths = project
+            .files
+            .iter()
+            .map(|f| f.path.strip_prefix(&root).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        file_paths.sort();
+        assert_eq!(
+            file_paths,
+            vec![PathBuf::from("bst.cc"), PathBuf::from("bst.hpp")]
+        );
+        assert!(project
+            .files
+            .iter()
+            .all(|f| f.code.language == Language::Cpp));
+        assert_eq!(project.files[0].code.get_all_variants().len(), 5);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
     #[test]
     fn test_project_config() {
         let config = ProjectConfig {
@@ -426,6 +753,9 @@ mod tests {
             ignore: vec!["src/syntax".to_string(), "**/src/lib.rs".to_string()],
             use_gitignore: false,
             custom_languages: vec![],
+            file_languages: BTreeMap::new(),
+            timeout: None,
+            build: None,
         };
         let project = Project::with_config(Path::new("."), config).unwrap();
         assert_eq!(project.root, PathBuf::from("."));
@@ -451,6 +781,9 @@ mod tests {
             ignore: vec!["src/syntax".to_string(), "src/lib.rs".to_string()],
             use_gitignore: true,
             custom_languages: vec![],
+            file_languages: BTreeMap::new(),
+            timeout: None,
+            build: None,
         };
         let project = Project::with_config(Path::new("."), config).unwrap();
         assert_eq!(project.root, PathBuf::from("."));
@@ -484,10 +817,14 @@ mod tests {
             custom_languages: vec![CustomLanguage {
                 name: "Marauder".to_string(),
                 extension: "rs".to_string(),
+                patterns: vec![],
                 comment_begin: "/*".to_string(),
-                comment_end: "*/".to_string(),
+                comment_end: Some("*/".to_string()),
                 mutation_marker: "|".to_string(),
             }],
+            file_languages: BTreeMap::new(),
+            timeout: None,
+            build: None,
         };
         let project = Project::with_config(Path::new("."), config).unwrap();
         assert_eq!(project.root, PathBuf::from("."));
@@ -504,4 +841,173 @@ mod tests {
         ));
         assert!(!file_paths.contains(&PathBuf::from("test/rocq/BST.v").canonicalize().unwrap()));
     }
+
+    #[test]
+    fn test_project_config_file_patterns() {
+        let root = std::env::temp_dir().join(format!("marauders_patterns_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("lib")).unwrap();
+        let recipe = "all:\n\t#! greeting\n\techo hello\n\t#!! greeting_1\n\t#! echo bye\n\t# !\n";
+        fs::write(root.join("Makefile"), recipe).unwrap();
+        fs::write(root.join("lib/rules.mk"), recipe).unwrap();
+        fs::write(root.join("lib/Makefile.bak"), recipe).unwrap();
+        fs::write(
+            root.join("lib/set.mli"),
+            "(*! mem *)\nval mem : int -> bool\n(*!! mem_1 *)\n(*! val mem : int -> int *)\n(* !*)\n",
+        )
+        .unwrap();
+
+        let config = ProjectConfig {
+            languages: vec![Language::OCaml],
+            ignore: vec![],
+            use_gitignore: false,
+            custom_languages: vec![CustomLanguage {
+                name: "Make".to_string(),
+                extension: String::new(),
+                patterns: vec!["Makefile".to_string(), "*.mk".to_string()],
+                comment_begin: "#".to_string(),
+                comment_end: None,
+                mutation_marker: "!".to_string(),
+            }],
+            file_languages: BTreeMap::new(),
+            timeout: None,
+            build: None,
+        };
+        let project = Project::with_config(&root, config).unwrap();
+        let mut file_paths = project
+            .files
+            .iter()
+            .map(|f| f.path.strip_prefix(&root).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        file_paths.sort();
+        assert_eq!(
+            file_paths,
+            vec![
+                PathBuf::from("Makefile"),
+                PathBuf::from("lib/rules.mk"),
+                PathBuf::from("lib/set.mli"),
+            ]
+        );
+        assert!(project.parse_errors.is_empty());
+        for file in &project.files {
+            assert_eq!(file.code.get_all_variants().len(), 1);
+        }
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_project_new_scripts() {
+        let root = std::env::temp_dir().join(format!("marauders_scripts_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("bin")).unwrap();
+        let body = "def add(a, b):\n    \"\"\"! add \"\"\"\n    return a + b\n    \"\"\"!! add_1 \"\"\"\n    \"\"\"!\n    return a - b\n    \"\"\"\n    \"\"\" !\"\"\"\n";
+        fs::write(
+            root.join("bin/deploy"),
+            format!("#!/usr/bin/env python3\n{body}"),
+        )
+        .unwrap();
+        fs::write(
+            root.join("bin/notes"),
+            format!("# vim: set ft=python:\n{body}"),
+        )
+        .unwrap();
+        fs::write(root.join("bin/plain"), body).unwrap();
+        fs::write(
+            root.join("bin/build"),
+            format!("#!/usr/bin/env node\n{body}"),
+        )
+        .unwrap();
+        fs::write(
+            root.join("bin/setup.cfg"),
+            format!("#!/usr/bin/env python3\n{body}"),
+        )
+        .unwrap();
+        fs::write(
+            root.join("bin/tool"),
+            format!("#!/usr/bin/env python3\n\0{body}"),
+        )
+        .unwrap();
+        // Excluded files are neither sniffed nor loaded, even though they name a language
+        for dir in ["out", "dist"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            fs::write(
+                root.join(dir).join("deploy"),
+                format!("#!/usr/bin/env python3\n{body}"),
+            )
+            .unwrap();
+        }
+        fs::write(root.join(".gitignore"), "dist/\n").unwrap();
+        fs::write(root.join("tasks.in"), body).unwrap();
+        fs::write(
+            root.join("marauder.toml"),
+            "languages = [\"Python\"]\nignore = [\"out\"]\nuse_gitignore = true\ncustom_languages = []\n\n[file_languages]\n\"*.in\" = \"Python\"\n",
+        )
+        .unwrap();
+
+        let project = Project::new(&root, None).unwrap();
+        let mut file_paths = project
+            .files
+            .iter()
+            .map(|f| f.path.strip_prefix(&root).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        file_paths.sort();
+        assert_eq!(
+            file_paths,
+            vec![
+                PathBuf::from("bin/deploy"),
+                PathBuf::from("bin/notes"),
+                PathBuf::from("tasks.in"),
+            ]
+        );
+        assert!(project.parse_errors.is_empty());
+        for file in &project.files {
+            assert_eq!(file.code.language, Language::Python);
+            assert_eq!(file.code.get_all_variants(), vec!["add_1"]);
+        }
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_run_with_timeout_kills_process_group() {
+        let root = std::env::temp_dir().join(format!("marauders_timeout_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let project = Project::new(&root, None).unwrap();
+
+        let output = project
+            .run_with_timeout("echo done", Some(Duration::from_secs(10)))
+            .unwrap()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "done\n");
+
+        // The background `sleep` is a grandchild of the runner, it must die with the shell.
+        let output = project
+            .run_with_timeout(
+                "sleep 30 & echo $! > sleep.pid; wait",
+                Some(Duration::from_millis(300)),
+            )
+            .unwrap();
+        assert!(output.is_none());
+
+        let pid = fs::read_to_string(root.join("sleep.pid")).unwrap();
+        let stat = Path::new("/proc").join(pid.trim()).join("stat");
+        let alive = || {
+            fs::read_to_string(&stat)
+                .map(|stat| !stat.contains(") Z "))
+                .unwrap_or(false)
+        };
+        for _ in 0..100 {
+            if !alive() {
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        assert!(!alive());
+
+        let _ = fs::remove_dir_all(root);
+    }
 }
@@ -0,0 +1,128 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::campaign::STATE_DIR;
+
+const JOURNAL_FILE: &str = "active";
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Variants a test run has activated in the working tree.
+///
+/// The journal is written before a term is applied and removed once the run has restored
+/// the project to base, so a journal left behind means the run was killed with those
+/// variants possibly still active.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Journal {
+    /// The variants of the term being tested
+    pub variants: Vec<String>,
+}
+
+impl Journal {
+    /// Path of the journal for a project rooted at `root`
+    pub fn path(root: &Path) -> PathBuf {
+        root.join(STATE_DIR).join(JOURNAL_FILE)
+    }
+
+    /// Loads the journal left by a test run of the project rooted at `root`, if any
+    pub fn load(root: &Path) -> anyhow::Result<Option<Self>> {
+        let path = Self::path(root);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)?;
+        let journal = serde_json::from_str(&content)
+            .with_context(|| format!("could not parse journal '{}'", path.to_string_lossy()))?;
+        Ok(Some(journal))
+    }
+
+    /// Saves the journal into the state directory of the project rooted at `root`
+    pub fn save(&self, root: &Path) -> anyhow::Result<()> {
+        let path = Self::path(root);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        crate::atomic::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Removes the journal of the project rooted at `root`
+    pub fn remove(root: &Path) -> anyhow::Result<()> {
+        let path = Self::path(root);
+        if path.is_file() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether SIGINT or SIGTERM was received since [`install_interrupt_handler`] was called
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Turns SIGINT and SIGTERM into a request to stop, see [`interrupted`].
+///
+/// Test runs check the request between terms and while waiting for the test command, so they
+/// can restore the project to base before exiting. A second signal terminates the process
+/// immediately.
+#[cfg(unix)]
+pub fn install_interrupt_handler() -> anyhow::Result<()> {
+    extern "C" fn handle(signal: libc::c_int) {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            // SAFETY: `signal` and `raise` are async-signal-safe.
+            unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+                libc::raise(signal);
+            }
+        }
+    }
+
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: `handle` only touches an atomic and calls async-signal-safe functions.
+        let previous = unsafe { libc::signal(signal, handle as *const () as libc::sighandler_t) };
+        if previous == libc::SIG_ERR {
+            return Err(std::io::Error::last_os_error())
+                .context("could not install the interrupt handler");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn install_interrupt_handler() -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_roundtrip() {
+        let root = std::env::temp_dir().join(format!(
+            "marauders_journal_roundtrip_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        assert_eq!(Journal::load(&root).unwrap(), None);
+
+        let journal = Journal {
+            variants: vec!["insert_1".to_string(), "delete_4".to_string()],
+        };
+        journal.save(&root).unwrap();
+        assert_eq!(Journal::load(&root).unwrap(), Some(journal));
+
+        Journal::remove(&root).unwrap();
+        assert_eq!(Journal::load(&root).unwrap(), None);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
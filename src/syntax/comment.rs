@@ -1,139 +1,292 @@
-use pest::iterators::Pair;
-use pest::Parser as _;
-use pest_derive::Parser;
-
-use crate::code::{Span, SpanContent};
+use crate::code::{MalformedVariation, MalformedVariations, ParseDiagnostic, Span, SpanContent};
+use crate::languages::Language;
 use crate::variation::{Variant, Variation};
 use crate::VariantBody;
 
-#[derive(Parser)]
-#[grammar = "syntax/comment.pest"]
-pub(crate) struct Parser;
+/// The markers of the comment syntax in a language with block comments, built from its
+/// delimiters and mutation marker.
+pub(crate) struct Syntax<'a> {
+    language: &'a Language,
+    begin: String,
+    end: String,
+    /// Accepted mutation markers, the language's own first. The built-in languages accept
+    /// both `!` and `|`.
+    markers: Vec<String>,
+}
+
+/// What the parser expected when it gave up, named in a diagnostic
+#[derive(Clone, Copy)]
+enum Expected {
+    VariationHeader,
+    VariationEnd,
+    VariantHeader,
+    VariantBody,
+    CommentEnd,
+    Name,
+}
+
+impl<'a> Syntax<'a> {
+    pub(crate) fn new(language: &'a Language) -> Syntax<'a> {
+        let mut markers = vec![language.mutation_marker().to_string()];
+        // Files in the first supported languages may use either marker
+        if matches!(
+            language,
+            Language::Rocq
+                | Language::Haskell
+                | Language::Racket
+                | Language::Rust
+                | Language::OCaml
+                | Language::Python
+        ) {
+            for marker in ["!", "|"] {
+                if !markers.iter().any(|m| m == marker) {
+                    markers.push(marker.to_string());
+                }
+            }
+        }
+        Syntax {
+            language,
+            begin: language.comment_begin(),
+            end: language.comment_end(),
+            markers,
+        }
+    }
+
+    /// The rest of `text` after its indentation, the comment start and one mutation marker
+    fn marker<'t>(&self, text: &'t str) -> Option<&'t str> {
+        let rest = text[indentation_of(text).len()..].strip_prefix(self.begin.as_str())?;
+        self.markers
+            .iter()
+            .find_map(|marker| rest.strip_prefix(marker.as_str()))
+    }
+
+    /// The name and tags of a variation header such as `(*! name [tag] *)`
+    fn variation_header(&self, text: &str) -> Option<(Option<String>, Vec<String>)> {
+        let title = self
+            .marker(text)?
+            .trim_end()
+            .strip_suffix(self.end.as_str())?;
+        if !title.starts_with([' ', '\t']) {
+            return None;
+        }
+        parse_title(title)
+    }
+
+    /// The rest of a variant header such as `(*!! name *)` after its markers
+    fn variant_header<'t>(&self, text: &'t str) -> Option<&'t str> {
+        let rest = self.marker(text)?;
+        self.markers
+            .iter()
+            .find_map(|marker| rest.strip_prefix(marker.as_str()))
+    }
+
+    /// Whether `text` is a variation end marker such as `(* !*)`
+    fn is_variation_end(&self, text: &str) -> bool {
+        let Some(rest) = text[indentation_of(text).len()..].strip_prefix(self.begin.as_str())
+        else {
+            return false;
+        };
+        let Some(rest) = rest.strip_prefix([' ', '\t']) else {
+            return false;
+        };
+        self.markers.iter().any(|marker| {
+            rest.strip_prefix(marker.as_str())
+                .is_some_and(|rest| rest.trim_end() == self.end)
+        })
+    }
+
+    /// The rest of the start of an inactive variant body, `(*!` not followed by a marker
+    fn body_marker<'t>(&self, text: &'t str) -> Option<&'t str> {
+        self.marker(text)
+            .filter(|rest| !self.markers.iter().any(|m| rest.starts_with(m.as_str())))
+    }
 
-pub(crate) fn parse_code(input: &str) -> anyhow::Result<Vec<Span>> {
-    let mut pairs = Parser::parse(Rule::program, input)?;
-    let pairs = pairs
-        .next()
-        .unwrap()
-        .into_inner()
-        .next()
-        .unwrap()
-        .into_inner();
-    let mut spans = vec![];
+    /// A readable name for what the parser expected, with the markers of the language
+    fn describe(&self, expected: Expected) -> String {
+        let language = self.language;
+        match expected {
+            Expected::VariationHeader => {
+                format!("variation header `{}`", language.variation_begin("name"))
+            }
+            Expected::VariationEnd => {
+                format!("variation end marker `{}`", language.variation_end())
+            }
+            Expected::VariantHeader => format!(
+                "variant header `{} name {}`",
+                language.variant_header_begin(),
+                language.variant_header_end()
+            ),
+            Expected::VariantBody => format!("variant body `{}`", language.variant_body_begin()),
+            Expected::CommentEnd => format!("comment end `{}`", self.end),
+            Expected::Name => "a name".to_string(),
+        }
+    }
 
-    let mut line = 1;
-    for pair in pairs {
-        line += parse_span(pair, &mut spans, line);
+    /// A diagnostic pointing at the start of the line at `index`
+    fn error(&self, text: &str, index: usize, expected: &[Expected]) -> ParseDiagnostic {
+        ParseDiagnostic {
+            path: Default::default(),
+            line: index + 1,
+            column: indentation_of(text).chars().count() + 1,
+            expected: expected.iter().map(|e| self.describe(*e)).collect(),
+            source_line: text.to_string(),
+        }
     }
+}
 
+/// Parses `input`, written in the comment syntax of `language`, failing if a variation does
+/// not have exactly one active variant.
+pub(crate) fn parse_code(input: &str, language: &Language) -> anyhow::Result<Vec<Span>> {
+    let (spans, malformed) = parse_code_repairing(input, language)?;
+    if !malformed.is_empty() {
+        return Err(MalformedVariations(malformed).into());
+    }
     Ok(spans)
 }
 
-fn parse_span(
-    pair: pest::iterators::Pair<Rule>,
-    spans: &mut Vec<crate::code::Span>,
-    line: usize,
-) -> usize {
-    match pair.as_rule() {
-        Rule::line | Rule::last_line => {
-            if spans.is_empty() {
-                log::trace!("adding first line: {:?}", pair.as_str());
-                spans.push(Span::constant(pair.as_str().to_string(), line));
-            } else {
-                let last = spans.last_mut().unwrap();
-                match &last.content {
-                    SpanContent::Line(c) => {
-                        log::trace!("adding line: {:?}", pair.as_str());
-                        last.content = SpanContent::Line(format!("{}{}", c, pair.as_str()));
-                    }
-                    SpanContent::Variation(_) => {
-                        log::trace!("adding variation: {:?}", pair.as_str());
-                        spans.push(Span::constant(pair.as_str().to_string(), line));
-                    }
-                }
+/// Parses `input`, resetting the variations that do not have exactly one active variant to
+/// their base and returning them alongside the spans. Neither the returned variations nor the
+/// diagnostic have a path.
+pub(crate) fn parse_code_repairing(
+    input: &str,
+    language: &Language,
+) -> Result<(Vec<Span>, Vec<MalformedVariation>), ParseDiagnostic> {
+    if language.uses_line_comments() {
+        return crate::syntax::line_comment::parse_code_repairing(input, language);
+    }
+    let syntax = Syntax::new(language);
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+    let mut spans: Vec<Span> = vec![];
+    let mut malformed = vec![];
+
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        let text = strip_newline(line);
+        if syntax.variation_header(text).is_some() {
+            let (mut variation, next) = parse_variation(&syntax, &lines, index)?;
+            if let Some(problem) = check_active(&variation, index + 1) {
+                log::warn!("{}, resetting it to base", problem);
+                variation.activate_base();
+                malformed.push(problem);
             }
-            pair.as_str().lines().count()
-        }
-        Rule::mutation => {
-            let mut pair = pair.into_inner();
-            let pair = pair.next().unwrap();
-            let (variation, current_lines) = parse_variation(pair);
             log::trace!("adding variation: {:?}", variation);
-            spans.push(Span::variation(variation, line));
-            current_lines
-        }
-        _ => {
-            unreachable!("unexpected rule {:?}", pair.as_rule());
+            spans.push(Span::variation(variation, index + 1));
+            index = next;
+        } else if syntax.variant_header(text).is_some() || syntax.is_variation_end(text) {
+            return Err(syntax.error(text, index, &[Expected::VariationHeader]));
+        } else {
+            match spans.last_mut() {
+                Some(Span {
+                    content: SpanContent::Line(c),
+                    ..
+                }) => {
+                    log::trace!("adding line: {:?}", line);
+                    c.push_str(line);
+                }
+                _ => {
+                    log::trace!("adding line: {:?}", line);
+                    spans.push(Span::constant(line.to_string(), index + 1));
+                }
+            }
+            index += 1;
         }
     }
+
+    Ok((spans, malformed))
 }
 
-fn parse_variation(pair: pest::iterators::Pair<Rule>) -> (Variation, usize) {
-    let mut pairs = pair.into_inner();
-    let header = pairs.next().unwrap();
+/// Reports `variation` unless exactly one of its base and variants is active
+pub(crate) fn check_active(variation: &Variation, line: usize) -> Option<MalformedVariation> {
+    let active: Vec<String> = std::iter::once(&variation.base)
+        .chain(&variation.variants)
+        .filter(|variant| variant.is_active())
+        .map(|variant| variant.name.clone())
+        .collect();
+    (active.len() != 1).then(|| MalformedVariation {
+        path: Default::default(),
+        line,
+        name: variation.name.clone(),
+        active,
+    })
+}
 
-    let (name, tags, variation_indentation) = parse_variation_header(header);
+/// Parses the variation whose header is at `start`, returning it with the index of the line
+/// following its end marker
+fn parse_variation(
+    syntax: &Syntax,
+    lines: &[&str],
+    start: usize,
+) -> Result<(Variation, usize), ParseDiagnostic> {
+    let header = strip_newline(lines[start]);
+    let (name, tags) = syntax
+        .variation_header(header)
+        .ok_or_else(|| syntax.error(header, start, &[Expected::VariationHeader]))?;
+    let indentation = indentation_of(header).to_string();
     log::trace!(
         "adding variation header: (name={:?}, tags={:?}, indentation={:?})",
         name,
         tags,
-        variation_indentation
+        indentation
     );
-    let base = pairs.next().unwrap();
 
-    let base = parse_base(base);
+    let mut index = start + 1;
+    let base = parse_body(syntax, lines, &mut index)?;
     log::trace!("adding variation base: {:?}", base);
-    let mut variants = vec![];
 
-    for pair in pairs {
-        if pair.as_rule() == Rule::variation_end {
+    let mut variants = vec![];
+    loop {
+        let Some(line) = lines.get(index) else {
+            // The file ended inside the variation, point at where it started.
+            return Err(syntax.error(header, start, &[Expected::VariationEnd]));
+        };
+        let text = strip_newline(line);
+        if syntax.is_variation_end(text) {
+            if variants.is_empty() {
+                return Err(syntax.error(text, index, &[Expected::VariantHeader]));
+            }
+            index += 1;
             break;
         }
-
-        assert!(pair.as_rule() == Rule::variant);
-
-        variants.push(parse_variant(pair));
-        log::trace!("adding variation variant: {:?}", variants.last().unwrap());
+        let Some(rest) = syntax.variant_header(text) else {
+            return Err(syntax.error(
+                text,
+                index,
+                &[Expected::VariantHeader, Expected::VariationEnd],
+            ));
+        };
+        let name = rest
+            .trim_end()
+            .strip_suffix(syntax.end.as_str())
+            .and_then(parse_name)
+            .ok_or_else(|| syntax.error(text, index, &[Expected::Name]))?;
+        index += 1;
+
+        let mut body = parse_body(syntax, lines, &mut index)?;
+        match &mut body {
+            VariantBody::Active { lines: body } if body.is_empty() => {
+                let text = lines.get(index).map_or("", |line| strip_newline(line));
+                return Err(syntax.error(text, index, &[Expected::VariantBody]));
+            }
+            VariantBody::InactiveMultiLine { indentation, .. }
+            | VariantBody::InactiveSingleLine { indentation, .. } => {
+                *indentation = indentation_of(text).to_string();
+            }
+            VariantBody::Active { .. } => {}
+        }
+        log::trace!("adding variation variant: {} {:?}", name, body);
+        variants.push(Variant {
+            name: name.to_string(),
+            body,
+        });
     }
 
-    // only one of the variants or the base can be active
-    let actives = variants.iter().filter(|v| v.is_active()).count();
-    let active = if let VariantBody::Active { .. } = base {
-        assert_eq!(
-            actives,
-            0,
-            "all active variants are {}",
-            variants
-                .iter()
-                .filter(|v| v.is_active())
-                .map(|v| v.name.as_str())
-                .collect::<Vec<_>>()
-                .join(", ")
-        );
-        0
-    } else {
-        assert_eq!(actives, 1);
-        variants.iter().position(|v| v.is_active()).unwrap() + 1
-    };
-
-    let mut lines = 0;
-    // Begin marker (*! *)
-    lines += 1;
-    // Base code
-    lines += base.count_lines();
-    // Variant codes
-    for variant in &variants {
-        // Begin marker (*!! *)
-        lines += 1;
-        // Variant code
-        lines += variant.body.count_lines();
-    }
-    // End marker (* !*)
-    lines += 1;
-    // Inline markers for the passive variants
-    lines += variants.len() * 2;
-
-    (
+    let active = variants
+        .iter()
+        .position(|v| v.is_active())
+        .map_or(0, |index| index + 1);
+
+    Ok((
         Variation {
             name,
             tags,
@@ -143,196 +296,113 @@ fn parse_variation(pair: pest::iterators::Pair<Rule>) -> (Variation, usize) {
             },
             variants,
             active,
-            indentation: variation_indentation,
+            indentation,
         },
-        lines,
-    )
+        index,
+    ))
 }
 
-fn parse_variation_header(
-    pair: pest::iterators::Pair<Rule>,
-) -> (Option<String>, Vec<String>, String) {
-    let mut pairs = pair.into_inner();
-
-    let (indentation, begin_marker) = next2(&mut pairs, Rule::indent).unwrap();
-    let indentation = indentation
-        .map(|pair| pair.as_str().to_string())
-        .unwrap_or_default();
-    assert_eq!(begin_marker.as_rule(), Rule::variation_begin_marker);
-
-    let maybe_name = pairs.peek().unwrap();
-    let name = match maybe_name.as_rule() {
-        Rule::identifier => {
-            // Move the iterator
-            let name = maybe_name.as_str().to_string();
-            pairs.next().unwrap();
-            Some(name)
-        }
-        Rule::comment_end | Rule::tags => None,
-        p => unreachable!("Unexpected rule {:?}", p),
-    };
-
-    let maybe_tags = pairs.peek().unwrap();
-    let tags: Vec<String> = match maybe_tags.as_rule() {
-        Rule::tags => {
-            let tags = maybe_tags
-                .into_inner()
-                .map(|pair| pair.as_str().to_string())
-                .collect();
-
-            // Move the iterator
-            pairs.next().unwrap();
-
-            tags
+/// Parses the body starting at `index`, leaving `index` on the line that follows it. A body
+/// is either commented out, on one line as in `(*! code *)` or on several lines between `(*!`
+/// and `*)`, or made of the active lines up to the next variant header or end marker.
+fn parse_body(
+    syntax: &Syntax,
+    lines: &[&str],
+    index: &mut usize,
+) -> Result<VariantBody, ParseDiagnostic> {
+    if let Some(text) = lines.get(*index).map(|line| strip_newline(line)) {
+        if let Some(rest) = syntax.body_marker(text) {
+            let indentation = indentation_of(text).to_string();
+            if rest.trim().is_empty() {
+                let start = *index;
+                let mut body = vec![];
+                loop {
+                    *index += 1;
+                    let Some(line) = lines.get(*index) else {
+                        return Err(syntax.error(text, start, &[Expected::CommentEnd]));
+                    };
+                    let line = strip_newline(line);
+                    if line.trim() == syntax.end {
+                        *index += 1;
+                        break;
+                    }
+                    body.push(line.to_string());
+                }
+                log::debug!("inactive multi-line variant body: {:?}", body);
+                return Ok(VariantBody::InactiveMultiLine {
+                    lines: body,
+                    indentation,
+                });
+            }
+            if let Some(line) = rest.trim_end().strip_suffix(syntax.end.as_str()) {
+                if !line.contains(syntax.end.as_str()) {
+                    *index += 1;
+                    return Ok(VariantBody::InactiveSingleLine {
+                        line: line.trim().to_string(),
+                        indentation,
+                    });
+                }
+            }
         }
-        Rule::comment_end => vec![],
-        p => unreachable!("Unexpected rule {:?}", p),
-    };
-
-    let end_marker = pairs.next().unwrap();
-
-    assert_eq!(end_marker.as_rule(), Rule::comment_end);
-
-    (name, tags, indentation)
-}
-
-fn parse_base(pair: pest::iterators::Pair<Rule>) -> VariantBody {
-    let mut pairs = pair.into_inner();
-    match pairs.next() {
-        Some(body) => parse_variant_body(body),
-        None => VariantBody::Active { lines: vec![] },
     }
-}
 
-fn parse_variant(pair: pest::iterators::Pair<Rule>) -> Variant {
-    let mut pairs = pair.into_inner();
-    let header = pairs.next().unwrap();
-    let (name, indent) = parse_variant_header(header);
-    log::trace!(
-        "adding variant header: (name={:?}, indentation={:?})",
-        name,
-        indent
-    );
-    let body = pairs.next().unwrap();
-
-    let mut body = parse_variant_body(body);
-    log::trace!("adding variant body: {:?}", body);
-    match &mut body {
-        VariantBody::InactiveMultiLine { indentation, .. }
-        | VariantBody::InactiveSingleLine { indentation, .. } => {
-            *indentation = indent.clone();
+    let mut body = vec![];
+    while let Some(line) = lines.get(*index) {
+        let text = strip_newline(line);
+        if syntax.variant_header(text).is_some() || syntax.is_variation_end(text) {
+            break;
         }
-        VariantBody::Active { .. } => {}
-    };
-
-    Variant { name, body }
+        body.push(text.to_string());
+        *index += 1;
+    }
+    Ok(VariantBody::Active { lines: body })
 }
 
-fn parse_variant_header(pair: pest::iterators::Pair<Rule>) -> (String, String) {
-    let mut pairs = pair.into_inner();
-
-    let (indentation, begin_marker) = next2(&mut pairs, Rule::indent).unwrap();
-
-    let indentation = indentation
-        .map(|pair| pair.as_str().to_string())
-        .unwrap_or_default();
-
-    assert_eq!(begin_marker.as_rule(), Rule::variant_begin_marker);
-
-    let name = pairs.next().unwrap();
-    let name = name.as_str().to_string();
-
-    let end_marker = pairs.next().unwrap();
-
-    assert_eq!(end_marker.as_rule(), Rule::comment_end);
-
-    assert_eq!(pairs.next(), None);
-    (name, indentation)
+/// The name and tags of a variation header, from the text between its markers, or `None` if
+/// the comment is not a header
+pub(crate) fn parse_title(title: &str) -> Option<(Option<String>, Vec<String>)> {
+    let title = title.trim();
+    let length = identifier_length(title);
+    let name = (length > 0).then(|| title[..length].to_string());
+    let rest = title[length..].trim_start();
+    if rest.is_empty() {
+        return Some((name, vec![]));
+    }
+    let tags = rest.strip_prefix('[')?.strip_suffix(']')?;
+    let tags: Vec<String> = tags.split(',').map(|tag| tag.trim().to_string()).collect();
+    tags.iter()
+        .all(|tag| !tag.is_empty() && identifier_length(tag) == tag.len())
+        .then_some((name, tags))
 }
 
-fn next2<'a>(
-    pairs: &'a mut pest::iterators::Pairs<Rule>,
-    rule: Rule,
-) -> Option<(Option<Pair<'a, Rule>>, Pair<'a, Rule>)> {
-    let maybe_r1 = pairs.peek();
-    if let Some(r1) = maybe_r1 {
-        pairs.next();
-        if r1.as_rule() == rule {
-            let r2 = pairs.next().unwrap();
-            Some((Some(r1), r2))
-        } else {
-            Some((None, r1))
-        }
-    } else {
-        None
+/// The name of a variant header, from the text between its markers
+pub(crate) fn parse_name(rest: &str) -> Option<&str> {
+    if !rest.starts_with([' ', '\t']) {
+        return None;
     }
+    let name = rest.trim();
+    (!name.is_empty() && identifier_length(name) == name.len()).then_some(name)
 }
 
-fn parse_variant_body(pair: pest::iterators::Pair<Rule>) -> VariantBody {
-    let mut body = pair.into_inner();
-    let body = body.next().unwrap();
-
-    match body.as_rule() {
-        Rule::inactive_multi_line_variant_body => {
-            let mut pairs = body.into_inner();
-
-            let (indentation, begin_marker) = next2(&mut pairs, Rule::indent).unwrap();
-            let indentation = indentation
-                .map(|pair| pair.as_str().to_string())
-                .unwrap_or_default();
-            assert_eq!(begin_marker.as_rule(), Rule::variant_body_begin_marker);
-
-            let body = pairs.next().unwrap();
-            assert_eq!(body.as_rule(), Rule::comment_text);
-
-            let body = body
-                .into_inner()
-                .map(|pair| pair.as_str().strip_suffix("\n").unwrap().to_string())
-                .collect();
-
-            let (_, end_marker) = next2(&mut pairs, Rule::indent).unwrap();
-            assert_eq!(end_marker.as_rule(), Rule::block_comment_end);
-
-            log::debug!("inactive multi-line variant body: {:?}", body);
-            log::debug!("inactive multi-line variant indentation: {:?}", indentation);
-
-            VariantBody::InactiveMultiLine {
-                lines: body,
-                indentation,
-            }
-        }
-        Rule::inactive_single_line_variant_body => {
-            let mut pairs = body.into_inner();
-
-            let (indentation, begin_marker) = next2(&mut pairs, Rule::indent).unwrap();
-            let indentation = indentation
-                .map(|pair| pair.as_str().to_string())
-                .unwrap_or_default();
-            assert_eq!(begin_marker.as_rule(), Rule::variant_body_begin_marker);
-
-            let body = pairs.next().unwrap();
-            assert_eq!(body.as_rule(), Rule::single_line_comment_text);
-
-            let body = body.as_str().trim().to_string();
-
-            let end_marker = pairs.next().unwrap();
-            assert_eq!(end_marker.as_rule(), Rule::comment_end);
+/// The length of the identifier `input` starts with, 0 if there is none
+fn identifier_length(input: &str) -> usize {
+    if !input.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return 0;
+    }
+    input
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(input.len())
+}
 
-            VariantBody::InactiveSingleLine {
-                line: body,
-                indentation,
-            }
-        }
-        Rule::active_variant_body => {
-            let body = body
-                .into_inner()
-                .map(|pair| pair.as_str().strip_suffix("\n").unwrap().to_string())
-                .collect();
+pub(crate) fn indentation_of(text: &str) -> &str {
+    &text[..text.len() - text.trim_start_matches([' ', '\t']).len()]
+}
 
-            VariantBody::Active { lines: body }
-        }
-        p => unreachable!("unexpected rule {:?}", p),
-    }
+/// A line of the input, without its line break
+pub(crate) fn strip_newline(line: &str) -> &str {
+    line.strip_suffix('\n')
+        .or_else(|| line.strip_suffix('\r'))
+        .unwrap_or(line)
 }
 
 #[cfg(test)]
@@ -340,37 +410,48 @@ mod tests {
     use std::{fs, path::PathBuf};
 
     use crate::code::{Code, SpanContent};
+    use crate::languages::CustomLanguage;
 
     use super::*;
 
+    /// Parses `input` as a body, returning it with the number of lines it spans
+    fn parse_body_of(language: &Language, input: &str) -> (VariantBody, usize) {
+        let lines: Vec<&str> = input.split_inclusive('\n').collect();
+        let mut index = 0;
+        let body = parse_body(&Syntax::new(language), &lines, &mut index).unwrap();
+        (body, index)
+    }
+
     #[test]
     fn test_parse_variation_begin_marker() {
-        let result = Parser::parse(Rule::variation_begin_marker, r#"(*!"#)
-            .unwrap()
-            .next()
-            .unwrap();
+        let syntax = Syntax::new(&Language::Rocq);
+        assert_eq!(syntax.marker("(*!"), Some(""));
+        assert_eq!(syntax.marker("  (*| x *)"), Some(" x *)"));
+        assert_eq!(syntax.marker("/*! x */"), None);
+    }
 
-        assert!(result.as_str() == "(*!");
+    #[test]
+    fn test_doc_comments_are_not_markers() {
+        let c = Syntax::new(&Language::C);
+        assert_eq!(c.marker("/*| insert */"), Some(" insert */"));
+        assert_eq!(c.marker("/*! Doxygen */"), None);
+        let lean = Syntax::new(&Language::Lean);
+        assert_eq!(lean.marker("  /-| insert -/"), Some(" insert -/"));
+        assert_eq!(lean.marker("/-! Module docs -/"), None);
     }
 
     #[test]
     fn test_parse_variation_header() {
-        let result = Parser::parse(Rule::variation_header, r#"(*! delete_4 *)"#)
-            .unwrap()
-            .next()
-            .unwrap();
-        let result = parse_variation_header(result);
+        let result = Syntax::new(&Language::Rocq).variation_header(r#"(*! delete_4 *)"#);
 
-        assert_eq!(result.0, Some("delete_4".to_string()));
+        assert_eq!(result, Some((Some("delete_4".to_string()), vec![])));
     }
 
     #[test]
     fn test_parse_variation_header_noid() {
-        let result = Parser::parse(Rule::variation_header, r#"(*! *)"#)
-            .unwrap()
-            .next()
+        let result = Syntax::new(&Language::Rocq)
+            .variation_header(r#"(*! *)"#)
             .unwrap();
-        let result = parse_variation_header(result);
 
         assert_eq!(result.0, None);
         assert_eq!(result.1, Vec::new() as Vec<String>);
@@ -378,41 +459,29 @@ mod tests {
 
     #[test]
     fn test_variation_end() {
-        let result = Parser::parse(Rule::variation_end, r#"(* !*)"#)
-            .unwrap()
-            .next()
-            .unwrap();
-
-        assert_eq!(result.as_str(), "(* !*)");
+        assert!(Syntax::new(&Language::Rocq).is_variation_end(r#"(* !*)"#));
     }
 
     #[test]
     fn test_variation_end_whitespace() {
-        let result = Parser::parse(Rule::variation_end, r#"(*! *)"#);
-        assert!(result.is_err());
+        assert!(!Syntax::new(&Language::Rocq).is_variation_end(r#"(*! *)"#));
     }
 
     #[test]
     fn test_variation_end_whitespace2() {
-        let result = Parser::parse(Rule::variation_end, r#"(* ! *)"#);
-        assert!(result.is_err());
+        assert!(!Syntax::new(&Language::Rocq).is_variation_end(r#"(* ! *)"#));
     }
 
     #[test]
     fn test_variation_base() {
-        let result = Parser::parse(
-            Rule::base,
+        let (result, _) = parse_body_of(
+            &Language::Rocq,
             r#"
 if k <? k' then T (delete k l) k' v' r
 else if k' <? k then T l k' v' (delete k r)
 else join l r
 "#,
-        )
-        .unwrap()
-        .next()
-        .unwrap();
-
-        let result = parse_base(result);
+        );
 
         assert_eq!(
             result.lines(),
@@ -429,52 +498,40 @@ else join l r
 
     #[test]
     fn test_variant_header() {
-        let result = Parser::parse(Rule::variant_header, r#"  (*!! delete_4 *)"#)
-            .unwrap()
-            .next()
-            .unwrap();
+        let header = r#"  (*!! delete_4 *)"#;
+        let rest = Syntax::new(&Language::Rocq).variant_header(header).unwrap();
+        let name = rest.trim_end().strip_suffix("*)").and_then(parse_name);
 
-        let (name, indent) = parse_variant_header(result);
-        assert_eq!(name, "delete_4");
-        assert_eq!(indent, "  ");
+        assert_eq!(name, Some("delete_4"));
+        assert_eq!(indentation_of(header), "  ");
     }
 
     #[test]
     fn test_variant_body_begin() {
-        let result = Parser::parse(Rule::variant_body_begin_marker, r#"(*!"#)
-            .unwrap()
-            .next()
-            .unwrap();
-
-        assert_eq!(result.as_str(), "(*!");
+        let syntax = Syntax::new(&Language::Rocq);
+        assert_eq!(syntax.body_marker("(*!"), Some(""));
+        assert_eq!(syntax.body_marker("(*!! delete_4 *)"), None);
     }
 
     #[test]
     fn test_variant_body_end() {
-        let result = Parser::parse(Rule::comment_end, r#"*)"#)
-            .unwrap()
-            .next()
-            .unwrap();
-
-        assert_eq!(result.as_str(), "*)");
+        assert_eq!(Syntax::new(&Language::Rocq).end, "*)");
+        assert_eq!(Syntax::new(&Language::Haskell).end, "-}");
     }
 
     #[test]
     fn test_variant_body() {
-        let mut result = Parser::parse(
-            Rule::variant_body,
+        let (result, lines) = parse_body_of(
+            &Language::Rocq,
             r#"(*!
 if k <? k' then delete k l
 else if k' <? k then delete k r
 else join l r
 *)
 "#,
-        )
-        .unwrap();
-
-        let result = result.next().unwrap();
+        );
 
-        let result = parse_variant_body(result);
+        assert_eq!(lines, 5);
         if let VariantBody::InactiveMultiLine { lines, .. } = result {
             assert_eq!(
                 lines,
@@ -491,21 +548,18 @@ else join l r
 
     #[test]
     fn test_variant2() {
-        let mut result = Parser::parse(
-            Rule::variant,
-            r#"(*!! delete_4 *)
+        let input = r#"(*! *)
+(*!! delete_4 *)
 (*!
 if k <? k' then delete k l
 else if k' <? k then delete k r
 else join l r
 *)
-"#,
-        )
-        .unwrap();
-
-        let result = result.next().unwrap();
-
-        let result = parse_variant(result);
+(* !*)
+"#;
+        let lines: Vec<&str> = input.split_inclusive('\n').collect();
+        let (variation, _) = parse_variation(&Syntax::new(&Language::Rocq), &lines, 0).unwrap();
+        let result = &variation.variants[0];
 
         assert_eq!(result.name, "delete_4");
         assert_eq!(
@@ -521,9 +575,7 @@ else join l r
 
     #[test]
     fn test_variation2() {
-        let result = Parser::parse(
-            Rule::variation,
-            r#"(*! *)
+        let input = r#"(*! *)
   if k <? k' then T (delete k l) k' v' r
   else if k' <? k then T l k' v' (delete k r)
   else join l r
@@ -539,13 +591,10 @@ else join l r
   else if k <? k' then T l k' v' (delete k r)
   else join l r
   *)
-  (* !*)"#,
-        )
-        .unwrap()
-        .next()
-        .unwrap();
+  (* !*)"#;
+        let lines: Vec<&str> = input.split_inclusive('\n').collect();
 
-        let (variation, line) = parse_variation(result);
+        let (variation, line) = parse_variation(&Syntax::new(&Language::Rocq), &lines, 0).unwrap();
 
         assert_eq!(
             variation.base.lines(),
@@ -588,34 +637,76 @@ else join l r
 
     #[test]
     fn test_tags() {
-        let input = r#"[new, easy]"#;
-        let result = Parser::parse(Rule::tags, input).unwrap().next().unwrap();
-        let tags = result
-            .into_inner()
-            .map(|pair| pair.as_str().to_string())
-            .collect::<Vec<String>>();
+        let (_, tags) = parse_title(" [new, easy] ").unwrap();
 
         assert_eq!(tags, vec!["new".to_string(), "easy".to_string()]);
+        assert_eq!(parse_title(" [new, two words] "), None);
     }
 
     #[test]
     fn test_variation_header() {
         let input = r#"    (*! insert [new, easy] *)"#;
-        let result = Parser::parse(Rule::variation_header, input)
-            .unwrap()
-            .next()
+        let (name, tags) = Syntax::new(&Language::Rocq)
+            .variation_header(input)
             .unwrap();
-        let (name, tags, indent) = parse_variation_header(result);
 
         assert_eq!(name, Some("insert".to_string()));
         assert_eq!(tags, vec!["new".to_string(), "easy".to_string()]);
-        assert_eq!(indent, "    ");
+        assert_eq!(indentation_of(input), "    ");
+    }
+
+    #[test]
+    fn test_custom_delimiters() {
+        let markdown = || {
+            Language::Custom(CustomLanguage {
+                name: "Markdown".to_string(),
+                extension: "md".to_string(),
+                patterns: vec![],
+                comment_begin: "<!--".to_string(),
+                comment_end: Some("-->".to_string()),
+                mutation_marker: "%".to_string(),
+            })
+        };
+        let input = r#"# Fact
+<!--% greeting [doc] -->
+Hello, world!
+<!--%% greeting_1 -->
+<!--% Goodbye, world! -->
+<!--%% greeting_2 -->
+<!--%
+Hello,
+world!
+-->
+<!-- %-->
+<!--! not_a_variation -->
+"#;
+        let spans = parse_code(input, &markdown()).unwrap();
+        assert_eq!(spans.len(), 3);
+        let SpanContent::Variation(variation) = &spans[1].content else {
+            panic!("unexpected span content {:?}", spans[1].content);
+        };
+        assert_eq!(variation.name, Some("greeting".to_string()));
+        assert_eq!(variation.tags, vec!["doc"]);
+        assert_eq!(variation.base.lines(), vec!["Hello, world!"]);
+        assert_eq!(variation.variants[0].lines(), vec!["Goodbye, world!"]);
+        assert_eq!(variation.variants[1].lines(), vec!["Hello,", "world!"]);
+
+        let rendered = Code::new(markdown(), spans, PathBuf::from("fact.md")).to_string();
+        let code = Code::new(
+            markdown(),
+            parse_code(&rendered, &markdown()).unwrap(),
+            PathBuf::from("fact.md"),
+        );
+        assert_eq!(code.to_string(), rendered);
+
+        // The delimiters of other languages are plain text.
+        let spans = parse_code(input, &Language::Rocq).unwrap();
+        assert_eq!(spans.len(), 1);
     }
 
     #[test]
     fn test_code() {
-        let result = Parser::parse(
-            Rule::code,
+        let result = parse_code(
             r#"Fixpoint delete (k: nat) (t: Tree) :=
   match t with
   | E => E
@@ -638,13 +729,10 @@ else join l r
   *)
   (* !*)
   end."#,
+            &Language::Rocq,
         )
-        .unwrap()
-        .next()
         .unwrap();
 
-        let result = parse_code(result.as_str()).unwrap();
-
         let s1 = &result[0];
         if let SpanContent::Line(c) = &s1.content {
             assert_eq!(
@@ -700,14 +788,14 @@ else join l r
     #[test]
     fn test_parse_code_roundtrip() {
         let code = fs::read_to_string("test/rocq/BST.v").unwrap();
-        let spans = parse_code(&code).unwrap();
+        let spans = parse_code(&code, &Language::Rocq).unwrap();
         let code = Code::new(
             crate::languages::Language::Rocq,
             spans.clone(),
             PathBuf::from("test/rocq/BST2.v"),
         );
         let code_as_str = code.to_string();
-        let spans2 = parse_code(&code_as_str).unwrap();
+        let spans2 = parse_code(&code_as_str, &Language::Rocq).unwrap();
 
         assert_eq!(spans.len(), spans2.len());
         for (span, span2) in spans.iter().zip(spans2.iter()) {
@@ -740,6 +828,7 @@ else join l r
         *)
         (* |*)
 end."#,
+            &Language::Rocq,
         )
         .unwrap();
 
@@ -804,6 +893,7 @@ end."#,
     */
     /* |*/
 }"#,
+            &Language::Rust,
         )
         .unwrap();
 
@@ -843,6 +933,7 @@ end."#,
     /* |*/
     x
 }"#,
+            &Language::Rust,
         )
         .unwrap();
 
@@ -877,6 +968,7 @@ end."#,
      */
     /* |*/
 }"#,
+            &Language::Rust,
         )
         .unwrap();
 
@@ -903,6 +995,7 @@ end."#,
 /*|
 */
 /* |*/"#,
+            &Language::Rust,
         )
         .unwrap();
 
@@ -939,6 +1032,7 @@ end."#,
         (* n (fact (- n 1))))))
 (fact 5)
 "#,
+            &Language::Racket,
         )
         .unwrap();
 
@@ -959,8 +1053,77 @@ end."#,
 
     #[test]
     fn test_single_line_file_without_trailing_newline() {
-        let result = parse_code("const DEFAULT_PRECISION: u64 = 100;").unwrap();
+        let result = parse_code("const DEFAULT_PRECISION: u64 = 100;", &Language::Rust).unwrap();
         assert_eq!(result.len(), 1);
         assert!(matches!(result[0].content, SpanContent::Line(_)));
     }
+
+    #[test]
+    fn test_several_active_variants() {
+        let input = r#"fn calc(a: i32, b: i32) -> i32 {
+    /*| add */
+    a + b
+    /*|| add_1 */
+    a - b
+    /*|| add_2 */
+    /*|
+    a * b
+    */
+    /* |*/
+}
+"#;
+        let err = parse_code(input, &Language::Rust).unwrap_err();
+        let MalformedVariations(malformed) = err.downcast_ref().unwrap();
+        assert_eq!(
+            malformed,
+            &vec![MalformedVariation {
+                path: Default::default(),
+                line: 2,
+                name: Some("add".to_string()),
+                active: vec!["base".to_string(), "add_1".to_string()],
+            }]
+        );
+
+        let (spans, repaired) = parse_code_repairing(input, &Language::Rust).unwrap();
+        assert_eq!(&repaired, malformed);
+        let SpanContent::Variation(variation) = &spans[1].content else {
+            panic!("unexpected span content {:?}", spans[1].content);
+        };
+        assert_eq!(variation.active, 0);
+        assert!(variation.base.is_active());
+        assert!(variation
+            .variants
+            .iter()
+            .all(|variant| !variant.is_active()));
+    }
+
+    #[test]
+    fn test_syntax_error_diagnostic() {
+        // The variant header is followed by the end of the variation instead of a body.
+        let input = "fn calc(a: i32, b: i32) -> i32 {
+    /*| add */
+    a + b
+    /*|| add_1 */
+\t/* |*/
+}
+";
+        let diagnostic = ParseDiagnostic {
+            path: PathBuf::from("calc.rs"),
+            ..parse_code_repairing(input, &Language::Rust).unwrap_err()
+        };
+        assert_eq!(
+            diagnostic,
+            ParseDiagnostic {
+                path: PathBuf::from("calc.rs"),
+                line: 5,
+                column: 2,
+                expected: vec!["variant body `/*|`".to_string()],
+                source_line: "\t/* |*/".to_string(),
+            }
+        );
+        assert_eq!(
+            diagnostic.to_string(),
+            "calc.rs:5:2: expected variant body `/*|`\n  |\n5 | \t/* |*/\n  | \t^"
+        );
+    }
 }
@@ -5,8 +5,8 @@ mod rust;
 
 #[cfg(feature = "syntax-rust-functional")]
 pub(crate) use core::{
-    functional_language_for_extension, looks_like_mutations, parse_variations,
-    render_comment_code_from_functional, render_functional_code,
+    functional_language_for_extension, looks_like_mutations, mutation_env_var, parse_variations,
+    render_comment_code_from_functional, render_functional_code, FunctionalLanguage,
 };
 
 #[cfg(feature = "syntax-rust-functional")]
@@ -29,6 +29,15 @@ pub(crate) fn looks_like_mutations(_language: FunctionalLanguage, _input: &str)
     false
 }
 
+#[cfg(not(feature = "syntax-rust-functional"))]
+pub(crate) fn mutation_env_var(
+    language: FunctionalLanguage,
+    _variation_name: Option<&str>,
+    _variant_name: &str,
+) -> String {
+    match language {}
+}
+
 #[cfg(not(feature = "syntax-rust-functional"))]
 pub(crate) fn parse_variations(_language: FunctionalLanguage, _input: &str) -> Vec<Span> {
     vec![]
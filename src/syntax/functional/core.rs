@@ -18,6 +18,19 @@ pub(crate) fn looks_like_mutations(language: FunctionalLanguage, input: &str) ->
     }
 }
 
+/// Name of the environment variable that activates `variant_name` when set to `active`.
+pub(crate) fn mutation_env_var(
+    language: FunctionalLanguage,
+    variation_name: Option<&str>,
+    variant_name: &str,
+) -> String {
+    match language {
+        FunctionalLanguage::Rust => {
+            super::rust::mutation_env_var_name(variation_name, variant_name)
+        }
+    }
+}
+
 pub(crate) fn parse_variations(language: FunctionalLanguage, input: &str) -> Vec<Span> {
     match language {
         FunctionalLanguage::Rust => super::rust::parse_rust_variations(input),
@@ -6,6 +6,7 @@ use syn::spanned::Spanned;
 use syn::visit::{self, Visit};
 
 use crate::code::Span;
+use crate::languages::Language;
 use crate::variation::{Variant, Variation};
 use crate::VariantBody;
 
@@ -441,7 +442,7 @@ pub(crate) fn render_rust_functional_code(input: &str, spans: &[Span]) -> anyhow
 
         rendered = apply_replacements(&rendered, selected)
             .ok_or_else(|| anyhow::anyhow!("failed to apply Rust functional replacements"))?;
-        current_spans = crate::syntax::comment::parse_code(&rendered)?;
+        current_spans = crate::syntax::comment::parse_code(&rendered, &Language::Rust)?;
     }
 }
 
@@ -604,7 +605,7 @@ fn apply_replacements(input: &str, replacements: Vec<TextReplacement>) -> Option
 
     let mut rendered = input.to_string();
     let mut replacements = replacements;
-    replacements.sort_by(|left, right| right.range.start.cmp(&left.range.start));
+    replacements.sort_by_key(|replacement| std::cmp::Reverse(replacement.range.start));
 
     for replacement in replacements {
         if replacement.range.end > rendered.len() || replacement.range.start > replacement.range.end
@@ -1850,7 +1851,7 @@ fn variant_activation_guard(variation_name: Option<&str>, variant_name: &str) ->
     format!(r#"matches!(std::env::var({env_var:?}).as_deref(), Ok("active"))"#)
 }
 
-fn mutation_env_var_name(variation_name: Option<&str>, variant_name: &str) -> String {
+pub(crate) fn mutation_env_var_name(variation_name: Option<&str>, variant_name: &str) -> String {
     if let Some(name) = variation_name {
         let expected_prefix = format!("{name}_");
         if variant_name.starts_with(&expected_prefix) {
@@ -2008,17 +2009,13 @@ fn collect_variants_from_pat(pat: &syn::Pat, out: &mut Vec<String>) {
                 collect_variants_from_pat(case, out);
             }
         }
-        syn::Pat::TupleStruct(tuple_struct) => {
-            if tuple_struct.path.is_ident("Ok") {
-                if let Some(syn::Pat::Lit(syn::ExprLit {
-                    lit: syn::Lit::Str(name),
-                    ..
-                })) = tuple_struct.elems.first()
-                {
-                    {
-                        out.push(name.value());
-                    }
-                }
+        syn::Pat::TupleStruct(tuple_struct) if tuple_struct.path.is_ident("Ok") => {
+            if let Some(syn::Pat::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(name),
+                ..
+            })) = tuple_struct.elems.first()
+            {
+                out.push(name.value());
             }
         }
         syn::Pat::Paren(inner) => collect_variants_from_pat(&inner.pat, out),
@@ -2327,7 +2324,7 @@ fn union(k: i32, k2: i32) -> i32 {
 }
 "#;
 
-        let spans = crate::syntax::comment::parse_code(source).unwrap();
+        let spans = crate::syntax::comment::parse_code(source, &Language::Rust).unwrap();
         let converted = render_rust_functional_code(source, &spans).unwrap();
 
         assert!(converted.contains("match () {"));
@@ -2368,12 +2365,13 @@ fn calc(a: i32, b: i32) -> i32 {
 }
 "#;
 
-        let spans = crate::syntax::comment::parse_code(source).unwrap();
+        let spans = crate::syntax::comment::parse_code(source, &Language::Rust).unwrap();
         let functional = render_rust_functional_code(source, &spans).unwrap();
         assert!(functional.contains("/* marauders:variation=add;tags=arith,core */"));
 
         let roundtrip = render_rust_comment_code_from_functional(&functional).unwrap();
-        let roundtrip_spans = crate::syntax::comment::parse_code(&roundtrip).unwrap();
+        let roundtrip_spans =
+            crate::syntax::comment::parse_code(&roundtrip, &Language::Rust).unwrap();
         let variation = roundtrip_spans
             .iter()
             .find_map(|span| match &span.content {
@@ -2406,7 +2404,7 @@ fn union_(l: i32, r: i32) -> i32 {
 }
 "#;
 
-        let spans = crate::syntax::comment::parse_code(source).unwrap();
+        let spans = crate::syntax::comment::parse_code(source, &Language::Rust).unwrap();
         let converted = render_rust_functional_code(source, &spans).unwrap();
 
         assert!(!converted.contains(r#"match std::env::var("M_union").as_deref() {"#));
@@ -2439,7 +2437,7 @@ fn insert(k: i32, k2: i32) -> i32 {
         let converted = render_rust_comment_code_from_functional(functional).unwrap();
         assert!(converted.contains("/*| insert */"));
         assert!(converted.contains("/*|| insert_1 */"));
-        let spans = crate::syntax::comment::parse_code(&converted).unwrap();
+        let spans = crate::syntax::comment::parse_code(&converted, &Language::Rust).unwrap();
         let names = spans
             .iter()
             .filter_map(|span| match &span.content {
@@ -2470,7 +2468,7 @@ fn union_(l: i32, r: i32) -> i32 {
         let converted = render_rust_comment_code_from_functional(functional).unwrap();
         assert!(converted.contains("/*| union */"));
         assert!(converted.contains("/*|| union_6 */"));
-        let spans = crate::syntax::comment::parse_code(&converted).unwrap();
+        let spans = crate::syntax::comment::parse_code(&converted, &Language::Rust).unwrap();
         let names = spans
             .iter()
             .filter_map(|span| match &span.content {
@@ -2544,7 +2542,7 @@ fn boundary(flag: bool, x: i32, y: i32, t: Option<i32>) -> i32 {
 }
 "#;
 
-        let original_spans = crate::syntax::comment::parse_code(source).unwrap();
+        let original_spans = crate::syntax::comment::parse_code(source, &Language::Rust).unwrap();
         let expected = variation_signatures(&original_spans);
         assert_eq!(
             expected,
@@ -2561,7 +2559,8 @@ fn boundary(flag: bool, x: i32, y: i32, t: Option<i32>) -> i32 {
             .contains(r#"matches!(std::env::var("M_arm_expr_1").as_deref(), Ok("active"))"#));
 
         let roundtrip_comment = render_rust_comment_code_from_functional(&functional).unwrap();
-        let roundtrip_spans = crate::syntax::comment::parse_code(&roundtrip_comment).unwrap();
+        let roundtrip_spans =
+            crate::syntax::comment::parse_code(&roundtrip_comment, &Language::Rust).unwrap();
         assert_eq!(variation_signatures(&roundtrip_spans), expected);
 
         let roundtrip_functional =
@@ -2602,7 +2601,7 @@ fn boundary(flag: bool, x: i32, y: i32, t: Option<i32>) -> i32 {
 */
 /* |*/
 "#;
-        let spans = crate::syntax::comment::parse_code(source).unwrap();
+        let spans = crate::syntax::comment::parse_code(source, &Language::Rust).unwrap();
         let converted = render_rust_functional_code(source, &spans).unwrap();
         assert_eq!(converted, source);
     }
@@ -2648,7 +2647,7 @@ fn mixed(l: i32, r: i32, flag: bool) -> i32 {
         assert!(converted.contains("/*|| guard_1 */"));
         assert!(converted.contains("(l, r) if l < r => {"));
 
-        let spans = crate::syntax::comment::parse_code(&converted).unwrap();
+        let spans = crate::syntax::comment::parse_code(&converted, &Language::Rust).unwrap();
         let expected = vec![
             ("expr".to_string(), vec!["expr_1".to_string()]),
             ("guard".to_string(), vec!["guard_1".to_string()]),
@@ -2683,7 +2682,7 @@ fn inline_match(t: Option<i32>) -> i32 {
         assert!(converted.contains("/*|| arm_1 */"));
         assert!(syn::parse_file(&converted).is_ok());
 
-        let spans = crate::syntax::comment::parse_code(&converted).unwrap();
+        let spans = crate::syntax::comment::parse_code(&converted, &Language::Rust).unwrap();
         let expected = vec![("arm".to_string(), vec!["arm_1".to_string()])];
         assert_eq!(variation_signatures(&spans), expected);
 
@@ -2710,7 +2709,7 @@ fn frag_call(a: i32, b: i32, c: i32) -> i32 {
 }
 "#;
 
-        let original_spans = crate::syntax::comment::parse_code(source).unwrap();
+        let original_spans = crate::syntax::comment::parse_code(source, &Language::Rust).unwrap();
         let expected = vec![("call".to_string(), vec!["wrong_call".to_string()])];
         assert_eq!(variation_signatures(&original_spans), expected);
 
@@ -2721,7 +2720,8 @@ fn frag_call(a: i32, b: i32, c: i32) -> i32 {
             .contains(r#"matches!(std::env::var("M_call__wrong_call").as_deref(), Ok("active"))"#));
 
         let roundtrip_comment = render_rust_comment_code_from_functional(&functional).unwrap();
-        let roundtrip_spans = crate::syntax::comment::parse_code(&roundtrip_comment).unwrap();
+        let roundtrip_spans =
+            crate::syntax::comment::parse_code(&roundtrip_comment, &Language::Rust).unwrap();
         assert_eq!(variation_signatures(&roundtrip_spans), expected);
     }
 
@@ -2740,7 +2740,7 @@ fn frag_call_paren(a: i32, b: i32, c: i32) -> i32 {
 }
 "#;
 
-        let spans = crate::syntax::comment::parse_code(source).unwrap();
+        let spans = crate::syntax::comment::parse_code(source, &Language::Rust).unwrap();
         let functional = render_rust_functional_code(source, &spans).unwrap();
 
         assert_eq!(functional.matches("\n            f(").count(), 2);
@@ -2778,7 +2778,7 @@ fn calc(a: i32, b: i32) -> i32 {
         assert!(imported.contains("/*|| tool_1_1 */"));
         assert!(imported.contains("/*|| tool_1_2 */"));
 
-        let spans = crate::syntax::comment::parse_code(&imported).unwrap();
+        let spans = crate::syntax::comment::parse_code(&imported, &Language::Rust).unwrap();
         assert_eq!(
             variation_signatures(&spans),
             vec![(
@@ -2790,7 +2790,8 @@ fn calc(a: i32, b: i32) -> i32 {
         let functional = render_rust_functional_code(&imported, &spans).unwrap();
         assert!(syn::parse_file(&functional).is_ok());
         let roundtrip_comment = render_rust_comment_code_from_functional(&functional).unwrap();
-        let roundtrip_spans = crate::syntax::comment::parse_code(&roundtrip_comment).unwrap();
+        let roundtrip_spans =
+            crate::syntax::comment::parse_code(&roundtrip_comment, &Language::Rust).unwrap();
         assert_eq!(
             variation_signatures(&roundtrip_spans),
             variation_signatures(&spans)
@@ -2819,7 +2820,7 @@ fn cmp(a: i32, b: i32) -> bool {
 "#;
 
         let imported = import_rust_mutants_from_files(base, &[mutant.to_string()], "ext").unwrap();
-        let spans = crate::syntax::comment::parse_code(&imported).unwrap();
+        let spans = crate::syntax::comment::parse_code(&imported, &Language::Rust).unwrap();
         assert_eq!(
             variation_signatures(&spans),
             vec![
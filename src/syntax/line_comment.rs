@@ -0,0 +1,406 @@
+//! The comment syntax for languages that only have line comments, such as shell scripts, Lua
+//! or Erlang. Variations use the same markers as in block comments, without a closing
+//! delimiter, and every line of an inactive variant is commented out on its own:
+//!
+//! ```text
+//! #! add [arith]
+//! echo $((a + b))
+//! #!! add_1
+//! #! echo $((a - b))
+//! # !
+//! ```
+
+use crate::code::{MalformedVariation, ParseDiagnostic, Span, SpanContent};
+use crate::languages::Language;
+use crate::syntax::comment::{
+    check_active, indentation_of, parse_name, parse_title, strip_newline,
+};
+use crate::variation::{Variant, VariantBody, Variation};
+
+/// A line of the input, classified by the marker it starts with
+enum Marker<'a> {
+    /// `# !`, the end of a variation
+    End,
+    /// `#!!`, followed by the name of a variant
+    VariantHeader(&'a str),
+    /// `#!`, followed by the title of a variation or by an inactive line
+    Commented(&'a str),
+    /// Any other line
+    Code,
+}
+
+/// A line of a variant body, as written in the file
+struct BodyLine {
+    text: String,
+    /// The indentation of the marker and the uncommented line, if the line is commented out
+    inactive: Option<(String, String)>,
+}
+
+/// Parses `input`, written in the line comment syntax of `language`, resetting the variations
+/// that do not have exactly one active variant to their base. Neither the returned variations
+/// nor the diagnostic have a path.
+pub(crate) fn parse_code_repairing(
+    input: &str,
+    language: &Language,
+) -> Result<(Vec<Span>, Vec<MalformedVariation>), ParseDiagnostic> {
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+    let mut spans: Vec<Span> = vec![];
+    let mut malformed = vec![];
+
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        let text = strip_newline(line);
+        match classify(text, language) {
+            Marker::Commented(title) if parse_title(title).is_some() => {
+                let (mut variation, next) = parse_variation(&lines, index, language)?;
+                if let Some(problem) = check_active(&variation, index + 1) {
+                    log::warn!("{}, resetting it to base", problem);
+                    variation.activate_base();
+                    malformed.push(problem);
+                }
+                log::trace!("adding variation: {:?}", variation);
+                spans.push(Span::variation(variation, index + 1));
+                index = next;
+            }
+            Marker::End | Marker::VariantHeader(_) => {
+                return Err(error(
+                    text,
+                    index,
+                    format!("variation header `{}`", language.variation_begin("name")),
+                ));
+            }
+            Marker::Commented(_) | Marker::Code => {
+                match spans.last_mut() {
+                    Some(Span {
+                        content: SpanContent::Line(c),
+                        ..
+                    }) => c.push_str(line),
+                    _ => spans.push(Span::constant(line.to_string(), index + 1)),
+                }
+                index += 1;
+            }
+        }
+    }
+
+    Ok((spans, malformed))
+}
+
+/// Parses the variation whose header is at `start`, returning it with the index of the line
+/// following its end marker
+fn parse_variation(
+    lines: &[&str],
+    start: usize,
+    language: &Language,
+) -> Result<(Variation, usize), ParseDiagnostic> {
+    let header = strip_newline(lines[start]);
+    let indentation = indentation_of(header).to_string();
+    let (name, tags) = match classify(header, language) {
+        Marker::Commented(title) => parse_title(title),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        error(
+            header,
+            start,
+            format!("variation header `{}`", language.variation_begin("name")),
+        )
+    })?;
+
+    let mut base = vec![];
+    let mut variants: Vec<(String, Vec<BodyLine>)> = vec![];
+    let mut index = start + 1;
+    loop {
+        let Some(line) = lines.get(index) else {
+            // The file ended inside the variation, point at where it started.
+            return Err(error(
+                header,
+                start,
+                format!("variation end marker `{}`", language.variation_end()),
+            ));
+        };
+        let text = strip_newline(line);
+        match classify(text, language) {
+            Marker::End if variants.is_empty() => {
+                return Err(error(
+                    text,
+                    index,
+                    format!("variant header `{} name`", language.variant_header_begin()),
+                ));
+            }
+            Marker::End => break,
+            Marker::VariantHeader(rest) => {
+                let name =
+                    parse_name(rest).ok_or_else(|| error(text, index, "a name".to_string()))?;
+                variants.push((name.to_string(), vec![]));
+            }
+            marker => {
+                let inactive = match marker {
+                    Marker::Commented(tail) => Some((
+                        indentation_of(text).to_string(),
+                        uncomment(indentation_of(text), tail),
+                    )),
+                    _ => None,
+                };
+                let body = match variants.last_mut() {
+                    Some((_, body)) => body,
+                    None => &mut base,
+                };
+                body.push(BodyLine {
+                    text: text.to_string(),
+                    inactive,
+                });
+            }
+        }
+        index += 1;
+    }
+
+    let variants: Vec<Variant> = variants
+        .into_iter()
+        .map(|(name, body)| Variant {
+            name,
+            body: parse_body(body),
+        })
+        .collect();
+    let active = variants
+        .iter()
+        .position(|v| v.is_active())
+        .map_or(0, |index| index + 1);
+
+    Ok((
+        Variation {
+            name,
+            tags,
+            base: Variant {
+                name: "base".to_string(),
+                body: parse_body(base),
+            },
+            variants,
+            active,
+            indentation,
+        },
+        index + 1,
+    ))
+}
+
+/// A body is inactive if all of its lines are commented out. A single marker without code
+/// stands for an inactive body without lines.
+fn parse_body(lines: Vec<BodyLine>) -> VariantBody {
+    if lines.is_empty() || lines.iter().any(|line| line.inactive.is_none()) {
+        return VariantBody::Active {
+            lines: lines.into_iter().map(|line| line.text).collect(),
+        };
+    }
+    let mut lines: Vec<(String, String)> = lines.into_iter().filter_map(|l| l.inactive).collect();
+    let indentation = lines[0].0.clone();
+    if lines.len() == 1 && lines[0].1.is_empty() {
+        lines.clear();
+    }
+    VariantBody::InactiveMultiLine {
+        lines: lines.into_iter().map(|(_, line)| line).collect(),
+        indentation,
+    }
+}
+
+/// Renders `variation` in the line comment syntax of `language`, one entry per line
+pub(crate) fn render_variation(language: &Language, variation: &Variation) -> Vec<String> {
+    let indentation = &variation.indentation;
+    let mut title = variation.name.clone().unwrap_or_default();
+    if !variation.tags.is_empty() {
+        title.push_str(&format!(" [{}]", variation.tags.join(", ")));
+    }
+
+    let mut lines = vec![format!(
+        "{}{}",
+        indentation,
+        language.variation_begin(title.trim_start())
+    )];
+    render_body(language, indentation, &variation.base.body, &mut lines);
+    for variant in &variation.variants {
+        lines.push(format!(
+            "{}{} {}",
+            indentation,
+            language.variant_header_begin(),
+            variant.name
+        ));
+        render_body(language, indentation, &variant.body, &mut lines);
+    }
+    lines.push(format!("{}{}", indentation, language.variation_end()));
+    lines
+}
+
+/// Renders a body, commenting out each of its lines at `indentation` if it is inactive
+fn render_body(language: &Language, indentation: &str, body: &VariantBody, out: &mut Vec<String>) {
+    let marker = language.variant_body_begin();
+    let lines: Vec<String> = match body {
+        VariantBody::Active { lines } => {
+            out.extend(
+                lines
+                    .iter()
+                    .flat_map(|line| line.split('\n'))
+                    .map(String::from),
+            );
+            return;
+        }
+        VariantBody::InactiveSingleLine { line, .. } => vec![format!("{}{}", indentation, line)],
+        VariantBody::InactiveMultiLine { lines, .. } => lines
+            .iter()
+            .flat_map(|line| line.split('\n'))
+            .map(String::from)
+            .collect(),
+    };
+    if lines.is_empty() {
+        out.push(format!("{}{}", indentation, marker));
+    }
+    for line in lines {
+        let rest = line.strip_prefix(indentation).unwrap_or(&line);
+        if rest.trim().is_empty() {
+            out.push(format!("{}{}", indentation, marker));
+        } else if rest.len() == line.len() && !indentation.is_empty() {
+            // The line is less indented than the variation, keep its own indentation.
+            let own = indentation_of(&line);
+            out.push(format!("{}{} {}", own, marker, &line[own.len()..]));
+        } else {
+            out.push(format!("{}{} {}", indentation, marker, rest));
+        }
+    }
+}
+
+fn classify<'a>(text: &'a str, language: &Language) -> Marker<'a> {
+    let marker = language.mutation_marker();
+    let comment = language.comment_begin();
+    let Some(after) = text[indentation_of(text).len()..].strip_prefix(comment.as_str()) else {
+        return Marker::Code;
+    };
+    if let Some(end) = after.strip_prefix([' ', '\t']) {
+        if end.trim_end() == marker {
+            return Marker::End;
+        }
+    }
+    let Some(tail) = after.strip_prefix(marker) else {
+        return Marker::Code;
+    };
+    if let Some(rest) = tail.strip_prefix(marker) {
+        return Marker::VariantHeader(rest);
+    }
+    if tail.is_empty() || tail.starts_with([' ', '\t']) {
+        return Marker::Commented(tail);
+    }
+    Marker::Code
+}
+
+/// The uncommented form of an inactive line, the marker and the space following it removed
+fn uncomment(indentation: &str, tail: &str) -> String {
+    if tail.trim().is_empty() {
+        return String::new();
+    }
+    let tail = tail.strip_prefix([' ', '\t']).unwrap_or(tail);
+    format!("{}{}", indentation, tail)
+}
+
+/// A diagnostic pointing at the marker of the line at `index`
+fn error(text: &str, index: usize, expected: String) -> ParseDiagnostic {
+    ParseDiagnostic {
+        path: Default::default(),
+        line: index + 1,
+        column: indentation_of(text).chars().count() + 1,
+        expected: vec![expected],
+        source_line: text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::code::Code;
+    use crate::languages::CustomLanguage;
+
+    use super::*;
+
+    fn shell() -> Language {
+        Language::Custom(CustomLanguage {
+            name: "shell".to_string(),
+            extension: "sh".to_string(),
+            patterns: vec![],
+            comment_begin: "#".to_string(),
+            comment_end: None,
+            mutation_marker: "!".to_string(),
+        })
+    }
+
+    #[test]
+    fn test_line_comment_roundtrip() {
+        let input = r#"#!/bin/sh
+# Adds its arguments
+add() {
+    #! add [arith, core]
+    echo $(($1 + $2))
+    #!! add_1
+    #! echo $(($1 - $2))
+    #!! add_2
+    #! if [ "$1" -gt 0 ]; then
+    #!     echo $(($1 * $2))
+    #!
+    #! fi
+    #!! add_3
+    #!
+    # !
+}
+"#;
+        let (spans, malformed) = parse_code_repairing(input, &shell()).unwrap();
+        assert!(malformed.is_empty());
+        assert_eq!(spans.len(), 3);
+        assert_eq!(spans[1].line, 4);
+        let SpanContent::Variation(variation) = &spans[1].content else {
+            panic!("unexpected span content {:?}", spans[1].content);
+        };
+        assert_eq!(variation.name, Some("add".to_string()));
+        assert_eq!(variation.tags, vec!["arith", "core"]);
+        assert_eq!(variation.active, 0);
+        assert_eq!(
+            variation.variants[1].lines(),
+            vec![
+                "    if [ \"$1\" -gt 0 ]; then",
+                "        echo $(($1 * $2))",
+                "",
+                "    fi",
+            ]
+        );
+        assert!(variation.variants[2].lines().is_empty());
+        assert!(!variation.variants[2].is_active());
+
+        let mut code = Code::new(shell(), spans.clone(), PathBuf::from("add.sh"));
+        assert_eq!(code.to_string(), input);
+
+        let SpanContent::Variation(variation) = &mut code.spans[1].content else {
+            unreachable!()
+        };
+        variation.activate_variant(2);
+        let activated = code.to_string();
+        assert!(activated.contains(
+            "    #! echo $(($1 + $2))\n    #!! add_1\n    #! echo $(($1 - $2))\n    #!! add_2\n    if [ \"$1\" -gt 0 ]; then\n        echo $(($1 * $2))\n\n    fi\n"
+        ));
+        let (spans, _) = parse_code_repairing(&activated, &shell()).unwrap();
+        let SpanContent::Variation(variation) = &spans[1].content else {
+            panic!("unexpected span content {:?}", spans[1].content);
+        };
+        assert_eq!(variation.active, 2);
+
+        code.spans[1].content = spans[1].content.clone();
+        let SpanContent::Variation(variation) = &mut code.spans[1].content else {
+            unreachable!()
+        };
+        variation.activate_base();
+        assert_eq!(code.to_string(), input);
+    }
+
+    #[test]
+    fn test_line_comment_unterminated_variation() {
+        let input = "echo start\n#! add\necho $((1 + 2))\n#!! add_1\n#! echo $((1 - 2))\n";
+        let diagnostic = parse_code_repairing(input, &shell()).unwrap_err();
+        assert_eq!(diagnostic.line, 2);
+        assert_eq!(diagnostic.column, 1);
+        assert_eq!(diagnostic.message(), "expected variation end marker `# !`");
+    }
+}
@@ -47,6 +47,19 @@ pub(crate) fn list_variant_names(input: &str) -> anyhow::Result<Vec<String>> {
         .collect())
 }
 
+/// Paths of the source files the variations of a match-replace document apply to
+pub(crate) fn source_paths(input: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let document = parse_document(input)?;
+    let mut paths: Vec<PathBuf> = vec![];
+    for variation in &document.variations {
+        let path = PathBuf::from(parse_scope_components(&variation.scope)?.0);
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
 pub(crate) fn set_variant_in_match_replace(
     input: &str,
     variant_name: &str,
@@ -287,7 +300,7 @@ fn apply_variant_in_match_replace(
         if trailing_newline {
             output.push('\n');
         }
-        std::fs::write(&source_path, output)?;
+        crate::atomic::write(&source_path, output)?;
     }
 
     Ok(MatchReplaceApplyResult {
@@ -570,7 +583,7 @@ fn calc(a: i32, b: i32) -> i32 {
 }
 "#;
 
-        let spans = crate::syntax::comment::parse_code(comment).unwrap();
+        let spans = crate::syntax::comment::parse_code(comment, &Language::Rust).unwrap();
         let pid = std::process::id();
         let nanos = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -627,7 +640,7 @@ fn calc(a: i32, b: i32) -> i32 {
 }
 "#;
 
-        let spans = crate::syntax::comment::parse_code(comment).unwrap();
+        let spans = crate::syntax::comment::parse_code(comment, &Language::Rust).unwrap();
         let pid = std::process::id();
         let nanos = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
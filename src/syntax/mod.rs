@@ -1,5 +1,6 @@
 pub(crate) mod comment;
 pub(crate) mod functional;
+pub(crate) mod line_comment;
 pub(crate) mod match_replace;
 pub(crate) mod patch;
 pub(crate) mod preprocessor;
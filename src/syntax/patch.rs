@@ -68,27 +68,28 @@ pub(crate) fn patch_bundle_dir_for_source(source_path: &Path) -> anyhow::Result<
     Ok(source_path.with_file_name(format!("{file_name}.patches")))
 }
 
+pub(crate) fn manifest_path(bundle_dir: &Path) -> PathBuf {
+    bundle_dir.join(MANIFEST_FILE)
+}
+
 pub(crate) fn write_patch_bundle(
     bundle_dir: &Path,
     rendered: &PatchBundleRender,
 ) -> anyhow::Result<PathBuf> {
-    if bundle_dir.exists() {
-        std::fs::remove_dir_all(bundle_dir)?;
-    }
-    std::fs::create_dir_all(bundle_dir)?;
-
-    let manifest_path = bundle_dir.join(MANIFEST_FILE);
-    std::fs::write(&manifest_path, &rendered.manifest)?;
+    crate::atomic::replace_dir(bundle_dir, |staged| {
+        std::fs::write(staged.join(MANIFEST_FILE), &rendered.manifest)?;
 
-    for file in &rendered.files {
-        let full_path = bundle_dir.join(&file.relative_path);
-        if let Some(parent) = full_path.parent() {
-            std::fs::create_dir_all(parent)?;
+        for file in &rendered.files {
+            let full_path = staged.join(&file.relative_path);
+            if let Some(parent) = full_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(full_path, &file.content)?;
         }
-        std::fs::write(full_path, &file.content)?;
-    }
+        Ok(())
+    })?;
 
-    Ok(manifest_path)
+    Ok(manifest_path(bundle_dir))
 }
 
 pub(crate) fn render_patch_bundle_from_comment(
@@ -272,7 +273,7 @@ fn load_resolved_variations(
             continue;
         }
 
-        patch_blocks.sort_by(|l, r| l.0.cmp(&r.0));
+        patch_blocks.sort_by_key(|l| l.0);
         let first_start = patch_blocks[0].2.old_start;
         let first_count = patch_blocks[0].2.old_count;
         let mut variants = Vec::new();
@@ -304,7 +305,7 @@ fn load_resolved_variations(
         ));
     }
 
-    variations.sort_by(|l, r| l.0.cmp(&r.0));
+    variations.sort_by_key(|l| l.0);
     let variations = variations
         .into_iter()
         .map(|(_order, variation)| variation)
@@ -656,7 +657,7 @@ fn calc(a: i32, b: i32) -> i32 {
 }
 "#;
 
-        let spans = crate::syntax::comment::parse_code(comment).unwrap();
+        let spans = crate::syntax::comment::parse_code(comment, &Language::Rust).unwrap();
         let pid = std::process::id();
         let nanos = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
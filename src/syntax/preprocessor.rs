@@ -370,7 +370,7 @@ fn calc(a: i32, b: i32) -> i32 {
 }
 "#;
 
-        let spans = crate::syntax::comment::parse_code(comment).unwrap();
+        let spans = crate::syntax::comment::parse_code(comment, &Language::Rust).unwrap();
         let preprocessor = render_preprocessor_code_from_comment(&spans).unwrap();
         assert!(preprocessor.contains("#if defined(M_add_1)"));
         assert!(preprocessor.contains("#elif defined(M_add_2)"));
@@ -380,7 +380,8 @@ fn calc(a: i32, b: i32) -> i32 {
 
         let roundtrip =
             render_comment_code_from_preprocessor(Language::Rust, &preprocessor).unwrap();
-        let roundtrip_spans = crate::syntax::comment::parse_code(&roundtrip).unwrap();
+        let roundtrip_spans =
+            crate::syntax::comment::parse_code(&roundtrip, &Language::Rust).unwrap();
 
         assert_eq!(spans.len(), roundtrip_spans.len());
         let variation = match &roundtrip_spans[1].content {
@@ -100,15 +100,6 @@ impl VariantBody {
         }
     }
 
-    pub(crate) fn count_lines(&self) -> usize {
-        match self {
-            VariantBody::InactiveMultiLine { lines, .. } | VariantBody::Active { lines } => {
-                lines.len()
-            }
-            VariantBody::InactiveSingleLine { .. } => 1,
-        }
-    }
-
     pub(crate) fn indentation(&self) -> Option<String> {
         match self {
             VariantBody::InactiveMultiLine { indentation, .. } => Some(indentation.clone()),
@@ -0,0 +1,244 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Output,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use ignore::WalkBuilder;
+
+use crate::{campaign::STATE_DIR, Project, ProjectConfig};
+
+/// Directories that are never copied into a workspace.
+const SKIPPED_DIRS: &[&str] = &[".git", STATE_DIR];
+
+static WORKSPACE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// An isolated copy of a project root, used to run a single mutant without
+/// touching the user's working tree.
+///
+/// The copy lives under the system temporary directory and is removed when the
+/// workspace is dropped.
+#[derive(Debug)]
+pub struct Workspace {
+    root: PathBuf,
+    /// Config of the copied project, reloaded as is in the copy
+    config: Option<ProjectConfig>,
+    /// Pattern of the copied project when it has no config
+    pattern: Option<String>,
+}
+
+impl Workspace {
+    /// Copies the root of `project` into a fresh temporary directory.
+    ///
+    /// Files excluded by `.gitignore` are skipped when the project uses gitignore rules,
+    /// and neither `.git` nor the marauders state directory are ever copied.
+    pub fn new(project: &Project) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            project.root.is_dir(),
+            "copy mode requires a project directory, '{}' is not a directory",
+            project.root.to_string_lossy()
+        );
+
+        let pid = std::process::id();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let counter = WORKSPACE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let root =
+            std::env::temp_dir().join(format!("marauders_workspace_{pid}_{nanos}_{counter}"));
+        std::fs::create_dir_all(&root)?;
+
+        // Construct the workspace before copying so a failed copy is cleaned up on drop.
+        let workspace = Workspace {
+            root,
+            config: project.config.clone(),
+            pattern: project.pattern.clone(),
+        };
+        let use_gitignore = project.config.as_ref().is_none_or(|c| c.use_gitignore);
+        copy_tree(&project.root, &workspace.root, use_gitignore)?;
+
+        log::debug!(
+            "created workspace '{}' for '{}'",
+            workspace.root.to_string_lossy(),
+            project.root.to_string_lossy()
+        );
+
+        Ok(workspace)
+    }
+
+    /// Root directory of the copy
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Loads the copied project with the config or pattern of the original
+    pub fn project(&self) -> anyhow::Result<Project> {
+        match &self.config {
+            Some(config) => Project::with_config(&self.root, config.clone()),
+            None => Project::with_pattern(&self.root, self.pattern.as_deref()),
+        }
+    }
+
+    /// Activates `variants` in the copy and runs `command` at its root
+    pub fn run(&self, variants: &Vec<String>, command: &str) -> anyhow::Result<Output> {
+        let mut project = self.project()?;
+        project.set_many(variants)?;
+        project.run(command)
+    }
+
+    /// Activates `variants` in the copy and runs `command` at its root under a time limit,
+    /// see [`Project::run_with_timeout`]
+    pub fn run_with_timeout(
+        &self,
+        variants: &Vec<String>,
+        command: &str,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Option<Output>> {
+        let mut project = self.project()?;
+        project.set_many(variants)?;
+        project.run_with_timeout(command, timeout)
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_dir_all(&self.root) {
+            log::warn!(
+                "could not remove workspace '{}': {}",
+                self.root.to_string_lossy(),
+                err
+            );
+        }
+    }
+}
+
+fn copy_tree(source: &Path, destination: &Path, use_gitignore: bool) -> anyhow::Result<()> {
+    let walk = WalkBuilder::new(source)
+        .hidden(false)
+        .git_ignore(use_gitignore)
+        .require_git(false)
+        .git_exclude(use_gitignore)
+        .git_global(use_gitignore)
+        .filter_entry(|entry| {
+            entry.depth() == 0
+                || !SKIPPED_DIRS
+                    .iter()
+                    .any(|skipped| entry.file_name() == std::ffi::OsStr::new(skipped))
+        })
+        .build();
+
+    for entry in walk {
+        let entry = entry?;
+        let relative = entry
+            .path()
+            .strip_prefix(source)
+            .context("walked outside of the project root")?;
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+        let target = destination.join(relative);
+        let Some(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(entry.path(), &target)?;
+        } else {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(entry.path(), &target).with_context(|| {
+                format!(
+                    "could not copy '{}' into workspace",
+                    entry.path().to_string_lossy()
+                )
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(source: &Path, target: &Path) -> anyhow::Result<()> {
+    let link = std::fs::read_link(source)?;
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::os::unix::fs::symlink(link, target)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(source: &Path, target: &Path) -> anyhow::Result<()> {
+    if source.is_file() {
+        std::fs::copy(source, target)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_isolates_mutations() {
+        let pid = std::process::id();
+        let root = std::env::temp_dir().join(format!("marauders_workspace_test_{pid}"));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+
+        let source = r#"fn calc(a: i32, b: i32) -> i32 {
+    /*| add */
+    a + b
+    /*|| add_1 */
+    /*|
+    a - b
+    */
+    /* |*/
+}
+"#;
+        std::fs::write(root.join("src/calc.rs"), source).unwrap();
+        std::fs::write(root.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        std::fs::write(root.join("src/calc.py"), "x = 1\n").unwrap();
+
+        let project = Project::with_pattern(&root, Some("*.rs")).unwrap();
+        let workspace = Workspace::new(&project).unwrap();
+        let workspace_root = workspace.root().to_path_buf();
+
+        assert!(workspace_root.join("src/calc.rs").is_file());
+        assert!(!workspace_root.join(".git").exists());
+        // The copy keeps the pattern of the original project
+        let files: Vec<_> = workspace
+            .project()
+            .unwrap()
+            .files
+            .into_iter()
+            .map(|file| file.path)
+            .collect();
+        assert_eq!(files, vec![workspace_root.join("src/calc.rs")]);
+
+        let output = workspace
+            .run(&vec!["add_1".to_string()], "cat src/calc.rs")
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("/*|\n    a + b\n    */"));
+
+        // The original tree is never touched.
+        assert_eq!(
+            std::fs::read_to_string(root.join("src/calc.rs")).unwrap(),
+            source
+        );
+
+        drop(workspace);
+        assert!(!workspace_root.exists());
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
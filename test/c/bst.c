@@ -0,0 +1,102 @@
+#include <stdlib.h>
+
+struct tree {
+    int key;
+    int value;
+    struct tree *left;
+    struct tree *right;
+};
+
+struct tree *node(struct tree *l, int k, int v, struct tree *r) {
+    struct tree *t = malloc(sizeof(struct tree));
+    t->key = k;
+    t->value = v;
+    t->left = l;
+    t->right = r;
+    return t;
+}
+
+// Join
+struct tree *join(struct tree *l, struct tree *r) {
+    if (l == NULL) {
+        return r;
+    }
+    if (r == NULL) {
+        return l;
+    }
+    return node(l->left, l->key, l->value, node(join(l->right, r->left), r->key, r->value, r->right));
+}
+
+// Insert
+struct tree *insert(int k, int v, struct tree *t) {
+    if (t == NULL) {
+        return node(NULL, k, v, NULL);
+    }
+    /*| insert */
+    if (k < t->key) {
+        return node(insert(k, v, t->left), t->key, t->value, t->right);
+    } else if (t->key < k) {
+        return node(t->left, t->key, t->value, insert(k, v, t->right));
+    } else {
+        return node(t->left, t->key, v, t->right);
+    }
+    /*|| insert_1 */
+    /*|
+    return node(NULL, k, v, NULL);
+    */
+    /*|| insert_2 */
+    /*|
+    if (k < t->key) {
+        return node(insert(k, v, t->left), t->key, t->value, t->right);
+    } else {
+        return node(t->left, t->key, v, t->right);
+    }
+    */
+    /*|| insert_3 */
+    /*|
+    if (k < t->key) {
+        return node(insert(k, v, t->left), t->key, t->value, t->right);
+    } else if (t->key < k) {
+        return node(t->left, t->key, t->value, insert(k, v, t->right));
+    } else {
+        return node(t->left, t->key, t->value, t->right);
+    }
+    */
+    /* |*/
+}
+
+// Delete
+struct tree *delete(int k, struct tree *t) {
+    if (t == NULL) {
+        return NULL;
+    }
+    /*| delete */
+    if (k < t->key) {
+        return node(delete(k, t->left), t->key, t->value, t->right);
+    } else if (t->key < k) {
+        return node(t->left, t->key, t->value, delete(k, t->right));
+    } else {
+        return join(t->left, t->right);
+    }
+    /*|| delete_4 */
+    /*|
+    if (k < t->key) {
+        return delete(k, t->left);
+    } else if (t->key < k) {
+        return delete(k, t->right);
+    } else {
+        return join(t->left, t->right);
+    }
+    */
+    /*|| delete_5 */
+    /*|
+    if (t->key < k) {
+        return node(delete(k, t->left), t->key, t->value, t->right);
+    } else if (k < t->key) {
+        return node(t->left, t->key, t->value, delete(k, t->right));
+    } else {
+        return join(t->left, t->right);
+    }
+    */
+    /* |*/
+}
@@ -160,7 +160,7 @@ function [rec] (BST) insert(KEY key, VALUE value, BST tree) {
     Leaf {} => { Node { data: { key: key, value: value },
                         smaller: Leaf {}, larger: Leaf {} } }
     Node { data: data, smaller: smaller, larger: larger } => {
-      //! //
+      //!
       if (data.key == key) {
         Node { data: { key: key, value: value },
                smaller: smaller, larger: larger }
@@ -173,13 +173,13 @@ function [rec] (BST) insert(KEY key, VALUE value, BST tree) {
                  smaller: insert(key,value,smaller), larger: larger }
         }
       }
-      //!! insert_1_spec //
-      //! Node { data: { key: key, value: value }, smaller: Leaf {}, larger: Leaf {} } //
-      //!! insert_2_spec //
-      //! if (data.key < key) { Node { data: data, smaller: smaller, larger: insert(key,value,larger) } } else { Node { data: { key: key, value: value }, smaller: smaller, larger: larger } } //
-      //!! insert_3_spec //
-      //! if (data.key == key) { Node { data: { key: key, value: data.value }, smaller: smaller, larger: larger } } else { if (data.key < key) { Node { data: data, smaller: smaller, larger: insert(key,value,larger) } } else { Node { data: data, smaller: insert(key,value,smaller), larger: larger } } } //
-      // !//
+      //!! insert_1_spec
+      //! Node { data: { key: key, value: value }, smaller: Leaf {}, larger: Leaf {} }
+      //!! insert_2_spec
+      //! if (data.key < key) { Node { data: data, smaller: smaller, larger: insert(key,value,larger) } } else { Node { data: { key: key, value: value }, smaller: smaller, larger: larger } }
+      //!! insert_3_spec
+      //! if (data.key == key) { Node { data: { key: key, value: data.value }, smaller: smaller, larger: larger } } else { if (data.key < key) { Node { data: data, smaller: smaller, larger: insert(key,value,larger) } } else { Node { data: data, smaller: insert(key,value,smaller), larger: larger } } }
+      // !
     }
   }
 }
@@ -220,25 +220,27 @@ function [rec] (BST) delKey(KEY key, BST root) {
     Node { data: data, smaller: smaller, larger: larger } => {
       if (key == data.key) {
         let res = delLeast(larger);
-        //! //
+        //!
         if (res.empty) {
-        //!! delete_empty_vs_singleton //
-        //! if (isLeaf(res.tree)) { //
+        //!! delete_empty_vs_singleton
+        //! if (isLeaf(res.tree)) {
+        // !
           smaller
         } else {
           Node { data: res.data, smaller: smaller, larger: res.tree }
         }
       } else {
-        //! //
+        //!
         if (key < data.key) {
           Node { data: data, smaller: delKey(key, smaller), larger: larger }
         } else {
           Node { data: data, smaller: smaller, larger: delKey(key, larger) }
         }
-        //!! delete_4_spec //
-        //! if (key < data.key) { delKey(key, smaller) } else { delKey(key, larger) } //
-        //!! delete_5_spec //
-        //! if (key > data.key) { Node { data: data, smaller: delKey(key, smaller), larger: larger } } else { Node { data: data, smaller: smaller, larger: delKey(key, larger) } } //
+        //!! delete_4_spec
+        //! if (key < data.key) { delKey(key, smaller) } else { delKey(key, larger) }
+        //!! delete_5_spec
+        //! if (key > data.key) { Node { data: data, smaller: delKey(key, smaller), larger: larger } } else { Node { data: data, smaller: smaller, larger: delKey(key, larger) } }
+        // !
       }
     }
   }
@@ -437,10 +439,11 @@ ensures
         parent = cur;
         cur = k < key ? cur->larger : cur->smaller;
     }
-    //! //
+    //!
     *node = cur;
-    //!! forget_last_node_cur //
-    //! //
+    //!! forget_last_node_cur
+    //!
+    // !
     return parent;
 }
 
@@ -473,19 +476,21 @@ ensures
   new_tree == insert(key, value, tree);
 @*/
 {
-    //! //
-    //!! insert_1_impl //
-    //! (*root)->key = key; (*root)->value = value; deleteTree((*root)->smaller); deleteTree((*root)->larger); (*root)->smaller = 0; (*root)->larger = 0; return; //
+    //!
+    //!! insert_1_impl
+    //! (*root)->key = key; (*root)->value = value; deleteTree((*root)->smaller); deleteTree((*root)->larger); (*root)->smaller = 0; (*root)->larger = 0; return;
+    // !
 
     struct MapNode *found = *root;
     struct MapNode *parent = findParent(&found, key);
 
     if (found)
     {
-        //! //
+        //!
         found->value = value;
-        //!! insert_3_impl //
-        //! //
+        //!! insert_3_impl
+        //!
+        // !
         return;
     }
 
@@ -496,14 +501,15 @@ ensures
     }
 
     struct MapNode *new_node = newNode(key, value);
-    //! //
+    //!
     if (parent->key < key)
     {
         parent->larger = new_node;
     }
     else
-    //!! insert_2_impl //
-    //! //
+    //!! insert_2_impl
+    //!
+    // !
     {
         parent->smaller = new_node;
     }
@@ -551,13 +557,14 @@ struct MapNode *deleteSmallest(struct MapNode **root)
     {
         parent->smaller = cur->larger;
     }
-    //! //
+    //!
     else
     {
         *root = cur->larger;
     }
-    //!! forget_to_update_root //
-    //! //
+    //!! forget_to_update_root
+    //!
+    // !
 
     return cur;
 }
@@ -589,18 +596,20 @@ ensures
     else
     {
         remove = found;
-        //! //
+        //!
         if (!parent)
         {
-            //!! always_update_root_instead_of_parent //
-            //! if (1) { //
+        //!! always_update_root_instead_of_parent
+        //! if (1) {
+        // !
             *root = found->smaller;
-            //! //
+            //!
         }
         else if (key < parent->key)
         {
-            //!! always_assign_smaller //
-            //! } else if (1) { //
+            //!! always_assign_smaller
+            //! } else if (1) {
+            // !
             parent->smaller = found->smaller;
         }
         else if (key > parent->key)
@@ -0,0 +1,100 @@
+#include <memory>
+
+struct Tree;
+using Ptr = std::shared_ptr<const Tree>;
+
+struct Tree {
+    Ptr left;
+    int key;
+    int value;
+    Ptr right;
+};
+
+Ptr node(Ptr l, int k, int v, Ptr r) {
+    return std::make_shared<const Tree>(Tree{l, k, v, r});
+}
+
+// Join
+Ptr join(Ptr l, Ptr r) {
+    if (!l) {
+        return r;
+    }
+    if (!r) {
+        return l;
+    }
+    return node(l->left, l->key, l->value, node(join(l->right, r->left), r->key, r->value, r->right));
+}
+
+// Insert
+Ptr insert(int k, int v, Ptr t) {
+    if (!t) {
+        return node(nullptr, k, v, nullptr);
+    }
+    /*| insert */
+    if (k < t->key) {
+        return node(insert(k, v, t->left), t->key, t->value, t->right);
+    } else if (t->key < k) {
+        return node(t->left, t->key, t->value, insert(k, v, t->right));
+    } else {
+        return node(t->left, t->key, v, t->right);
+    }
+    /*|| insert_1 */
+    /*|
+    return node(nullptr, k, v, nullptr);
+    */
+    /*|| insert_2 */
+    /*|
+    if (k < t->key) {
+        return node(insert(k, v, t->left), t->key, t->value, t->right);
+    } else {
+        return node(t->left, t->key, v, t->right);
+    }
+    */
+    /*|| insert_3 */
+    /*|
+    if (k < t->key) {
+        return node(insert(k, v, t->left), t->key, t->value, t->right);
+    } else if (t->key < k) {
+        return node(t->left, t->key, t->value, insert(k, v, t->right));
+    } else {
+        return node(t->left, t->key, t->value, t->right);
+    }
+    */
+    /* |*/
+}
+
+// Delete
+Ptr remove(int k, Ptr t) {
+    if (!t) {
+        return nullptr;
+    }
+    /*| delete */
+    if (k < t->key) {
+        return node(remove(k, t->left), t->key, t->value, t->right);
+    } else if (t->key < k) {
+        return node(t->left, t->key, t->value, remove(k, t->right));
+    } else {
+        return join(t->left, t->right);
+    }
+    /*|| delete_4 */
+    /*|
+    if (k < t->key) {
+        return remove(k, t->left);
+    } else if (t->key < k) {
+        return remove(k, t->right);
+    } else {
+        return join(t->left, t->right);
+    }
+    */
+    /*|| delete_5 */
+    /*|
+    if (t->key < k) {
+        return node(remove(k, t->left), t->key, t->value, t->right);
+    } else if (k < t->key) {
+        return node(t->left, t->key, t->value, remove(k, t->right));
+    } else {
+        return join(t->left, t->right);
+    }
+    */
+    /* |*/
+}
@@ -0,0 +1,97 @@
+package bst
+
+type Tree struct {
+	Left  *Tree
+	Key   int
+	Value int
+	Right *Tree
+}
+
+func node(l *Tree, k int, v int, r *Tree) *Tree {
+	return &Tree{l, k, v, r}
+}
+
+// Join
+func Join(l *Tree, r *Tree) *Tree {
+	if l == nil {
+		return r
+	}
+	if r == nil {
+		return l
+	}
+	return node(l.Left, l.Key, l.Value, node(Join(l.Right, r.Left), r.Key, r.Value, r.Right))
+}
+
+// Insert
+func Insert(k int, v int, t *Tree) *Tree {
+	if t == nil {
+		return node(nil, k, v, nil)
+	}
+	/*| insert */
+	if k < t.Key {
+		return node(Insert(k, v, t.Left), t.Key, t.Value, t.Right)
+	} else if t.Key < k {
+		return node(t.Left, t.Key, t.Value, Insert(k, v, t.Right))
+	} else {
+		return node(t.Left, t.Key, v, t.Right)
+	}
+	/*|| insert_1 */
+	/*|
+	return node(nil, k, v, nil)
+	*/
+	/*|| insert_2 */
+	/*|
+	if k < t.Key {
+		return node(Insert(k, v, t.Left), t.Key, t.Value, t.Right)
+	} else {
+		return node(t.Left, t.Key, v, t.Right)
+	}
+	*/
+	/*|| insert_3 */
+	/*|
+	if k < t.Key {
+		return node(Insert(k, v, t.Left), t.Key, t.Value, t.Right)
+	} else if t.Key < k {
+		return node(t.Left, t.Key, t.Value, Insert(k, v, t.Right))
+	} else {
+		return node(t.Left, t.Key, t.Value, t.Right)
+	}
+	*/
+	/* |*/
+}
+
+// Delete
+func Delete(k int, t *Tree) *Tree {
+	if t == nil {
+		return nil
+	}
+	/*| delete */
+	if k < t.Key {
+		return node(Delete(k, t.Left), t.Key, t.Value, t.Right)
+	} else if t.Key < k {
+		return node(t.Left, t.Key, t.Value, Delete(k, t.Right))
+	} else {
+		return Join(t.Left, t.Right)
+	}
+	/*|| delete_4 */
+	/*|
+	if k < t.Key {
+		return Delete(k, t.Left)
+	} else if t.Key < k {
+		return Delete(k, t.Right)
+	} else {
+		return Join(t.Left, t.Right)
+	}
+	*/
+	/*|| delete_5 */
+	/*|
+	if t.Key < k {
+		return node(Delete(k, t.Left), t.Key, t.Value, t.Right)
+	} else if k < t.Key {
+		return node(t.Left, t.Key, t.Value, Delete(k, t.Right))
+	} else {
+		return Join(t.Left, t.Right)
+	}
+	*/
+	/* |*/
+}
@@ -0,0 +1,88 @@
+public final class BST {
+    public record Tree(Tree left, int key, int value, Tree right) {}
+
+    // Join
+    static Tree join(Tree l, Tree r) {
+        if (l == null) {
+            return r;
+        }
+        if (r == null) {
+            return l;
+        }
+        return new Tree(l.left(), l.key(), l.value(), new Tree(join(l.right(), r.left()), r.key(), r.value(), r.right()));
+    }
+
+    // Insert
+    static Tree insert(int k, int v, Tree t) {
+        if (t == null) {
+            return new Tree(null, k, v, null);
+        }
+        /*| insert */
+        if (k < t.key()) {
+            return new Tree(insert(k, v, t.left()), t.key(), t.value(), t.right());
+        } else if (t.key() < k) {
+            return new Tree(t.left(), t.key(), t.value(), insert(k, v, t.right()));
+        } else {
+            return new Tree(t.left(), t.key(), v, t.right());
+        }
+        /*|| insert_1 */
+        /*|
+        return new Tree(null, k, v, null);
+        */
+        /*|| insert_2 */
+        /*|
+        if (k < t.key()) {
+            return new Tree(insert(k, v, t.left()), t.key(), t.value(), t.right());
+        } else {
+            return new Tree(t.left(), t.key(), v, t.right());
+        }
+        */
+        /*|| insert_3 */
+        /*|
+        if (k < t.key()) {
+            return new Tree(insert(k, v, t.left()), t.key(), t.value(), t.right());
+        } else if (t.key() < k) {
+            return new Tree(t.left(), t.key(), t.value(), insert(k, v, t.right()));
+        } else {
+            return new Tree(t.left(), t.key(), t.value(), t.right());
+        }
+        */
+        /* |*/
+    }
+
+    // Delete
+    static Tree delete(int k, Tree t) {
+        if (t == null) {
+            return null;
+        }
+        /*| delete */
+        if (k < t.key()) {
+            return new Tree(delete(k, t.left()), t.key(), t.value(), t.right());
+        } else if (t.key() < k) {
+            return new Tree(t.left(), t.key(), t.value(), delete(k, t.right()));
+        } else {
+            return join(t.left(), t.right());
+        }
+        /*|| delete_4 */
+        /*|
+        if (k < t.key()) {
+            return delete(k, t.left());
+        } else if (t.key() < k) {
+            return delete(k, t.right());
+        } else {
+            return join(t.left(), t.right());
+        }
+        */
+        /*|| delete_5 */
+        /*|
+        if (t.key() < k) {
+            return new Tree(delete(k, t.left()), t.key(), t.value(), t.right());
+        } else if (k < t.key()) {
+            return new Tree(t.left(), t.key(), t.value(), delete(k, t.right()));
+        } else {
+            return join(t.left(), t.right());
+        }
+        */
+        /* |*/
+    }
+}
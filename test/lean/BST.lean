@@ -0,0 +1,61 @@
+/-! Binary search trees with mutants in `insert` and `delete` -/
+
+inductive Tree where
+  | E : Tree
+  | T : Tree → Nat → Nat → Tree → Tree
+
+open Tree
+
+-- Join
+def join : Tree → Tree → Tree
+  | E, r => r
+  | l, E => l
+  | T l1 k1 v1 r1, T l2 k2 v2 r2 => T l1 k1 v1 (T (join r1 l2) k2 v2 r2)
+termination_by l r => sizeOf l + sizeOf r
+
+-- Insert
+def insert (k v : Nat) : Tree → Tree
+  | E => T E k v E
+  | T l k' v' r =>
+    /-| insert -/
+    if k < k' then T (insert k v l) k' v' r
+    else if k' < k then T l k' v' (insert k v r)
+    else T l k' v r
+    /-|| insert_1 -/
+    /-|
+    T E k v E
+    -/
+    /-|| insert_2 -/
+    /-|
+    if k < k' then T (insert k v l) k' v' r
+    else T l k' v r
+    -/
+    /-|| insert_3 -/
+    /-|
+    if k < k' then T (insert k v l) k' v' r
+    else if k' < k then T l k' v' (insert k v r)
+    else T l k' v' r
+    -/
+    /- |-/
+
+-- Delete
+def delete (k : Nat) : Tree → Tree
+  | E => E
+  | T l k' v' r =>
+    /-| delete -/
+    if k < k' then T (delete k l) k' v' r
+    else if k' < k then T l k' v' (delete k r)
+    else join l r
+    /-|| delete_4 -/
+    /-|
+    if k < k' then delete k l
+    else if k' < k then delete k r
+    else join l r
+    -/
+    /-|| delete_5 -/
+    /-|
+    if k' < k then T (delete k l) k' v' r
+    else if k < k' then T l k' v' (delete k r)
+    else join l r
+    -/
+    /- |-/
@@ -0,0 +1,88 @@
+export type Tree = { left: Tree; key: number; value: number; right: Tree } | null;
+
+const node = (left: Tree, key: number, value: number, right: Tree): Tree => ({ left, key, value, right });
+
+// Join
+export function join(l: Tree, r: Tree): Tree {
+  if (l === null) {
+    return r;
+  }
+  if (r === null) {
+    return l;
+  }
+  return node(l.left, l.key, l.value, node(join(l.right, r.left), r.key, r.value, r.right));
+}
+
+// Insert
+export function insert(k: number, v: number, t: Tree): Tree {
+  if (t === null) {
+    return node(null, k, v, null);
+  }
+  /*| insert */
+  if (k < t.key) {
+    return node(insert(k, v, t.left), t.key, t.value, t.right);
+  } else if (t.key < k) {
+    return node(t.left, t.key, t.value, insert(k, v, t.right));
+  } else {
+    return node(t.left, t.key, v, t.right);
+  }
+  /*|| insert_1 */
+  /*|
+  return node(null, k, v, null);
+  */
+  /*|| insert_2 */
+  /*|
+  if (k < t.key) {
+    return node(insert(k, v, t.left), t.key, t.value, t.right);
+  } else {
+    return node(t.left, t.key, v, t.right);
+  }
+  */
+  /*|| insert_3 */
+  /*|
+  if (k < t.key) {
+    return node(insert(k, v, t.left), t.key, t.value, t.right);
+  } else if (t.key < k) {
+    return node(t.left, t.key, t.value, insert(k, v, t.right));
+  } else {
+    return node(t.left, t.key, t.value, t.right);
+  }
+  */
+  /* |*/
+}
+
+// Delete
+export function remove(k: number, t: Tree): Tree {
+  if (t === null) {
+    return null;
+  }
+  /*| delete */
+  if (k < t.key) {
+    return node(remove(k, t.left), t.key, t.value, t.right);
+  } else if (t.key < k) {
+    return node(t.left, t.key, t.value, remove(k, t.right));
+  } else {
+    return join(t.left, t.right);
+  }
+  /*|| delete_4 */
+  /*|
+  if (k < t.key) {
+    return remove(k, t.left);
+  } else if (t.key < k) {
+    return remove(k, t.right);
+  } else {
+    return join(t.left, t.right);
+  }
+  */
+  /*|| delete_5 */
+  /*|
+  if (t.key < k) {
+    return node(remove(k, t.left), t.key, t.value, t.right);
+  } else if (k < t.key) {
+    return node(t.left, t.key, t.value, remove(k, t.right));
+  } else {
+    return join(t.left, t.right);
+  }
+  */
+  /* |*/
+}